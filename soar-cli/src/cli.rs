@@ -1,4 +1,5 @@
-use clap::{ArgAction, Parser, Subcommand, ValueHint};
+use clap::{ArgAction, Parser, Subcommand, ValueEnum, ValueHint};
+use soar_core::utils::parse_size;
 
 use crate::utils::parse_default_repos_arg;
 
@@ -39,6 +40,14 @@ pub struct Args {
     #[arg(short, long, global = true)]
     pub config: Option<String>,
 
+    /// Use the system-wide installation
+    #[arg(long, global = true)]
+    pub system: bool,
+
+    /// Wait for other soar processes modifying the store to finish
+    #[arg(long, global = true)]
+    pub wait: bool,
+
     /// Set proxy
     #[arg(required = false, long, short = 'P', global = true)]
     pub proxy: Option<String>,
@@ -63,6 +72,101 @@ pub enum SelfAction {
     Uninstall,
 }
 
+#[derive(Subcommand)]
+pub enum ProfileAction {
+    /// Add a new profile
+    #[command(arg_required_else_help = true)]
+    Add {
+        /// Name of the profile
+        #[arg(required = true)]
+        name: String,
+
+        /// Root directory of the profile
+        #[arg(required = true, long)]
+        root: String,
+
+        /// Directory where packages are stored
+        #[arg(required = false, long)]
+        packages_path: Option<String>,
+    },
+    /// List profiles
+    List,
+    /// Set the default profile
+    #[command(arg_required_else_help = true)]
+    Use {
+        /// Name of the profile
+        #[arg(required = true)]
+        name: String,
+    },
+    /// Remove a profile
+    #[command(arg_required_else_help = true)]
+    Remove {
+        /// Name of the profile
+        #[arg(required = true)]
+        name: String,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum HookShell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum SortBy {
+    /// Most downloaded first
+    Downloads,
+    /// Smallest first
+    Size,
+    /// Alphabetically by package name
+    Name,
+}
+
+#[derive(clap::Args, Clone, Default)]
+pub struct PackageFilters {
+    /// Only show packages in the category
+    #[arg(required = false, long)]
+    pub category: Option<String>,
+
+    /// Only show packages with the tag
+    #[arg(required = false, long)]
+    pub tag: Option<String>,
+
+    /// Only show packages of the type (e.g. appimage, static)
+    #[arg(required = false, long = "type")]
+    pub pkg_type: Option<String>,
+
+    /// Only show packages with the license
+    #[arg(required = false, long)]
+    pub license: Option<String>,
+
+    /// Only show packages maintained by the maintainer
+    #[arg(required = false, long)]
+    pub maintainer: Option<String>,
+
+    /// Only show packages not larger than the size (e.g. 500K, 20M, 1G)
+    #[arg(
+        required = false,
+        long,
+        value_parser = |size: &str| parse_size(size).ok_or(format!("invalid size '{}'", size))
+    )]
+    pub max_size: Option<u64>,
+
+    /// Only show installed packages
+    #[arg(required = false, long, conflicts_with = "not_installed")]
+    pub installed: bool,
+
+    /// Only show packages that are not installed
+    #[arg(required = false, long)]
+    pub not_installed: bool,
+
+    /// Sort the results
+    #[arg(required = false, long, value_enum)]
+    pub sort: Option<SortBy>,
+}
+
 #[derive(Subcommand)]
 pub enum Commands {
     /// Print the configuration file to stdout
@@ -115,6 +219,13 @@ pub enum Commands {
         #[arg(required = false, long)]
         binary_only: bool,
 
+        /// Extract AppImages and run them without FUSE
+        ///
+        /// Note: This is the default when /dev/fuse is unavailable. Extracted
+        /// AppImages run without APPIMAGE set, so they can't update themselves
+        #[arg(required = false, long)]
+        extract: bool,
+
         /// Ask for confirmation before installation
         #[arg(required = false, long, short)]
         ask: bool,
@@ -135,6 +246,9 @@ pub enum Commands {
         /// Limit number of result
         #[arg(required = false, long)]
         limit: Option<usize>,
+
+        #[command(flatten)]
+        filters: PackageFilters,
     },
 
     /// Query package info
@@ -144,6 +258,14 @@ pub enum Commands {
         /// Package to query
         #[arg(required = true)]
         query: String,
+
+        /// Query installed packages, including their provenance
+        #[arg(required = false, long)]
+        installed: bool,
+
+        /// Export provenance of the installed packages as JSON to the file
+        #[arg(required = false, long, requires = "installed")]
+        export: Option<String>,
     },
 
     /// Remove packages
@@ -193,6 +315,9 @@ pub enum Commands {
         /// Which repository to get the packages from
         #[arg(required = false)]
         repo_name: Option<String>,
+
+        #[command(flatten)]
+        filters: PackageFilters,
     },
 
     /// Inspect package build log
@@ -213,6 +338,159 @@ pub enum Commands {
         package: String,
     },
 
+    /// Find packages providing a binary
+    #[command(arg_required_else_help = true)]
+    #[clap(name = "provides", visible_alias = "whatprovides")]
+    Provides {
+        /// Binary to look up
+        #[arg(required = true)]
+        binary: String,
+
+        /// Arguments of the missing command, used with --command-not-found
+        #[arg(
+            required = false,
+            trailing_var_arg = true,
+            allow_hyphen_values = true,
+            hide = true
+        )]
+        args: Vec<String>,
+
+        /// Handle a missing command, for use in shell hooks
+        #[arg(required = false, long, hide = true)]
+        command_not_found: bool,
+    },
+
+    /// Find the installed package owning a file
+    #[command(arg_required_else_help = true)]
+    #[clap(name = "owns")]
+    Owns {
+        /// Path of the file
+        #[arg(required = true)]
+        path: String,
+    },
+
+    /// List files installed by a package
+    #[command(arg_required_else_help = true)]
+    #[clap(name = "files")]
+    Files {
+        /// Package to list files for
+        #[arg(required = true)]
+        package: String,
+    },
+
+    /// Verify integrity of installed packages
+    #[clap(name = "verify")]
+    Verify {
+        /// Package to verify, all installed packages if omitted
+        #[arg(required = false)]
+        package: Option<String>,
+    },
+
+    /// Restore missing or corrupted files and links of installed packages
+    #[clap(name = "repair")]
+    Repair {
+        /// Packages to repair, all installed packages if omitted
+        #[arg(required = false)]
+        packages: Option<Vec<String>>,
+
+        /// Ask for confirmation before repair
+        #[arg(required = false, long, short)]
+        ask: bool,
+    },
+
+    /// Set the package as the default application for MIME types
+    #[command(arg_required_else_help = true)]
+    #[clap(name = "default")]
+    Default {
+        /// Installed package to set as default
+        #[arg(required = true)]
+        package: String,
+
+        /// MIME type to handle, all types of the desktop file if omitted
+        #[arg(required = false, long, value_name = "MIME")]
+        mime: Vec<String>,
+    },
+
+    /// Configure the sandbox the package binaries run in
+    #[command(arg_required_else_help = true)]
+    #[clap(name = "sandbox")]
+    Sandbox {
+        /// Installed package to sandbox
+        #[arg(required = true)]
+        package: String,
+
+        /// Allow reading and writing the path
+        #[arg(required = false, long, value_name = "PATH", value_hint = ValueHint::AnyPath)]
+        allow: Vec<String>,
+
+        /// Allow reading the path
+        #[arg(required = false, long, value_name = "PATH", value_hint = ValueHint::AnyPath)]
+        allow_ro: Vec<String>,
+
+        /// Remove the path from the allowed paths
+        #[arg(required = false, long, value_name = "PATH", value_hint = ValueHint::AnyPath)]
+        deny: Vec<String>,
+
+        /// Allow network access
+        #[arg(required = false, long, conflicts_with = "no_network")]
+        network: bool,
+
+        /// Deny network access
+        #[arg(required = false, long)]
+        no_network: bool,
+
+        /// Use a separate home, config and share dir for the package
+        #[arg(required = false, long, conflicts_with = "share_home")]
+        isolate_home: bool,
+
+        /// Allow access to the user's home
+        #[arg(required = false, long)]
+        share_home: bool,
+
+        /// Remove the sandbox of the package
+        #[arg(
+            required = false,
+            long,
+            conflicts_with_all = ["allow", "allow_ro", "deny", "network", "no_network", "isolate_home", "share_home"]
+        )]
+        disable: bool,
+    },
+
+    /// Run a binary in the sandbox, used by the sandbox launchers
+    #[clap(name = "sandbox-exec", hide = true)]
+    SandboxExec {
+        #[arg(required = true, long)]
+        package_dir: String,
+
+        #[arg(required = false, long)]
+        allow: Vec<String>,
+
+        #[arg(required = false, long)]
+        allow_ro: Vec<String>,
+
+        #[arg(required = false, long)]
+        no_network: bool,
+
+        #[arg(required = false, long)]
+        home: Option<String>,
+
+        #[arg(required = false, long)]
+        argv0: Option<String>,
+
+        /// Binary to run, followed by its arguments
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        command: Vec<String>,
+    },
+
+    /// Print shell hook suggesting packages for missing commands
+    #[command(arg_required_else_help = true)]
+    #[clap(name = "hook")]
+    Hook {
+        /// Shell to print the hook for
+        #[arg(required = true, value_enum)]
+        shell: HookShell,
+    },
+
     /// Run packages without installing to PATH
     #[command(arg_required_else_help = true)]
     #[clap(name = "run", visible_alias = "exec", visible_alias = "execute")]
@@ -221,7 +499,8 @@ pub enum Commands {
         #[arg(required = false, short, long)]
         yes: bool,
 
-        /// Command to execute
+        /// Package to run, followed by its arguments. A provided binary can be
+        /// selected with pkg:binary, or by name as the first argument
         #[arg(required = true)]
         command: Vec<String>,
 
@@ -232,6 +511,27 @@ pub enum Commands {
         /// Repo name
         #[arg(required = false, short, long)]
         repo_name: Option<String>,
+
+        /// Run from a temporary directory, without using or filling the cache
+        #[arg(required = false, long)]
+        no_cache: bool,
+    },
+
+    /// Start a shell with packages in PATH, without installing them
+    #[command(arg_required_else_help = true)]
+    #[clap(name = "shell")]
+    Shell {
+        /// Packages to add to PATH
+        #[arg(required = true)]
+        packages: Vec<String>,
+
+        /// Skip all prompts and use first
+        #[arg(required = false, short, long)]
+        yes: bool,
+
+        /// Run the command in the shell and exit
+        #[arg(required = false, long)]
+        command: Option<String>,
     },
 
     /// Use package from different family
@@ -351,6 +651,14 @@ pub enum Commands {
         broken: bool,
     },
 
+    /// Manage profiles
+    #[command(arg_required_else_help = true)]
+    #[clap(name = "profile")]
+    Profile {
+        #[clap(subcommand)]
+        action: ProfileAction,
+    },
+
     /// Modify the soar installation
     #[command(arg_required_else_help = true)]
     #[clap(name = "self")]
@@ -359,3 +667,26 @@ pub enum Commands {
         action: SelfAction,
     },
 }
+
+impl Commands {
+    /// Whether the command modifies the packages store.
+    pub fn modifies_store(&self) -> bool {
+        matches!(
+            self,
+            Commands::Install { .. }
+                | Commands::Remove { .. }
+                | Commands::Sync
+                | Commands::Update { .. }
+                | Commands::Repair { .. }
+                | Commands::Sandbox { .. }
+                | Commands::Default { .. }
+                | Commands::Use { .. }
+                | Commands::Clean { .. }
+                | Commands::Profile {
+                    action: ProfileAction::Add { .. }
+                        | ProfileAction::Use { .. }
+                        | ProfileAction::Remove { .. }
+                }
+        )
+    }
+}
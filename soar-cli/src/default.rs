@@ -0,0 +1,115 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use nu_ansi_term::Color::{Blue, Cyan, Green, Magenta};
+use soar_core::{
+    database::packages::{FilterCondition, PackageQueryBuilder},
+    desktop_entry::{DesktopEntry, MAIN_GROUP},
+    error::ErrorContext,
+    package::query::PackageQuery,
+    utils::{desktop_dir, process_dir},
+    xdg::{mimeapps_list_path, set_default_application},
+    SoarResult,
+};
+use tracing::{info, warn};
+
+use crate::{state::AppState, utils::Colored};
+
+/// Returns the desktop files of the package that are integrated, with their
+/// desktop ids.
+fn integrated_desktop_files(installed_path: &Path) -> SoarResult<Vec<(String, PathBuf)>> {
+    let desktop_dir = PathBuf::from(desktop_dir());
+    let mut desktop_files = Vec::new();
+
+    let mut collect_action = |path: &Path| -> SoarResult<()> {
+        if path.extension().is_none_or(|ext| ext != "desktop") {
+            return Ok(());
+        }
+        let desktop_id = format!(
+            "{}-soar.desktop",
+            path.file_stem().unwrap().to_string_lossy()
+        );
+        let is_linked = fs::read_link(desktop_dir.join(&desktop_id))
+            .is_ok_and(|target| target.starts_with(installed_path));
+        if is_linked {
+            desktop_files.push((desktop_id, path.to_path_buf()));
+        }
+        Ok(())
+    };
+    process_dir(installed_path, &mut collect_action)?;
+
+    Ok(desktop_files)
+}
+
+pub async fn set_default(package: &str, mime_types: Vec<String>) -> SoarResult<()> {
+    let state = AppState::new();
+    let core_db = state.core_db()?;
+
+    let query = PackageQuery::try_from(package)?;
+    let builder = query
+        .apply_filters(PackageQueryBuilder::new(core_db.clone()))
+        .where_and("is_installed", FilterCondition::Eq("1".to_string()));
+    let installed_pkgs = builder.load_installed()?.items;
+
+    let Some(pkg) = installed_pkgs.first() else {
+        warn!("Package {} is not installed.", package);
+        return Ok(());
+    };
+    if installed_pkgs.len() > 1 {
+        warn!(
+            "Multiple packages match {}, using {}#{}:{}",
+            package,
+            Colored(Blue, &pkg.pkg_name),
+            Colored(Cyan, &pkg.pkg_id),
+            Colored(Green, &pkg.repo_name)
+        );
+    }
+
+    let desktop_files = integrated_desktop_files(Path::new(&pkg.installed_path))?;
+    let Some((desktop_id, desktop_file)) = desktop_files.first() else {
+        warn!(
+            "Package {} has no integrated desktop file.",
+            Colored(Blue, &pkg.pkg_name)
+        );
+        return Ok(());
+    };
+
+    let content = fs::read_to_string(desktop_file)
+        .with_context(|| format!("reading {}", desktop_file.display()))?;
+    let entry = DesktopEntry::parse(&content)?;
+    let supported = entry.get_list(MAIN_GROUP, "MimeType");
+
+    let mime_types = if mime_types.is_empty() {
+        supported.iter().map(|mime| mime.to_string()).collect()
+    } else {
+        for mime_type in &mime_types {
+            if !supported.contains(&mime_type.as_str()) {
+                warn!(
+                    "{} doesn't declare support for {}",
+                    desktop_id,
+                    Colored(Magenta, mime_type)
+                );
+            }
+        }
+        mime_types
+    };
+
+    if mime_types.is_empty() {
+        warn!("{} doesn't declare any MIME type.", desktop_id);
+        return Ok(());
+    }
+
+    set_default_application(desktop_id, &mime_types)?;
+    for mime_type in &mime_types {
+        info!(
+            "Set {} as default for {}",
+            Colored(Blue, desktop_id),
+            Colored(Magenta, mime_type)
+        );
+    }
+    info!("Updated {}", Colored(Green, mimeapps_list_path().display()));
+
+    Ok(())
+}
@@ -0,0 +1,125 @@
+use std::{env, path::PathBuf};
+
+use nu_ansi_term::Color::{Blue, Cyan, Green, LightRed, Magenta, Red};
+use soar_core::{
+    database::{models::InstalledPackage, packages::PackageQueryBuilder},
+    error::ErrorContext,
+    package::{
+        files::{find_path_owner, get_package_files},
+        query::PackageQuery,
+    },
+    SoarResult,
+};
+use tracing::{info, warn};
+
+use crate::{state::AppState, utils::Colored};
+
+pub async fn list_package_files(package: String) -> SoarResult<()> {
+    let state = AppState::new();
+    let core_db = state.core_db()?;
+
+    let query = PackageQuery::try_from(package.as_str())?;
+    let builder = query.apply_filters(PackageQueryBuilder::new(core_db.clone()));
+    let installed_pkgs = builder.load_installed()?.items;
+
+    if installed_pkgs.is_empty() {
+        warn!("Package {} is not installed.", package);
+        return Ok(());
+    }
+
+    for installed_pkg in installed_pkgs {
+        info!(
+            "{}#{}:{} ({})",
+            Colored(Blue, &installed_pkg.pkg_name),
+            Colored(Cyan, &installed_pkg.pkg_id),
+            Colored(Green, &installed_pkg.repo_name),
+            Colored(LightRed, &installed_pkg.version)
+        );
+
+        let files = {
+            let conn = core_db.lock()?;
+            get_package_files(&conn, installed_pkg.id)?
+        };
+
+        if files.is_empty() {
+            info!("  No files recorded. Reinstall the package to record its files.");
+            continue;
+        }
+
+        for file in files {
+            let is_missing = !PathBuf::from(&file.path).exists();
+            info!(
+                pkg_name = installed_pkg.pkg_name,
+                pkg_id = installed_pkg.pkg_id,
+                path = file.path,
+                kind = %file.kind,
+                missing = is_missing,
+                "  {} {}{}",
+                Colored(Magenta, format!("{:<7}", file.kind)),
+                file.path,
+                if is_missing {
+                    Colored(Red, " [Missing]").to_string()
+                } else {
+                    String::new()
+                }
+            );
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn find_owner(path: String) -> SoarResult<bool> {
+    let state = AppState::new();
+    let core_db = state.core_db()?;
+
+    let path = PathBuf::from(&path);
+    let path = if path.is_absolute() {
+        path
+    } else {
+        env::current_dir()
+            .with_context(|| "retrieving current directory".into())?
+            .join(path)
+    };
+
+    let owner_id = {
+        let conn = core_db.lock()?;
+        find_path_owner(&conn, &path)?
+    };
+
+    let installed_pkgs = PackageQueryBuilder::new(core_db.clone())
+        .load_installed()?
+        .items;
+
+    // files created after install aren't recorded, so fall back to the install directory
+    let owner: Option<&InstalledPackage> = match owner_id {
+        Some(id) => installed_pkgs.iter().find(|pkg| pkg.id == id),
+        None => installed_pkgs
+            .iter()
+            .find(|pkg| path.starts_with(&pkg.installed_path)),
+    };
+
+    let Some(owner) = owner else {
+        info!(
+            "{} is not owned by any package",
+            Colored(Red, path.display())
+        );
+        return Ok(false);
+    };
+
+    info!(
+        path = %path.display(),
+        pkg_name = owner.pkg_name,
+        pkg_id = owner.pkg_id,
+        repo_name = owner.repo_name,
+        version = owner.version,
+        "{} is owned by {}#{}:{} ({})",
+        Colored(Blue, path.display()),
+        Colored(Blue, &owner.pkg_name),
+        Colored(Cyan, &owner.pkg_id),
+        Colored(Green, &owner.repo_name),
+        Colored(LightRed, &owner.version)
+    );
+
+    Ok(true)
+}
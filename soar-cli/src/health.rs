@@ -4,8 +4,8 @@ use nu_ansi_term::Color::{Blue, Green, Red};
 use soar_core::{
     config::get_config,
     database::packages::{FilterCondition, PackageQueryBuilder},
-    package::remove::PackageRemover,
-    utils::{desktop_dir, icons_dir, process_dir},
+    package::{files::get_package_files, remove::PackageRemover},
+    utils::{desktop_dir, fuse_available, icons_dir, metainfo_dir, mime_dir, process_dir},
     SoarResult,
 };
 use tracing::{info, warn};
@@ -23,12 +23,30 @@ pub async fn display_health() -> SoarResult<()> {
         );
     }
 
+    display_fuse_status();
+    println!();
     list_broken_packages().await?;
     println!();
+    list_missing_files()?;
+    println!();
     list_broken_symlinks()?;
     Ok(())
 }
 
+/// Reports whether AppImages can be mounted, or are extracted instead.
+fn display_fuse_status() {
+    if fuse_available() {
+        info!("FUSE is available.");
+    } else if get_config().extract_appimages() {
+        info!("FUSE is unavailable, AppImages are extracted to run without it.");
+    } else {
+        warn!(
+            "FUSE is unavailable and {} is disabled, installed AppImages won't run.",
+            Colored(Green, "appimage_extract")
+        );
+    }
+}
+
 pub async fn list_broken_packages() -> SoarResult<()> {
     let state = AppState::new();
     let core_db = state.core_db()?;
@@ -57,13 +75,63 @@ pub async fn list_broken_packages() -> SoarResult<()> {
     }
 
     info!(
-        "Broken packages can be uninstalled using command: {}",
+        "Broken packages can be repaired using command: {} or uninstalled using command: {}",
+        Colored(Green, "soar repair"),
         Colored(Green, "soar clean --broken")
     );
 
     Ok(())
 }
 
+pub fn list_missing_files() -> SoarResult<()> {
+    let state = AppState::new();
+    let core_db = state.core_db()?;
+
+    let installed_packages = PackageQueryBuilder::new(core_db.clone())
+        .where_and("is_installed", FilterCondition::Eq("1".to_string()))
+        .load_installed()?
+        .items;
+
+    let conn = core_db.lock()?;
+    let mut incomplete_packages = Vec::new();
+    for package in installed_packages {
+        let missing_files: Vec<String> = get_package_files(&conn, package.id)?
+            .into_iter()
+            .map(|file| file.path)
+            .filter(|path| !Path::new(path).exists())
+            .collect();
+
+        if !missing_files.is_empty() {
+            incomplete_packages.push((package, missing_files));
+        }
+    }
+
+    if incomplete_packages.is_empty() {
+        info!("No missing package files found.");
+        return Ok(());
+    }
+
+    info!(
+        "Packages With Missing Files ({}):",
+        incomplete_packages.len()
+    );
+
+    for (package, missing_files) in incomplete_packages {
+        info!(
+            pkg_name = package.pkg_name,
+            pkg_id = package.pkg_id,
+            "{}#{}:",
+            Colored(Blue, &package.pkg_name),
+            Colored(Blue, &package.pkg_id),
+        );
+        for path in missing_files {
+            info!("  {}", Colored(Red, path));
+        }
+    }
+
+    Ok(())
+}
+
 pub fn list_broken_symlinks() -> SoarResult<()> {
     let broken_symlinks = Rc::new(RefCell::new(Vec::new()));
 
@@ -84,9 +152,21 @@ pub fn list_broken_symlinks() -> SoarResult<()> {
         Ok(())
     };
 
+    // the stem of `<id>-soar.metainfo.xml` still has the `.metainfo` suffix
+    let mut soar_metainfo_action = |path: &Path| -> SoarResult<()> {
+        if let Some(filename) = path.file_name().and_then(|s| s.to_str()) {
+            if filename.ends_with("-soar.metainfo.xml") && !path.exists() {
+                broken_symlinks_clone.borrow_mut().push(path.to_path_buf());
+            }
+        }
+        Ok(())
+    };
+
     process_dir(&get_config().get_bin_path()?, &mut collect_action)?;
     process_dir(desktop_dir(), &mut soar_files_action)?;
     process_dir(icons_dir(), &mut soar_files_action)?;
+    process_dir(format!("{}/packages", mime_dir()), &mut soar_files_action)?;
+    process_dir(metainfo_dir(), &mut soar_metainfo_action)?;
 
     let broken_symlinks = Rc::try_unwrap(broken_symlinks)
         .unwrap_or_else(|rc| rc.borrow().clone().into())
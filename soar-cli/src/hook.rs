@@ -0,0 +1,52 @@
+use std::env;
+
+use soar_core::{error::ErrorContext, utils::shell_quote, SoarResult};
+
+use crate::cli::HookShell;
+
+const BASH_HOOK: &str = r#"command_not_found_handle() {
+    {soar} provides --command-not-found -- "$@"
+    local status=$?
+    if [ "$status" -eq 127 ]; then
+        printf 'bash: %s: command not found\n' "$1" >&2
+    fi
+    return "$status"
+}
+"#;
+
+const ZSH_HOOK: &str = r#"command_not_found_handler() {
+    {soar} provides --command-not-found -- "$@"
+    local status=$?
+    if [ "$status" -eq 127 ]; then
+        printf 'zsh: command not found: %s\n' "$1" >&2
+    fi
+    return "$status"
+}
+"#;
+
+const FISH_HOOK: &str = r#"function fish_command_not_found
+    {soar} provides --command-not-found -- $argv
+    set -l status_code $status
+    if test $status_code -eq 127
+        printf 'fish: Unknown command: %s\n' $argv[1] >&2
+    end
+    return $status_code
+end
+"#;
+
+/// Prints the command-not-found hook for the shell, to be evaluated from the
+/// shell's rc file, e.g. `eval "$(soar hook bash)"`.
+pub fn print_hook(shell: HookShell) -> SoarResult<()> {
+    // use the absolute path so the hook doesn't recurse if soar isn't in PATH
+    let soar = env::current_exe().with_context(|| "retrieving soar executable path".into())?;
+    let soar = shell_quote(soar.to_string_lossy());
+
+    let hook = match shell {
+        HookShell::Bash => BASH_HOOK,
+        HookShell::Zsh => ZSH_HOOK,
+        HookShell::Fish => FISH_HOOK,
+    };
+    print!("{}", hook.replace("{soar}", &soar));
+
+    Ok(())
+}
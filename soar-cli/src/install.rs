@@ -1,7 +1,7 @@
 use std::{
     collections::HashMap,
-    fs::{self, File},
-    io::{BufReader, Read},
+    fs::File,
+    io::BufReader,
     path::PathBuf,
     sync::{
         atomic::{AtomicU64, Ordering},
@@ -10,23 +10,26 @@ use std::{
 };
 
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
-use minisign_verify::{PublicKey, Signature};
 use nu_ansi_term::Color::{Blue, Green};
 use rand::{distr::Alphanumeric, Rng};
 use rusqlite::Connection;
 use soar_core::{
-    config::get_config,
+    config::{get_config, get_current_profile},
     database::{
         models::{InstalledPackage, Package},
         packages::{FilterCondition, PackageQueryBuilder, PaginatedResponse},
     },
     error::{ErrorContext, SoarError},
     package::{
-        formats::common::integrate_package,
+        formats::{
+            appimage::{extract_appimage, APPIMAGE_EXTRACT_DIR},
+            common::{integrate_package, symlink_shell_integration},
+            get_file_type, PackageFormat,
+        },
         install::{InstallTarget, PackageInstaller},
         query::PackageQuery,
     },
-    utils::{apply_sig_variants, calculate_checksum, default_install_patterns},
+    utils::{apply_sig_variants, default_install_patterns},
     SoarResult,
 };
 use soar_dl::downloader::DownloadState;
@@ -35,13 +38,17 @@ use tracing::{error, info, warn};
 
 use crate::{
     progress::handle_install_progress,
+    sandbox::reapply_sandbox,
     state::AppState,
     utils::{
         ask_target_action, has_desktop_integration, mangle_package_symlinks,
-        select_package_interactively, Colored,
+        select_package_interactively, verify_package_signatures, Colored, SignatureCheck,
     },
 };
 
+/// Install directory and created bin symlinks of an installed package.
+type InstalledEntry = (PathBuf, Vec<(PathBuf, PathBuf)>);
+
 #[derive(Clone)]
 pub struct InstallContext {
     pub multi_progress: Arc<MultiProgress>,
@@ -57,10 +64,12 @@ pub struct InstallContext {
     pub errors: Arc<Mutex<Vec<String>>>,
     pub retrying: Arc<AtomicU64>,
     pub failed: Arc<AtomicU64>,
-    pub installed_indices: Arc<Mutex<HashMap<usize, (PathBuf, Vec<(PathBuf, PathBuf)>)>>>,
+    pub installed_indices: Arc<Mutex<HashMap<usize, InstalledEntry>>>,
     pub binary_only: bool,
+    pub extract: bool,
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn create_install_context(
     total_packages: usize,
     parallel_limit: u32,
@@ -69,6 +78,7 @@ pub fn create_install_context(
     portable_config: Option<String>,
     portable_share: Option<String>,
     binary_only: bool,
+    extract: bool,
 ) -> InstallContext {
     let multi_progress = Arc::new(MultiProgress::new());
     let total_progress_bar = multi_progress.add(ProgressBar::new(total_packages as u64));
@@ -91,9 +101,11 @@ pub fn create_install_context(
         failed: Arc::new(AtomicU64::new(0)),
         installed_indices: Arc::new(Mutex::new(HashMap::new())),
         binary_only,
+        extract,
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn install_packages(
     packages: &[String],
     force: bool,
@@ -104,6 +116,7 @@ pub async fn install_packages(
     portable_share: Option<String>,
     no_notes: bool,
     binary_only: bool,
+    extract: bool,
     ask: bool,
 ) -> SoarResult<()> {
     let state = AppState::new();
@@ -129,6 +142,7 @@ pub async fn install_packages(
         portable_config,
         portable_share,
         binary_only,
+        extract,
     );
 
     perform_installation(install_context, install_targets, core_db.clone(), no_notes).await
@@ -413,58 +427,50 @@ pub async fn install_single_package(
     progress_callback: Arc<dyn Fn(DownloadState) + Send + Sync>,
     core_db: Arc<Mutex<Connection>>,
 ) -> SoarResult<(PathBuf, Vec<(PathBuf, PathBuf)>)> {
-    let bin_dir = get_config().get_bin_path()?;
+    let profile = target.profile.clone().unwrap_or_else(get_current_profile);
+    let bin_dir = get_config().get_profile_bin_path(&profile)?;
+
+    let (install_dir, unlinked, portable, portable_home, portable_config, portable_share, excludes) =
+        if let Some(ref existing) = target.existing_install {
+            let install_dir = PathBuf::from(&existing.installed_path);
+
+            (
+                install_dir,
+                existing.unlinked,
+                existing.portable_path.as_deref(),
+                existing.portable_home.as_deref(),
+                existing.portable_config.as_deref(),
+                existing.portable_share.as_deref(),
+                existing.install_patterns.as_deref(),
+            )
+        } else {
+            let rand_str: String = rand::rng()
+                .sample_iter(&Alphanumeric)
+                .take(12)
+                .map(char::from)
+                .collect();
+
+            let install_dir = get_config()
+                .get_packages_path(target.profile.clone())
+                .unwrap()
+                .join(format!(
+                    "{}-{}-{}",
+                    target.package.pkg_name, target.package.pkg_id, rand_str
+                ));
 
-    let (
-        install_dir,
-        real_bin,
-        unlinked,
-        portable,
-        portable_home,
-        portable_config,
-        portable_share,
-        excludes,
-    ) = if let Some(ref existing) = target.existing_install {
-        let install_dir = PathBuf::from(&existing.installed_path);
-        let real_bin = install_dir.join(&target.package.pkg_name);
-
-        (
-            install_dir,
-            real_bin,
-            existing.unlinked,
-            existing.portable_path.as_deref(),
-            existing.portable_home.as_deref(),
-            existing.portable_config.as_deref(),
-            existing.portable_share.as_deref(),
-            existing.install_patterns.as_deref(),
-        )
-    } else {
-        let rand_str: String = rand::rng()
-            .sample_iter(&Alphanumeric)
-            .take(12)
-            .map(char::from)
-            .collect();
-
-        let install_dir = get_config()
-            .get_packages_path(target.profile.clone())
-            .unwrap()
-            .join(format!(
-                "{}-{}-{}",
-                target.package.pkg_name, target.package.pkg_id, rand_str
-            ));
-        let real_bin = install_dir.join(&target.package.pkg_name);
-
-        (
-            install_dir,
-            real_bin,
-            false,
-            ctx.portable.as_deref(),
-            ctx.portable_home.as_deref(),
-            ctx.portable_config.as_deref(),
-            ctx.portable_share.as_deref(),
-            None,
-        )
-    };
+            (
+                install_dir,
+                false,
+                ctx.portable.as_deref(),
+                ctx.portable_home.as_deref(),
+                ctx.portable_config.as_deref(),
+                ctx.portable_share.as_deref(),
+                None,
+            )
+        };
+
+    // keeps reinstalls of an extracted AppImage extracted
+    let was_extracted = install_dir.join(APPIMAGE_EXTRACT_DIR).is_dir();
 
     if install_dir.exists() {
         if let Err(err) = std::fs::remove_dir_all(&install_dir) {
@@ -491,148 +497,81 @@ pub async fn install_single_package(
     });
     let install_patterns = apply_sig_variants(install_patterns);
 
-    let installer = PackageInstaller::new(
+    let mut installer = PackageInstaller::new(
         target,
         &install_dir,
         Some(progress_callback),
-        core_db,
+        core_db.clone(),
         target.with_pkg_id,
         install_patterns.to_vec(),
     )
     .await?;
 
     let downloaded_checksum = installer.download_package().await?;
-
-    if let Some(repository) = get_config().get_repository(&target.package.repo_name) {
-        if repository.signature_verification() {
-            let repository_path = repository.get_path()?;
-            let pubkey_file = repository_path.join("minisign.pub");
-            if pubkey_file.exists() {
-                let pubkey = PublicKey::from_base64(
-                    fs::read_to_string(&pubkey_file)
-                        .with_context(|| {
-                            format!("reading minisign key from {}", pubkey_file.display())
-                        })?
-                        .trim(),
-                )
-                .map_err(|err| {
-                    SoarError::Custom(format!(
-                        "Failed to load public key from {}: {}",
-                        pubkey_file.display(),
-                        err
-                    ))
-                })?;
-                let entries = fs::read_dir(&install_dir).with_context(|| {
-                    format!("reading package directory {}", install_dir.display())
-                })?;
-                for entry in entries {
-                    let path = entry
-                        .with_context(|| {
-                            format!("reading entry from directory {}", install_dir.display())
-                        })?
-                        .path();
-                    let is_signature_file =
-                        path.extension().map_or_else(|| false, |ext| ext == "sig");
-                    let original_file = path.with_extension("");
-                    if is_signature_file && path.is_file() && original_file.is_file() {
-                        let signature = Signature::from_file(&path).map_err(|err| {
-                            SoarError::Custom(format!(
-                                "Failed to load signature file from {}: {}",
-                                path.display(),
-                                err
-                            ))
-                        })?;
-                        let mut stream_verifier =
-                            pubkey.verify_stream(&signature).map_err(|err| {
-                                SoarError::Custom(format!(
-                                    "Failed to setup stream verifier: {}",
-                                    err
-                                ))
-                            })?;
-
-                        let file = File::open(&original_file).with_context(|| {
-                            format!(
-                                "opening file {} for signature verification",
-                                original_file.display()
-                            )
-                        })?;
-                        let mut buf_reader = BufReader::new(file);
-
-                        let mut buffer = [0u8; 8192];
-                        loop {
-                            match buf_reader.read(&mut buffer).with_context(|| {
-                                format!("reading to buffer from {}", original_file.display())
-                            })? {
-                                0 => break,
-                                n => {
-                                    stream_verifier.update(&buffer[..n]);
-                                }
-                            }
-                        }
-
-                        stream_verifier.finalize().map_err(|_| {
-                            SoarError::Custom(format!(
-                                "Signature verification failed for {}",
-                                original_file.display()
-                            ))
-                        })?;
-
-                        // we can safely remove the signature file
-                        fs::remove_file(&path).with_context(|| {
-                            format!("removing minisign file {}", path.display())
-                        })?;
-                    }
-                }
-            } else {
-                ctx.warnings.lock().unwrap().push(format!(
-                    "{}#{} - Signature verification skipped as no pubkey was found.",
-                    target.package.pkg_name, target.package.pkg_id
-                ))
-            }
+    // signatures are retained to record the package provenance
+    let verified_with = match verify_package_signatures(&target.package.repo_name, &install_dir)? {
+        SignatureCheck::Verified(fingerprint) => fingerprint,
+        SignatureCheck::MissingPubkey => {
+            ctx.warnings.lock().unwrap().push(format!(
+                "{}#{} - Signature verification skipped as no pubkey was found.",
+                target.package.pkg_name, target.package.pkg_id
+            ));
+            None
         }
-    }
-
-    if target.package.provides.is_some() {
-        let final_checksum = if target.package.ghcr_pkg.is_some() {
-            if real_bin.exists() {
-                Some(calculate_checksum(&real_bin)?)
-            } else {
-                None
-            }
-        } else {
-            downloaded_checksum
-        };
+        SignatureCheck::Disabled => None,
+    };
 
-        if let Some(calculated_checksum) = final_checksum {
-            if let Some(ref expected_checksum) = target.package.bsum {
-                if calculated_checksum != *expected_checksum {
-                    return Err(SoarError::Custom(format!(
-                        "{}#{} - Invalid checksum, skipped installation.",
-                        target.package.pkg_name, target.package.pkg_id
-                    )));
-                }
+    if !installer.validate_checksum(downloaded_checksum)? {
+        ctx.warnings.lock().unwrap().push(format!(
+            "{}#{} - {}. Skipped checksum validation.",
+            target.package.pkg_name,
+            target.package.pkg_id,
+            if target.package.bsum.is_some() {
+                "Checksummed file was not installed"
             } else {
-                ctx.warnings.lock().unwrap().push(format!(
-                    "{}#{} - Blake3 checksum not found. Skipped checksum validation.",
-                    target.package.pkg_name, target.package.pkg_id
-                ));
+                "Blake3 checksum not found"
             }
-        }
+        ));
     }
 
-    let symlinks =
-        mangle_package_symlinks(&install_dir, &bin_dir, target.package.provides.as_deref()).await?;
+    let symlinks = mangle_package_symlinks(
+        &install_dir,
+        &bin_dir,
+        target.package.provides.as_deref(),
+        &target.package.pkg_name,
+        &core_db,
+    )
+    .await?;
+
+    let mut created_paths: Vec<PathBuf> = symlinks.iter().map(|(_, link)| link.clone()).collect();
 
     if !unlinked || has_desktop_integration(&target.package) {
-        integrate_package(
-            &install_dir,
-            &target.package,
-            portable,
-            portable_home,
-            portable_config,
-            portable_share,
-        )
-        .await?;
+        created_paths.extend(
+            integrate_package(
+                &install_dir,
+                &bin_dir,
+                &target.package,
+                portable,
+                portable_home,
+                portable_config,
+                portable_share,
+            )
+            .await?,
+        );
+    }
+    created_paths.extend(symlink_shell_integration(&install_dir)?);
+
+    if ctx.extract || was_extracted || installer.was_extracted() || get_config().extract_appimages()
+    {
+        let bin_path = install_dir.join(&target.package.pkg_name);
+        let file =
+            File::open(&bin_path).with_context(|| format!("opening {}", bin_path.display()))?;
+        if matches!(
+            get_file_type(&mut BufReader::new(file)),
+            Ok(PackageFormat::AppImage)
+        ) {
+            extract_appimage(&install_dir, &bin_path)?;
+        }
     }
 
     installer
@@ -642,8 +581,13 @@ pub async fn install_single_package(
             portable_home,
             portable_config,
             portable_share,
+            &created_paths,
+            verified_with.as_deref(),
         )
         .await?;
 
+    // the launchers were replaced by the new bin links
+    reapply_sandbox(&core_db, &target.package, &install_dir)?;
+
     Ok((install_dir, symlinks))
 }
@@ -1,24 +1,31 @@
 use std::{
     collections::{HashMap, HashSet},
+    fs,
     path::PathBuf,
+    sync::{Arc, Mutex},
 };
 
 use indicatif::HumanBytes;
 use nu_ansi_term::Color::{Blue, Cyan, Green, LightRed, Magenta, Purple, Red, White, Yellow};
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use rusqlite::Connection;
+use serde_json::json;
 use soar_core::{
     config::get_config,
     database::{
         models::{FromRow, Package},
         packages::{FilterCondition, PackageQueryBuilder, PaginatedResponse, SortDirection},
+        search::{correct_terms, match_expression, tokenize},
     },
-    package::query::PackageQuery,
+    error::{ErrorContext, SoarError},
+    package::{provenance::get_provenance, query::PackageQuery},
     utils::calculate_dir_size,
     SoarResult,
 };
 use tracing::info;
 
 use crate::{
+    cli::{PackageFilters, SortBy},
     state::AppState,
     utils::{pretty_package_size, vec_string, Colored},
 };
@@ -52,45 +59,165 @@ impl FromRow for PackageSearchList {
     }
 }
 
+const SEARCH_COLUMNS: &[&str] = &[
+    "pkg_id",
+    "pkg_name",
+    "pkg_type",
+    "version",
+    "version_upstream",
+    "description",
+    "ghcr_size",
+    "size",
+    "download_count",
+];
+
+type InstalledMap = HashMap<(String, String, String), bool>;
+
+fn installed_packages(core_db: &Arc<Mutex<Connection>>) -> SoarResult<InstalledMap> {
+    Ok(PackageQueryBuilder::new(core_db.clone())
+        .load_installed()?
+        .items
+        .into_par_iter()
+        .map(|pkg| ((pkg.repo_name, pkg.pkg_id, pkg.pkg_name), pkg.is_installed))
+        .collect())
+}
+
+fn apply_package_filters(
+    mut builder: PackageQueryBuilder,
+    filters: &PackageFilters,
+    installed_pkgs: &InstalledMap,
+) -> PackageQueryBuilder {
+    if let Some(ref category) = filters.category {
+        builder =
+            builder.json_array_where_and("categories", FilterCondition::ILike(category.clone()));
+    }
+    if let Some(ref tag) = filters.tag {
+        builder = builder.json_array_where_and("tags", FilterCondition::ILike(tag.clone()));
+    }
+    if let Some(ref license) = filters.license {
+        builder = builder.json_array_where_and("licenses", FilterCondition::ILike(license.clone()));
+    }
+    if let Some(ref pkg_type) = filters.pkg_type {
+        builder = builder.where_and(
+            "LOWER(pkg_type)",
+            FilterCondition::Eq(pkg_type.to_lowercase()),
+        );
+    }
+    if let Some(ref maintainer) = filters.maintainer {
+        builder = builder.maintainer(FilterCondition::ILike(maintainer.clone()));
+    }
+    if let Some(max_size) = filters.max_size {
+        // the cast gives the expression integer affinity so the bound text compares as a number
+        builder = builder.where_and(
+            "CAST(COALESCE(ghcr_size, size, 0) AS INTEGER)",
+            FilterCondition::Lte(max_size.to_string()),
+        );
+    }
+
+    if filters.installed || filters.not_installed {
+        let installed: Vec<String> = installed_pkgs
+            .iter()
+            .filter(|(_, is_installed)| **is_installed)
+            .map(|((repo_name, pkg_id, pkg_name), _)| {
+                format!("{}#{}#{}", repo_name, pkg_id, pkg_name)
+            })
+            .collect();
+        let key = "r.name || '#' || p.pkg_id || '#' || p.pkg_name";
+        builder = if filters.installed {
+            builder.where_and(key, FilterCondition::In(installed))
+        } else {
+            builder.where_and(key, FilterCondition::NotIn(installed))
+        };
+    }
+
+    match filters.sort {
+        Some(SortBy::Downloads) => builder.sort_by("download_count", SortDirection::Desc),
+        Some(SortBy::Size) => builder.sort_by("COALESCE(ghcr_size, size)", SortDirection::Asc),
+        Some(SortBy::Name) => builder.sort_by("pkg_name", SortDirection::Asc),
+        None => builder,
+    }
+}
+
+fn full_text_search(
+    repo_db: &Arc<Mutex<Connection>>,
+    builder: &PackageQueryBuilder,
+    query: &str,
+) -> SoarResult<Option<PaginatedResponse<PackageSearchList>>> {
+    let terms = tokenize(query);
+    if terms.is_empty() {
+        return Ok(None);
+    }
+
+    let packages: PaginatedResponse<PackageSearchList> =
+        builder.clone().search(match_expression(&terms)).load()?;
+    if packages.total > 0 {
+        return Ok(Some(packages));
+    }
+
+    let corrected = {
+        let conn = repo_db.lock()?;
+        correct_terms(&conn, &terms)?
+    };
+    if let Some(corrected) = corrected {
+        let packages: PaginatedResponse<PackageSearchList> = builder
+            .clone()
+            .search(match_expression(&corrected))
+            .load()?;
+        if packages.total > 0 {
+            info!(
+                "No results for {}. Showing results for {}",
+                Colored(Red, query),
+                Colored(Green, corrected.join(" "))
+            );
+            return Ok(Some(packages));
+        }
+    }
+
+    Ok(None)
+}
+
 pub async fn search_packages(
     query: String,
     case_sensitive: bool,
     limit: Option<usize>,
+    filters: PackageFilters,
 ) -> SoarResult<()> {
     let state = AppState::new();
     let repo_db = state.repo_db().await?;
     let core_db = state.core_db()?;
+    let limit = limit.or(get_config().search_limit).unwrap_or(20) as u32;
 
-    let filter_condition = if case_sensitive {
-        FilterCondition::Like(query)
+    let installed_pkgs = installed_packages(core_db)?;
+    let filtered =
+        |builder: PackageQueryBuilder| apply_package_filters(builder, &filters, &installed_pkgs);
+
+    let ranked = if case_sensitive {
+        None
     } else {
-        FilterCondition::ILike(query)
+        let builder = PackageQueryBuilder::new(repo_db.clone())
+            .limit(limit)
+            .select(SEARCH_COLUMNS);
+        full_text_search(repo_db, &filtered(builder), &query)?
+    };
+
+    let packages = match ranked {
+        Some(packages) => packages,
+        None => {
+            let filter_condition = if case_sensitive {
+                FilterCondition::Like(query)
+            } else {
+                FilterCondition::ILike(query)
+            };
+            let builder = PackageQueryBuilder::new(repo_db.clone())
+                .where_or("pkg_name", filter_condition.clone())
+                .where_or("pkg_id", filter_condition.clone())
+                .where_or("pkg", filter_condition.clone())
+                .json_where_or("provides", "target_name", filter_condition.clone())
+                .limit(limit)
+                .select(SEARCH_COLUMNS);
+            filtered(builder).load()?
+        }
     };
-    let packages: PaginatedResponse<PackageSearchList> = PackageQueryBuilder::new(repo_db.clone())
-        .where_or("pkg_name", filter_condition.clone())
-        .where_or("pkg_id", filter_condition.clone())
-        .where_or("pkg", filter_condition.clone())
-        .json_where_or("provides", "target_name", filter_condition.clone())
-        .limit(limit.or(get_config().search_limit).unwrap_or(20) as u32)
-        .select(&[
-            "pkg_id",
-            "pkg_name",
-            "pkg_type",
-            "version",
-            "version_upstream",
-            "description",
-            "ghcr_size",
-            "size",
-        ])
-        .load()?;
-
-    let installed_pkgs: HashMap<(String, String, String), bool> =
-        PackageQueryBuilder::new(core_db.clone())
-            .load_installed()?
-            .items
-            .into_par_iter()
-            .map(|pkg| ((pkg.repo_name, pkg.pkg_id, pkg.pkg_name), pkg.is_installed))
-            .collect();
 
     for package in packages.items {
         let key = (
@@ -408,6 +535,174 @@ pub async fn query_package(query: String) -> SoarResult<()> {
     Ok(())
 }
 
+pub async fn query_installed_package(query: String, export: Option<String>) -> SoarResult<()> {
+    let state = AppState::new();
+    let core_db = state.core_db()?;
+
+    let query = PackageQuery::try_from(query.as_str())?;
+    let builder = query.apply_filters(PackageQueryBuilder::new(core_db.clone()));
+    let packages = builder.load_installed()?.items;
+
+    let mut records = Vec::new();
+    for package in packages {
+        let provenance = {
+            let conn = core_db.lock()?;
+            get_provenance(&conn, package.id)?
+        };
+
+        let mut fields = vec![
+            format!(
+                "\n{}: {} ({1}#{}:{})",
+                Colored(Purple, "Name"),
+                Colored(Cyan, &package.pkg_name),
+                Colored(Blue, &package.pkg_id),
+                Colored(Green, &package.repo_name),
+            ),
+            format!(
+                "{}: {}",
+                Colored(Purple, "Version"),
+                Colored(Blue, &package.version)
+            ),
+            format!(
+                "{}: {}",
+                Colored(Purple, "Installed Path"),
+                Colored(Blue, &package.installed_path)
+            ),
+            format!(
+                "{}: {}",
+                Colored(Purple, "Installed Date"),
+                Colored(Blue, &package.installed_date)
+            ),
+            package
+                .checksum
+                .as_ref()
+                .map(|cs| {
+                    format!(
+                        "{}: {} (blake3)",
+                        Colored(Purple, "Checksum"),
+                        Colored(Blue, cs)
+                    )
+                })
+                .unwrap_or_default(),
+        ];
+
+        match provenance {
+            Some(ref provenance) => {
+                fields.extend([
+                    format!("{}:", Colored(Purple, "Provenance")),
+                    format!(
+                        "  - {}: {}",
+                        Colored(Purple, "Repository"),
+                        Colored(Green, &provenance.repo_name)
+                    ),
+                    format!(
+                        "  - {}: {}",
+                        Colored(Purple, "Download URL"),
+                        Colored(Blue, &provenance.download_url)
+                    ),
+                    provenance
+                        .ghcr_digest
+                        .as_ref()
+                        .map(|digest| {
+                            format!(
+                                "  - {}: {}",
+                                Colored(Purple, "GHCR Digest"),
+                                Colored(Blue, digest)
+                            )
+                        })
+                        .unwrap_or_default(),
+                    provenance
+                        .build_action
+                        .as_ref()
+                        .map(|action| {
+                            format!(
+                                "  - {}: {}{}",
+                                Colored(Purple, "Build CI"),
+                                Colored(Blue, action),
+                                provenance
+                                    .build_id
+                                    .as_ref()
+                                    .map(|id| format!(" ({})", Colored(Yellow, id)))
+                                    .unwrap_or_default()
+                            )
+                        })
+                        .unwrap_or_default(),
+                    format!(
+                        "  - {}: {}",
+                        Colored(Purple, "Signature"),
+                        match provenance.pubkey_fingerprint {
+                            Some(ref fingerprint) if provenance.signature_verified =>
+                                Colored(Green, format!("verified with key {}", fingerprint))
+                                    .to_string(),
+                            _ => Colored(Red, "not verified").to_string(),
+                        }
+                    ),
+                ]);
+                fields.extend(
+                    provenance
+                        .signatures
+                        .iter()
+                        .map(|signature| format!("    - {}.sig", Colored(Blue, &signature.file))),
+                );
+            }
+            None => fields.push(format!(
+                "{}: {}",
+                Colored(Purple, "Provenance"),
+                Colored(Red, "not recorded")
+            )),
+        }
+
+        info!(
+            pkg_name = package.pkg_name,
+            pkg_id = package.pkg_id,
+            repo_name = package.repo_name,
+            version = package.version,
+            installed_path = package.installed_path,
+            installed_date = package.installed_date,
+            checksum = package.checksum,
+            download_url = provenance.as_ref().map(|p| p.download_url.clone()),
+            ghcr_digest = provenance.as_ref().and_then(|p| p.ghcr_digest.clone()),
+            build_id = provenance.as_ref().and_then(|p| p.build_id.clone()),
+            build_action = provenance.as_ref().and_then(|p| p.build_action.clone()),
+            pubkey_fingerprint = provenance
+                .as_ref()
+                .and_then(|p| p.pubkey_fingerprint.clone()),
+            signature_verified = provenance.as_ref().map(|p| p.signature_verified),
+            "{}",
+            fields
+                .iter()
+                .filter(|s| !s.is_empty())
+                .map(|s| s.as_str())
+                .collect::<Vec<&str>>()
+                .join("\n")
+        );
+
+        records.push(json!({
+            "pkg_name": package.pkg_name,
+            "pkg_id": package.pkg_id,
+            "repo_name": package.repo_name,
+            "version": package.version,
+            "installed_path": package.installed_path,
+            "installed_date": package.installed_date,
+            "checksum": package.checksum,
+            "provenance": provenance,
+        }));
+    }
+
+    if let Some(export) = export {
+        let content = serde_json::to_string_pretty(&records)
+            .map_err(|err| SoarError::Custom(format!("Failed to serialize provenance: {}", err)))?;
+        fs::write(&export, content).with_context(|| format!("writing provenance to {}", export))?;
+        info!(
+            "\nExported provenance of {} packages to {}",
+            records.len(),
+            Colored(Green, &export)
+        );
+    }
+
+    Ok(())
+}
+
 #[derive(Debug, Clone)]
 pub struct PackageList {
     pkg_id: String,
@@ -431,14 +726,20 @@ impl FromRow for PackageList {
     }
 }
 
-pub async fn list_packages(repo_name: Option<String>) -> SoarResult<()> {
+pub async fn list_packages(repo_name: Option<String>, filters: PackageFilters) -> SoarResult<()> {
     let state = AppState::new();
     let repo_db = state.repo_db().await?;
     let core_db = state.core_db()?;
 
-    let mut builder = PackageQueryBuilder::new(repo_db.clone())
-        .sort_by("pkg_name", SortDirection::Asc)
-        .limit(3000);
+    let installed_pkgs = installed_packages(core_db)?;
+
+    let mut builder = apply_package_filters(
+        PackageQueryBuilder::new(repo_db.clone()),
+        &filters,
+        &installed_pkgs,
+    )
+    .sort_by("pkg_name", SortDirection::Asc)
+    .limit(3000);
 
     if let Some(repo_name) = repo_name {
         builder = builder.where_and("repo_name", FilterCondition::Eq(repo_name));
@@ -450,16 +751,11 @@ pub async fn list_packages(repo_name: Option<String>) -> SoarResult<()> {
         "pkg_type",
         "version",
         "version_upstream",
+        "ghcr_size",
+        "size",
+        "download_count",
     ]);
 
-    let installed_pkgs: HashMap<(String, String, String), bool> =
-        PackageQueryBuilder::new(core_db.clone())
-            .load_installed()?
-            .items
-            .into_par_iter()
-            .map(|pkg| ((pkg.repo_name, pkg.pkg_id, pkg.pkg_name), pkg.is_installed))
-            .collect();
-
     loop {
         let packages: PaginatedResponse<PackageList> = builder.load()?;
 
@@ -2,19 +2,33 @@ use std::{env, error::Error, fs, io::Read, process::Command, sync::Arc};
 
 use clap::Parser;
 use cli::Args;
+use default::set_default;
 use download::{create_regex_patterns, download, DownloadContext};
+use files::{find_owner, list_package_files};
 use health::{display_health, remove_broken_packages};
+use hook::print_hook;
 use inspect::{inspect_log, InspectType};
 use install::install_packages;
-use list::{list_installed_packages, list_packages, query_package, search_packages};
+use list::{
+    list_installed_packages, list_packages, query_installed_package, query_package, search_packages,
+};
 use logging::setup_logging;
+use profile::process_profile_action;
 use progress::create_progress_bar;
+use provides::{find_providers, handle_command_not_found};
 use remove::remove_packages;
+use repair::repair_packages;
 use run::run_package;
+use sandbox::{configure_sandbox, exec_sandboxed, SandboxChanges, SandboxExecArgs};
 use self_actions::process_self_action;
+use shell::enter_shell;
 use soar_core::{
-    config::{self, generate_default_config, get_config, set_current_profile, Config, CONFIG_PATH},
+    config::{
+        self, enable_system_mode, generate_default_config, get_config, has_write_access,
+        set_current_profile, system_config_available, Config, CONFIG_PATH,
+    },
     error::{ErrorContext, SoarError},
+    lock::StoreLock,
     utils::{build_path, cleanup_cache, remove_broken_symlinks, setup_required_paths},
     SoarResult,
 };
@@ -24,23 +38,33 @@ use tracing::{error, info, warn};
 use update::update_packages;
 use use_package::use_alternate_package;
 use utils::COLOR;
+use verify::verify_packages;
 
 mod cli;
+mod default;
 mod download;
+mod files;
 mod health;
+mod hook;
 mod inspect;
 mod install;
 mod list;
 mod logging;
+mod profile;
 mod progress;
+mod provides;
 mod remove;
+mod repair;
 mod run;
+mod sandbox;
 mod self_actions;
+mod shell;
 mod state;
 mod update;
 #[path = "use.rs"]
 mod use_package;
 mod utils;
+mod verify;
 
 async fn handle_cli() -> SoarResult<()> {
     let mut args = env::args().collect::<Vec<_>>();
@@ -71,6 +95,10 @@ async fn handle_cli() -> SoarResult<()> {
         *color = false;
     }
 
+    if args.system || (args.config.is_none() && system_config_available()) {
+        enable_system_mode();
+    }
+
     if let Some(ref c) = args.config {
         {
             let mut config_path = CONFIG_PATH.write().unwrap();
@@ -120,7 +148,18 @@ async fn handle_cli() -> SoarResult<()> {
                 set_current_profile(profile)?;
             }
 
-            setup_required_paths().unwrap();
+            if has_write_access() {
+                setup_required_paths().unwrap();
+            }
+
+            let _lock = if command.modifies_store() {
+                if !has_write_access() {
+                    return Err(SoarError::SystemModeRequiresRoot);
+                }
+                Some(StoreLock::acquire(get_config().get_db_path()?, args.wait)?)
+            } else {
+                None
+            };
 
             match command {
                 cli::Commands::Install {
@@ -133,6 +172,7 @@ async fn handle_cli() -> SoarResult<()> {
                     portable_share,
                     no_notes,
                     binary_only,
+                    extract,
                     ask,
                 } => {
                     if portable.is_some()
@@ -159,6 +199,7 @@ async fn handle_cli() -> SoarResult<()> {
                         portable_share,
                         no_notes,
                         binary_only,
+                        extract,
                         ask,
                     )
                     .await?;
@@ -167,11 +208,20 @@ async fn handle_cli() -> SoarResult<()> {
                     query,
                     case_sensitive,
                     limit,
+                    filters,
                 } => {
-                    search_packages(query, case_sensitive, limit).await?;
+                    search_packages(query, case_sensitive, limit, filters).await?;
                 }
-                cli::Commands::Query { query } => {
-                    query_package(query).await?;
+                cli::Commands::Query {
+                    query,
+                    installed,
+                    export,
+                } => {
+                    if installed {
+                        query_installed_package(query, export).await?;
+                    } else {
+                        query_package(query).await?;
+                    }
                 }
                 cli::Commands::Remove { packages } => {
                     remove_packages(&packages).await?;
@@ -191,8 +241,8 @@ async fn handle_cli() -> SoarResult<()> {
                 cli::Commands::ListInstalledPackages { repo_name, count } => {
                     list_installed_packages(repo_name, count).await?;
                 }
-                cli::Commands::ListPackages { repo_name } => {
-                    list_packages(repo_name).await?;
+                cli::Commands::ListPackages { repo_name, filters } => {
+                    list_packages(repo_name, filters).await?;
                 }
                 cli::Commands::Log { package } => {
                     inspect_log(&package, InspectType::BuildLog).await?
@@ -200,19 +250,112 @@ async fn handle_cli() -> SoarResult<()> {
                 cli::Commands::Inspect { package } => {
                     inspect_log(&package, InspectType::BuildScript).await?
                 }
+                cli::Commands::Provides {
+                    binary,
+                    args,
+                    command_not_found,
+                } => {
+                    if command_not_found {
+                        let command = std::iter::once(binary).chain(args).collect();
+                        let code = handle_command_not_found(command).await?;
+                        if code != 0 {
+                            std::process::exit(code);
+                        }
+                    } else if !find_providers(binary).await? {
+                        std::process::exit(1);
+                    }
+                }
+                cli::Commands::Owns { path } => {
+                    if !find_owner(path).await? {
+                        std::process::exit(1);
+                    }
+                }
+                cli::Commands::Files { package } => {
+                    list_package_files(package).await?;
+                }
+                cli::Commands::Verify { package } => {
+                    if !verify_packages(package).await? {
+                        std::process::exit(1);
+                    }
+                }
+                cli::Commands::Repair { packages, ask } => {
+                    repair_packages(packages, ask).await?;
+                }
+                cli::Commands::Sandbox {
+                    package,
+                    allow,
+                    allow_ro,
+                    deny,
+                    network,
+                    no_network,
+                    isolate_home,
+                    share_home,
+                    disable,
+                } => {
+                    let changes = SandboxChanges {
+                        allow,
+                        allow_ro,
+                        deny,
+                        network: (network || no_network).then_some(network),
+                        isolate_home: (isolate_home || share_home).then_some(isolate_home),
+                        disable,
+                    };
+                    configure_sandbox(&package, changes).await?;
+                }
+                cli::Commands::Default { package, mime } => {
+                    set_default(&package, mime).await?;
+                }
+                cli::Commands::SandboxExec {
+                    package_dir,
+                    allow,
+                    allow_ro,
+                    no_network,
+                    home,
+                    argv0,
+                    command,
+                } => {
+                    let code = exec_sandboxed(SandboxExecArgs {
+                        package_dir,
+                        allow,
+                        allow_ro,
+                        no_network,
+                        home,
+                        argv0,
+                        command,
+                    })?;
+                    std::process::exit(code);
+                }
+                cli::Commands::Hook { shell } => {
+                    print_hook(shell)?;
+                }
                 cli::Commands::Run {
                     yes,
                     command,
                     pkg_id,
                     repo_name,
+                    no_cache,
                 } => {
-                    run_package(
+                    let code = run_package(
                         command.as_ref(),
                         yes,
                         repo_name.as_deref(),
                         pkg_id.as_deref(),
+                        no_cache,
                     )
                     .await?;
+                    if code != 0 {
+                        std::process::exit(code);
+                    }
+                }
+                cli::Commands::Shell {
+                    packages,
+                    yes,
+                    command,
+                } => {
+                    let code = enter_shell(&packages, yes, command.as_deref()).await?;
+                    if code != 0 {
+                        std::process::exit(code);
+                    }
                 }
                 cli::Commands::Use { package_name } => {
                     use_alternate_package(&package_name).await?;
@@ -276,6 +419,9 @@ async fn handle_cli() -> SoarResult<()> {
                         config.get_repositories_path()?.display()
                     );
                 }
+                cli::Commands::Profile { action } => {
+                    process_profile_action(&action)?;
+                }
                 cli::Commands::SelfCmd { action } => {
                     process_self_action(&action).await?;
                 }
@@ -0,0 +1,86 @@
+use nu_ansi_term::Color::{Blue, Cyan, Green, Magenta};
+use soar_core::{
+    config::{self, get_config, get_current_profile, Profile},
+    SoarResult,
+};
+use tracing::info;
+
+use crate::{cli::ProfileAction, utils::Colored};
+
+pub fn process_profile_action(action: &ProfileAction) -> SoarResult<()> {
+    match action {
+        ProfileAction::Add {
+            name,
+            root,
+            packages_path,
+        } => {
+            let profile = Profile {
+                root_path: root.clone(),
+                packages_path: packages_path.clone(),
+            };
+            config::add_profile(name, &profile)?;
+            info!("Added profile {}", Colored(Blue, name));
+        }
+        ProfileAction::List => list_profiles()?,
+        ProfileAction::Use { name } => {
+            config::set_default_profile(name)?;
+            info!("Default profile set to {}", Colored(Blue, name));
+        }
+        ProfileAction::Remove { name } => {
+            let root_path = get_config().get_profile(name)?.get_root_path()?;
+            config::remove_profile(name)?;
+            info!("Removed profile {}", Colored(Blue, name));
+            info!(
+                "Packages of the profile are kept in {}",
+                Colored(Magenta, root_path.display())
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn list_profiles() -> SoarResult<()> {
+    let config = get_config();
+    let current_profile = get_current_profile();
+
+    let mut names: Vec<&String> = config.profile.keys().collect();
+    names.sort();
+
+    for name in names {
+        let profile = config.get_profile(name)?;
+        let root_path = profile.get_root_path()?;
+        let bin_path = config.get_profile_bin_path(name)?;
+        let db_path = config.get_profile_db_path(name)?;
+
+        let mut markers = Vec::new();
+        if *name == config.default_profile {
+            markers.push("default");
+        }
+        if *name == current_profile {
+            markers.push("current");
+        }
+        let markers = if markers.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", markers.join(", "))
+        };
+
+        info!(
+            name = name,
+            default = *name == config.default_profile,
+            current = *name == current_profile,
+            root_path = %root_path.display(),
+            bin_path = %bin_path.display(),
+            db_path = %db_path.display(),
+            "{}{}",
+            Colored(Blue, name),
+            Colored(Green, markers)
+        );
+        info!("  Root: {}", Colored(Cyan, root_path.display()));
+        info!("  Bin: {}", Colored(Cyan, bin_path.display()));
+        info!("  Database: {}", Colored(Cyan, db_path.display()));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,307 @@
+use std::{fs, io::IsTerminal, path::PathBuf, process::Command};
+
+use nu_ansi_term::Color::{Blue, Cyan, Green, LightRed, Red, Yellow};
+use soar_core::{
+    config::get_config,
+    database::{
+        models::{FromRow, InstalledPackage},
+        packages::{FilterCondition, PackageProvide, PackageQueryBuilder},
+    },
+    error::ErrorContext,
+    lock::StoreLock,
+    SoarResult,
+};
+use tracing::info;
+
+use crate::{
+    install::install_packages,
+    run::run_package,
+    state::AppState,
+    utils::{get_valid_selection, interactive_ask, Colored},
+};
+
+#[derive(Debug, Clone)]
+pub struct ProvidingPackage {
+    pkg_id: String,
+    pkg_name: String,
+    repo_name: String,
+    version: String,
+    provides: Option<Vec<PackageProvide>>,
+}
+
+impl FromRow for ProvidingPackage {
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        let provides = row
+            .get::<_, Option<String>>("provides")?
+            .and_then(|json| serde_json::from_str(&json).ok());
+
+        Ok(ProvidingPackage {
+            pkg_id: row.get("pkg_id")?,
+            pkg_name: row.get("pkg_name")?,
+            repo_name: row.get("repo_name")?,
+            version: row.get("version")?,
+            provides,
+        })
+    }
+}
+
+/// Describes how the package provides the binary, e.g. `coreutils=>ls`.
+fn provided_as(binary: &str, pkg_name: &str, provides: Option<&[PackageProvide]>) -> String {
+    provides
+        .unwrap_or_default()
+        .iter()
+        .find(|provide| provide.name == binary || provide.target.as_deref() == Some(binary))
+        .map(|provide| match (&provide.target, &provide.strategy) {
+            (Some(target), Some(strategy)) => format!("{}{}{}", provide.name, strategy, target),
+            _ => provide.name.clone(),
+        })
+        .unwrap_or_else(|| pkg_name.to_string())
+}
+
+fn providing_packages(builder: PackageQueryBuilder, binary: &str) -> PackageQueryBuilder {
+    let condition = FilterCondition::Eq(binary.to_string());
+    builder
+        .where_or("pkg_name", condition.clone())
+        .json_where_or("provides", "name", condition.clone())
+        .json_where_or("provides", "target", condition)
+}
+
+/// Finds the installed package owning the entry in the bin directory, if any.
+fn bin_owner(binary: &str, installed: &[InstalledPackage]) -> SoarResult<Option<PathBuf>> {
+    let bin_path = get_config().get_bin_path()?.join(binary);
+    let Ok(real_path) = fs::read_link(&bin_path) else {
+        return Ok(None);
+    };
+
+    let owner = installed
+        .iter()
+        .find(|package| real_path.starts_with(&package.installed_path));
+
+    match owner {
+        Some(package) => {
+            info!(
+                bin_path = %bin_path.display(),
+                real_path = %real_path.display(),
+                pkg_name = package.pkg_name,
+                pkg_id = package.pkg_id,
+                repo_name = package.repo_name,
+                "{} is owned by {}#{}:{} ({})",
+                Colored(Blue, bin_path.display()),
+                Colored(Blue, &package.pkg_name),
+                Colored(Cyan, &package.pkg_id),
+                Colored(Green, &package.repo_name),
+                real_path.display()
+            );
+        }
+        None => {
+            info!(
+                bin_path = %bin_path.display(),
+                real_path = %real_path.display(),
+                "{} is not owned by any installed package ({})",
+                Colored(Blue, bin_path.display()),
+                real_path.display()
+            );
+        }
+    }
+
+    Ok(Some(bin_path))
+}
+
+pub async fn find_providers(binary: String) -> SoarResult<bool> {
+    let state = AppState::new();
+    let repo_db = state.repo_db().await?;
+
+    let available: Vec<ProvidingPackage> =
+        providing_packages(PackageQueryBuilder::new(repo_db.clone()), &binary)
+            .select(&[
+                "pkg_id",
+                "pkg_name",
+                "version",
+                "json(provides) AS provides",
+            ])
+            .load()?
+            .items;
+
+    let core_db = state.core_db()?;
+    let installed = PackageQueryBuilder::new(core_db.clone())
+        .where_and("is_installed", FilterCondition::Eq("1".to_string()))
+        .load_installed()?
+        .items;
+    let bin_path = bin_owner(&binary, &installed)?;
+
+    let installed_providers: Vec<&InstalledPackage> = installed
+        .iter()
+        .filter(|package| {
+            package.pkg_name == binary
+                || package.provides.as_ref().is_some_and(|provides| {
+                    provides.iter().any(|provide| {
+                        provide.name == binary || provide.target.as_deref() == Some(&binary)
+                    })
+                })
+        })
+        .collect();
+
+    for package in &installed_providers {
+        info!(
+            pkg_name = package.pkg_name,
+            pkg_id = package.pkg_id,
+            repo_name = package.repo_name,
+            version = package.version,
+            installed = true,
+            "[+] {}#{}:{} | {} | {}",
+            Colored(Blue, &package.pkg_name),
+            Colored(Cyan, &package.pkg_id),
+            Colored(Green, &package.repo_name),
+            Colored(LightRed, &package.version),
+            Colored(
+                Yellow,
+                provided_as(&binary, &package.pkg_name, package.provides.as_deref())
+            )
+        );
+    }
+
+    for package in available.iter().filter(|package| {
+        !installed_providers.iter().any(|installed| {
+            installed.repo_name == package.repo_name
+                && installed.pkg_id == package.pkg_id
+                && installed.pkg_name == package.pkg_name
+        })
+    }) {
+        info!(
+            pkg_name = package.pkg_name,
+            pkg_id = package.pkg_id,
+            repo_name = package.repo_name,
+            version = package.version,
+            installed = false,
+            "[-] {}#{}:{} | {} | {}",
+            Colored(Blue, &package.pkg_name),
+            Colored(Cyan, &package.pkg_id),
+            Colored(Green, &package.repo_name),
+            Colored(LightRed, &package.version),
+            Colored(
+                Yellow,
+                provided_as(&binary, &package.pkg_name, package.provides.as_deref())
+            )
+        );
+    }
+
+    let found = bin_path.is_some() || !installed_providers.is_empty() || !available.is_empty();
+    if !found {
+        info!("No package provides {}", Colored(Red, &binary));
+    }
+
+    Ok(found)
+}
+
+/// Handles a command missing from the shell, offering to install the package
+/// providing it or to run it once. Only the local metadata is consulted.
+///
+/// Returns the exit code for the shell hook, `127` if the command wasn't handled.
+pub async fn handle_command_not_found(command: Vec<String>) -> SoarResult<i32> {
+    let Some((binary, args)) = command.split_first() else {
+        return Ok(127);
+    };
+
+    let state = AppState::new();
+    let repo_db = state.local_repo_db()?;
+
+    let available: Vec<ProvidingPackage> =
+        providing_packages(PackageQueryBuilder::new(repo_db.clone()), binary)
+            .select(&[
+                "pkg_id",
+                "pkg_name",
+                "version",
+                "json(provides) AS provides",
+            ])
+            .load()?
+            .items;
+
+    if available.is_empty() {
+        return Ok(127);
+    }
+
+    info!(
+        "{}: command not found, but it is provided by:\n",
+        Colored(Red, binary)
+    );
+    for (idx, package) in available.iter().enumerate() {
+        info!(
+            pkg_name = package.pkg_name,
+            pkg_id = package.pkg_id,
+            repo_name = package.repo_name,
+            "[{}] {}#{}:{} | {}",
+            idx + 1,
+            Colored(Blue, &package.pkg_name),
+            Colored(Cyan, &package.pkg_id),
+            Colored(Green, &package.repo_name),
+            Colored(
+                Yellow,
+                provided_as(binary, &package.pkg_name, package.provides.as_deref())
+            )
+        );
+    }
+
+    if !std::io::stdin().is_terminal() {
+        let package = &available[0];
+        info!(
+            "\nInstall it with: soar install {}#{}:{}",
+            package.pkg_name, package.pkg_id, package.repo_name
+        );
+        return Ok(127);
+    }
+
+    let selection = if available.len() > 1 {
+        get_valid_selection(available.len())?
+    } else {
+        0
+    };
+    let package = &available[selection];
+    let query = format!(
+        "{}#{}:{}",
+        package.pkg_name, package.pkg_id, package.repo_name
+    );
+
+    let response = interactive_ask(&format!(
+        "\nInstall {}, run once {} or skip [{}]? ",
+        Colored(Green, "[i]"),
+        Colored(Green, "[r]"),
+        Colored(Red, "N")
+    ))?
+    .to_lowercase();
+
+    match response.as_str() {
+        "i" | "install" => {
+            // the hook doesn't hold the store lock, so it's taken for the
+            // install only
+            let lock = StoreLock::acquire(get_config().get_db_path()?, false)?;
+            install_packages(
+                &[query],
+                false,
+                true,
+                None,
+                None,
+                None,
+                None,
+                false,
+                false,
+                false,
+                false,
+            )
+            .await?;
+            drop(lock);
+
+            let bin_path = get_config().get_bin_path()?.join(binary);
+            let status = Command::new(&bin_path)
+                .args(args)
+                .status()
+                .with_context(|| format!("executing command {}", bin_path.display()))?;
+            Ok(status.code().unwrap_or(1))
+        }
+        "r" | "run" => {
+            let query = format!("{}:{}", query, binary);
+            let command: Vec<String> = std::iter::once(query).chain(args.to_vec()).collect();
+            run_package(&command, true, None, None, false).await
+        }
+        _ => Ok(127),
+    }
+}
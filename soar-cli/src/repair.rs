@@ -0,0 +1,443 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{atomic::Ordering, Arc, Mutex},
+};
+
+use nu_ansi_term::Color::{Blue, Cyan, Green, Red, Yellow};
+use rusqlite::{prepare_and_bind, Connection};
+use soar_core::{
+    config::get_config,
+    database::{
+        models::{InstalledPackage, Package},
+        packages::{FilterCondition, PackageQueryBuilder},
+    },
+    error::{ErrorContext, SoarError},
+    package::{
+        files::{collect_package_files, record_package_files},
+        formats::{
+            appimage::{extract_appimage, APPIMAGE_EXTRACT_DIR},
+            common::{integrate_package, symlink_shell_integration},
+        },
+        install::PackageFetcher,
+        query::PackageQuery,
+    },
+    utils::{apply_sig_variants, calculate_checksum, create_symlink},
+    SoarResult,
+};
+use soar_dl::downloader::DownloadState;
+use tracing::{error, info, warn};
+
+use crate::{
+    install::{create_install_context, InstallContext},
+    progress::{self, create_progress_bar},
+    run::TempRunDir,
+    sandbox::reapply_installed_sandbox,
+    state::AppState,
+    utils::{
+        has_desktop_integration, interactive_ask, mangle_package_symlinks,
+        verify_package_signatures, Colored, SignatureCheck,
+    },
+    verify::{signature_pubkey, verify_package, VerifyIssue},
+};
+
+/// Installed package with the issues found by verify.
+struct RepairTarget {
+    installed: InstalledPackage,
+    /// The installed version in the repository, to restore files from
+    package: Option<Package>,
+    issues: Vec<VerifyIssue>,
+}
+
+/// Whether the issue is with the files of the install directory, which are
+/// restored from the package, rather than with the links to them.
+fn is_content_issue(issue: &VerifyIssue, install_dir: &Path) -> bool {
+    match issue {
+        VerifyIssue::Incomplete
+        | VerifyIssue::ChecksumMismatch { .. }
+        | VerifyIssue::InvalidSignature(_)
+        | VerifyIssue::DanglingSymlink(_) => true,
+        VerifyIssue::MissingFile(path) => path.starts_with(install_dir),
+        VerifyIssue::ForeignSymlink { .. } => false,
+    }
+}
+
+pub async fn repair_packages(packages: Option<Vec<String>>, ask: bool) -> SoarResult<()> {
+    let state = AppState::new();
+    let core_db = state.core_db()?;
+    let repo_db = state.repo_db().await?;
+    let config = state.config();
+
+    let installed_pkgs = match packages {
+        Some(packages) => {
+            let mut installed_pkgs = Vec::new();
+            for package in packages {
+                let query = PackageQuery::try_from(package.as_str())?;
+                let builder = query.apply_filters(PackageQueryBuilder::new(core_db.clone()));
+                installed_pkgs.extend(builder.load_installed()?.items);
+            }
+            installed_pkgs
+        }
+        None => {
+            PackageQueryBuilder::new(core_db.clone())
+                .load_installed()?
+                .items
+        }
+    };
+
+    let mut pubkeys = HashMap::new();
+    let mut repair_targets = Vec::new();
+
+    for pkg in installed_pkgs {
+        let pubkey = signature_pubkey(&pkg.repo_name, &mut pubkeys)?;
+        let issues = {
+            let conn = core_db.lock()?;
+            verify_package(&conn, &pkg, pubkey.as_ref())?
+        };
+
+        if issues.is_empty() {
+            continue;
+        }
+
+        info!(
+            pkg_name = pkg.pkg_name,
+            pkg_id = pkg.pkg_id,
+            repo_name = pkg.repo_name,
+            "{}#{}:{}",
+            Colored(Blue, &pkg.pkg_name),
+            Colored(Cyan, &pkg.pkg_id),
+            Colored(Green, &pkg.repo_name)
+        );
+        for issue in &issues {
+            info!("  {}", Colored(Yellow, issue));
+        }
+
+        // the same version is required to restore the files as they were installed
+        let package: Option<Package> = PackageQueryBuilder::new(repo_db.clone())
+            .where_and("repo_name", FilterCondition::Eq(pkg.repo_name.clone()))
+            .where_and("pkg_name", FilterCondition::Eq(pkg.pkg_name.clone()))
+            .where_and("pkg_id", FilterCondition::Eq(pkg.pkg_id.clone()))
+            .where_and("version", FilterCondition::Eq(pkg.version.clone()))
+            .limit(1)
+            .load()?
+            .items
+            .into_iter()
+            .next();
+
+        let install_dir = PathBuf::from(&pkg.installed_path);
+        let needs_fetch = issues
+            .iter()
+            .any(|issue| is_content_issue(issue, &install_dir));
+        if needs_fetch && package.is_none() {
+            warn!(
+                "{}#{} - Version {} is no longer available in the repository. Skipped repair.",
+                pkg.pkg_name, pkg.pkg_id, pkg.version
+            );
+            continue;
+        }
+
+        repair_targets.push(RepairTarget {
+            installed: pkg,
+            package,
+            issues,
+        });
+    }
+
+    if repair_targets.is_empty() {
+        info!("No packages to repair.");
+        return Ok(());
+    }
+
+    if ask {
+        let response = interactive_ask(&format!(
+            "\nWould you like to repair these packages? [{}/{}] ",
+            Colored(Green, "Yes"),
+            Colored(Red, "No")
+        ))?
+        .to_lowercase();
+        let response = response.trim();
+
+        if !response.is_empty() && response != "y" {
+            info!("Quitting");
+            return Ok(());
+        }
+    }
+
+    let ctx = create_install_context(
+        repair_targets.len(),
+        config.parallel_limit.unwrap_or(4),
+        None,
+        None,
+        None,
+        None,
+        false,
+        false,
+    );
+
+    perform_repair(ctx, repair_targets, core_db.clone()).await
+}
+
+async fn perform_repair(
+    ctx: InstallContext,
+    targets: Vec<RepairTarget>,
+    core_db: Arc<Mutex<Connection>>,
+) -> SoarResult<()> {
+    let mut handles = Vec::new();
+    let fixed_width = 40;
+
+    for (idx, target) in targets.into_iter().enumerate() {
+        let handle = spawn_repair_task(&ctx, target, core_db.clone(), idx, fixed_width).await;
+        handles.push(handle);
+    }
+
+    for handle in handles {
+        handle
+            .await
+            .map_err(|err| SoarError::Custom(format!("Join handle error: {}", err)))?;
+    }
+
+    ctx.total_progress_bar.finish_and_clear();
+    for warn in ctx.warnings.lock().unwrap().iter() {
+        warn!("{warn}");
+    }
+
+    for error in ctx.errors.lock().unwrap().iter() {
+        error!("{error}");
+    }
+    info!(
+        "Repaired {}/{} packages",
+        ctx.installed_count.load(Ordering::Relaxed),
+        ctx.total_packages
+    );
+
+    Ok(())
+}
+
+async fn spawn_repair_task(
+    ctx: &InstallContext,
+    target: RepairTarget,
+    core_db: Arc<Mutex<Connection>>,
+    idx: usize,
+    fixed_width: usize,
+) -> tokio::task::JoinHandle<()> {
+    let permit = ctx.semaphore.clone().acquire_owned().await.unwrap();
+    let progress_bar = ctx
+        .multi_progress
+        .insert_from_back(1, create_progress_bar());
+
+    let message = format!(
+        "[{}/{}] {}#{}",
+        idx + 1,
+        ctx.total_packages,
+        target.installed.pkg_name,
+        target.installed.pkg_id
+    );
+    let message = if message.len() > fixed_width {
+        format!("{:.width$}", message, width = fixed_width)
+    } else {
+        format!("{:<width$}", message, width = fixed_width)
+    };
+    progress_bar.set_prefix(message);
+
+    let progress_callback = Arc::new(move |state| {
+        progress::handle_progress(state, &progress_bar);
+    });
+
+    let total_pb = ctx.total_progress_bar.clone();
+    let installed_count = ctx.installed_count.clone();
+    let ctx = ctx.clone();
+
+    tokio::spawn(async move {
+        match repair_package(&ctx, &target, progress_callback, core_db).await {
+            Ok(_) => {
+                installed_count.fetch_add(1, Ordering::Relaxed);
+                total_pb.inc(1);
+            }
+            Err(SoarError::Warning(err)) => {
+                ctx.warnings.lock().unwrap().push(err);
+            }
+            Err(err) => {
+                ctx.errors.lock().unwrap().push(err.to_string());
+            }
+        }
+
+        drop(permit);
+    })
+}
+
+/// Copies the files of the fetched package that are missing from the install
+/// directory or differ from it. Other files, like portable dirs, are kept.
+fn restore_files(fetched_dir: &Path, install_dir: &Path, skip: Option<&Path>) -> SoarResult<()> {
+    fs::create_dir_all(install_dir)
+        .with_context(|| format!("creating directory {}", install_dir.display()))?;
+
+    for entry in fs::read_dir(fetched_dir)
+        .with_context(|| format!("reading directory {}", fetched_dir.display()))?
+    {
+        let entry = entry
+            .with_context(|| format!("reading entry from directory {}", fetched_dir.display()))?;
+        let path = entry.path();
+        let dest = install_dir.join(entry.file_name());
+        if skip == Some(dest.as_path()) {
+            continue;
+        }
+
+        let metadata = fs::symlink_metadata(&path)
+            .with_context(|| format!("reading metadata of {}", path.display()))?;
+        let dest_metadata = fs::symlink_metadata(&dest).ok();
+
+        if metadata.is_dir() {
+            restore_files(&path, &dest, skip)?;
+        } else if metadata.is_symlink() {
+            if dest_metadata.is_none() {
+                let target = fs::read_link(&path)
+                    .with_context(|| format!("reading symlink {}", path.display()))?;
+                create_symlink(target, dest)?;
+            }
+        } else {
+            let is_intact = dest_metadata.as_ref().is_some_and(|m| m.is_file())
+                && calculate_checksum(&path)? == calculate_checksum(&dest)?;
+            if is_intact {
+                continue;
+            }
+            // never writes through a link replacing the file
+            if dest_metadata.is_some_and(|m| m.is_symlink()) {
+                fs::remove_file(&dest)
+                    .with_context(|| format!("removing symlink {}", dest.display()))?;
+            }
+            fs::copy(&path, &dest)
+                .with_context(|| format!("copying {} to {}", path.display(), dest.display()))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Restores the files and links reported by verify. The package is only
+/// fetched if files of the install directory are missing or corrupt, which
+/// are restored without touching the rest of the install directory.
+async fn repair_package(
+    ctx: &InstallContext,
+    target: &RepairTarget,
+    progress_callback: Arc<dyn Fn(DownloadState) + Send + Sync>,
+    core_db: Arc<Mutex<Connection>>,
+) -> SoarResult<()> {
+    let installed = &target.installed;
+    let install_dir = PathBuf::from(&installed.installed_path);
+    let bin_path = install_dir.join(&installed.pkg_name);
+    let app_dir = install_dir.join(APPIMAGE_EXTRACT_DIR);
+    let is_extracted = app_dir.is_dir();
+
+    let needs_fetch = target
+        .issues
+        .iter()
+        .any(|issue| is_content_issue(issue, &install_dir));
+    if let (true, Some(package)) = (needs_fetch, &target.package) {
+        let fetch_dir = TempRunDir::new("soar-repair")?;
+        let install_patterns = apply_sig_variants(
+            installed
+                .install_patterns
+                .clone()
+                .unwrap_or_else(|| get_config().install_patterns.clone().unwrap_or_default()),
+        );
+        let mut fetcher = PackageFetcher::new(
+            package,
+            fetch_dir.path(),
+            Some(progress_callback),
+            install_patterns,
+        )?;
+        let downloaded_checksum = fetcher.download_package().await?;
+
+        if let SignatureCheck::MissingPubkey =
+            verify_package_signatures(&package.repo_name, fetch_dir.path())?
+        {
+            ctx.warnings.lock().unwrap().push(format!(
+                "{}#{} - Signature verification skipped as no pubkey was found.",
+                package.pkg_name, package.pkg_id
+            ));
+        }
+        if !fetcher.validate_checksum(downloaded_checksum)? {
+            ctx.warnings.lock().unwrap().push(format!(
+                "{}#{} - {}. Skipped checksum validation.",
+                package.pkg_name,
+                package.pkg_id,
+                if package.bsum.is_some() {
+                    "Checksummed file was not fetched"
+                } else {
+                    "Blake3 checksum not found"
+                }
+            ));
+        }
+
+        // extracted AppImages are replaced by a launcher, so they're restored
+        // by extracting them again
+        restore_files(
+            fetch_dir.path(),
+            &install_dir,
+            is_extracted.then_some(bin_path.as_path()),
+        )?;
+        let needs_extract = is_extracted
+            && target.issues.iter().any(|issue| {
+                issue
+                    .path()
+                    .is_some_and(|path| path == bin_path || path.starts_with(&app_dir))
+            });
+        if needs_extract {
+            let fetched_bin = fetch_dir.path().join(&installed.pkg_name);
+            fs::copy(&fetched_bin, &bin_path).with_context(|| {
+                format!(
+                    "copying {} to {}",
+                    fetched_bin.display(),
+                    bin_path.display()
+                )
+            })?;
+            extract_appimage(&install_dir, &bin_path)?;
+        }
+    }
+
+    let bin_dir = get_config().get_profile_bin_path(&installed.profile)?;
+    let mut created_paths = Vec::new();
+    if !installed.unlinked {
+        let symlinks = mangle_package_symlinks(
+            &install_dir,
+            &bin_dir,
+            installed.provides.as_deref(),
+            &installed.pkg_name,
+            &core_db,
+        )
+        .await?;
+        created_paths.extend(symlinks.into_iter().map(|(_, link)| link));
+    }
+    if !installed.unlinked || target.package.as_ref().is_some_and(has_desktop_integration) {
+        created_paths.extend(
+            integrate_package(
+                &install_dir,
+                &bin_dir,
+                installed,
+                installed.portable_path.as_deref(),
+                installed.portable_home.as_deref(),
+                installed.portable_config.as_deref(),
+                installed.portable_share.as_deref(),
+            )
+            .await?,
+        );
+    }
+    created_paths.extend(symlink_shell_integration(&install_dir)?);
+
+    let conn = core_db.lock()?;
+    let files = collect_package_files(&install_dir, &created_paths)?;
+    record_package_files(&conn, installed.id, &files)?;
+
+    let id = installed.id;
+    let mut stmt = prepare_and_bind!(
+        conn,
+        "UPDATE packages SET is_installed = true WHERE id = $id"
+    );
+    stmt.raw_execute()?;
+
+    // the launchers were replaced by the new bin links
+    reapply_installed_sandbox(&conn, installed)?;
+
+    Ok(())
+}
@@ -1,41 +1,371 @@
-use std::{fs, path::PathBuf, process::Command, sync::Arc};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
+use rand::{distr::Alphanumeric, Rng};
+use serde::{Deserialize, Serialize};
 use soar_core::{
+    config::get_config,
     database::{
         models::Package,
         packages::{FilterCondition, PackageQueryBuilder},
     },
     error::{ErrorContext, SoarError},
-    package::query::PackageQuery,
-    utils::{calculate_checksum, get_extract_dir},
+    package::{install::PackageFetcher, query::PackageQuery},
+    utils::{apply_sig_variants, calculate_dir_size},
     SoarResult,
 };
-use soar_dl::{
-    downloader::{DownloadOptions, Downloader, OciDownloadOptions, OciDownloader},
-    utils::FileMode,
-};
+use tracing::warn;
 
 use crate::{
     progress::{self, create_progress_bar},
     state::AppState,
-    utils::{interactive_ask, select_package_interactively},
+    utils::{select_package_interactively, verify_package_signatures, SignatureCheck},
 };
 
+/// Metadata of a run cache entry, stored in its directory.
+const RUN_CACHE_META: &str = ".soar-run.json";
+
+#[derive(Debug, Deserialize, Serialize)]
+struct RunCacheEntry {
+    repo_name: String,
+    pkg_id: String,
+    pkg_name: String,
+    version: String,
+    bsum: Option<String>,
+    source: String,
+    last_used: u64,
+}
+
+impl RunCacheEntry {
+    fn new(package: &Package) -> Self {
+        Self {
+            repo_name: package.repo_name.clone(),
+            pkg_id: package.pkg_id.clone(),
+            pkg_name: package.pkg_name.clone(),
+            version: package.version.clone(),
+            bsum: package.bsum.clone(),
+            source: package_source(package).to_string(),
+            last_used: now(),
+        }
+    }
+
+    fn read(entry_dir: &Path) -> Option<Self> {
+        let content = fs::read_to_string(entry_dir.join(RUN_CACHE_META)).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    fn write(&self, entry_dir: &Path) -> SoarResult<()> {
+        let path = entry_dir.join(RUN_CACHE_META);
+        fs::write(&path, serde_json::to_string(self).unwrap())
+            .with_context(|| format!("writing {}", path.display()))
+    }
+
+    fn is_same_package(&self, package: &Package) -> bool {
+        self.repo_name == package.repo_name
+            && self.pkg_id == package.pkg_id
+            && self.pkg_name == package.pkg_name
+    }
+
+    fn has_same_package(&self, other: &Self) -> bool {
+        self.repo_name == other.repo_name
+            && self.pkg_id == other.pkg_id
+            && self.pkg_name == other.pkg_name
+    }
+
+    /// Whether the cached files still match the package metadata.
+    fn is_current(&self, package: &Package) -> bool {
+        self.is_same_package(package)
+            && self.version == package.version
+            && self.bsum == package.bsum
+            && self.source == package_source(package)
+    }
+}
+
+/// Temporary directory removed once dropped.
+pub struct TempRunDir(PathBuf);
+
+impl TempRunDir {
+    pub fn new(prefix: &str) -> SoarResult<Self> {
+        let suffix: String = rand::rng()
+            .sample_iter(&Alphanumeric)
+            .take(12)
+            .map(char::from)
+            .collect();
+        let path = env::temp_dir().join(format!("{}-{}", prefix, suffix));
+        fs::create_dir_all(&path)
+            .with_context(|| format!("creating directory {}", path.display()))?;
+        Ok(Self(path))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempRunDir {
+    fn drop(&mut self) {
+        fs::remove_dir_all(&self.0).ok();
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn package_source(package: &Package) -> &str {
+    package.ghcr_pkg.as_deref().unwrap_or(&package.download_url)
+}
+
+/// Each package version is cached in its own directory, so files of different
+/// packages never collide.
+fn run_cache_entry_dir(run_cache: &Path, package: &Package) -> PathBuf {
+    let key = format!(
+        "{}-{}-{}@{}",
+        package.repo_name, package.pkg_name, package.pkg_id, package.version
+    );
+    run_cache.join(key.replace(['/', '\\'], "_"))
+}
+
+/// Fetches the full package into the directory, verifying it the same way
+/// as on install.
+async fn fetch_package(package: &Package, output_dir: &Path) -> SoarResult<()> {
+    let progress_bar = create_progress_bar();
+    let progress_callback = Arc::new(move |state| {
+        progress::handle_progress(state, &progress_bar);
+    });
+
+    let install_patterns =
+        apply_sig_variants(get_config().install_patterns.clone().unwrap_or_default());
+    let mut fetcher = PackageFetcher::new(
+        package,
+        output_dir,
+        Some(progress_callback),
+        install_patterns,
+    )?;
+    let downloaded_checksum = fetcher.download_package().await?;
+
+    if let SignatureCheck::MissingPubkey =
+        verify_package_signatures(&package.repo_name, output_dir)?
+    {
+        warn!(
+            "{}#{} - Signature verification skipped as no pubkey was found.",
+            package.pkg_name, package.pkg_id
+        );
+    }
+
+    if !fetcher.validate_checksum(downloaded_checksum)? {
+        warn!(
+            "{}#{} - {}. Skipped checksum validation.",
+            package.pkg_name,
+            package.pkg_id,
+            if package.bsum.is_some() {
+                "Checksummed file was not fetched"
+            } else {
+                "Blake3 checksum not found"
+            }
+        );
+    }
+
+    Ok(())
+}
+
+/// Returns the cache entry directory of the package, fetching the package if
+/// the entry is missing or outdated.
+pub async fn cached_package(run_cache: &Path, package: &Package) -> SoarResult<PathBuf> {
+    let entry_dir = run_cache_entry_dir(run_cache, package);
+
+    if let Some(mut entry) = RunCacheEntry::read(&entry_dir) {
+        if entry.is_current(package) {
+            entry.last_used = now();
+            entry.write(&entry_dir)?;
+            return Ok(entry_dir);
+        }
+    }
+
+    if entry_dir.exists() {
+        fs::remove_dir_all(&entry_dir)
+            .with_context(|| format!("removing directory {}", entry_dir.display()))?;
+    }
+    fs::create_dir_all(&entry_dir)
+        .with_context(|| format!("creating directory {}", entry_dir.display()))?;
+
+    match fetch_package(package, &entry_dir).await {
+        Ok(()) => {
+            // written last, an entry without it is incomplete
+            RunCacheEntry::new(package).write(&entry_dir)?;
+            Ok(entry_dir)
+        }
+        Err(err) => {
+            fs::remove_dir_all(&entry_dir).ok();
+            Err(err)
+        }
+    }
+}
+
+/// Returns the file of the provided binary with the given name or alias.
+pub fn provided_binary(package: &Package, package_dir: &Path, binary: &str) -> Option<PathBuf> {
+    package
+        .provides
+        .iter()
+        .flatten()
+        .find(|provide| provide.name == binary || provide.target.as_deref() == Some(binary))
+        .map(|provide| package_dir.join(&provide.name))
+        .filter(|path| path.is_file())
+}
+
+/// Resolves the executable to run and its arguments. Without a requested
+/// binary, the package binary is used, or if the package has none, the
+/// provided binary named by the first argument.
+fn resolve_executable<'a>(
+    package: &Package,
+    package_dir: &Path,
+    binary: Option<&str>,
+    args: &'a [String],
+) -> SoarResult<(PathBuf, &'a [String])> {
+    if let Some(binary) = binary {
+        return provided_binary(package, package_dir, binary)
+            .or_else(|| Some(package_dir.join(binary)).filter(|path| path.is_file()))
+            .map(|path| (path, args))
+            .ok_or_else(|| {
+                SoarError::Custom(format!(
+                    "{}#{} does not provide {}",
+                    package.pkg_name, package.pkg_id, binary
+                ))
+            });
+    }
+
+    let main_binary = package_dir.join(&package.pkg_name);
+    if main_binary.is_file() {
+        return Ok((main_binary, args));
+    }
+
+    if let Some((first, rest)) = args.split_first() {
+        if let Some(path) = provided_binary(package, package_dir, first) {
+            return Ok((path, rest));
+        }
+    }
+
+    let provides = package.provides.as_deref().unwrap_or_default();
+    if let [provide] = provides {
+        let path = package_dir.join(&provide.name);
+        if path.is_file() {
+            return Ok((path, args));
+        }
+    }
+
+    let binaries = provides
+        .iter()
+        .map(|provide| provide.target.as_deref().unwrap_or(&provide.name))
+        .collect::<Vec<_>>();
+    Err(SoarError::Custom(format!(
+        "{}#{} has no binary named {}. Select one with {}:<binary>{}",
+        package.pkg_name,
+        package.pkg_id,
+        package.pkg_name,
+        package.pkg_name,
+        if binaries.is_empty() {
+            String::new()
+        } else {
+            format!(" from: {}", binaries.join(", "))
+        }
+    )))
+}
+
+/// Splits the requested binary off `pkg:binary`. A single suffix naming a
+/// repository is kept as in `pkg:repo`, `pkg:repo:binary` selects both.
+fn split_binary(query: &str) -> (&str, Option<&str>) {
+    match query.rsplit_once(':') {
+        Some((rest, binary))
+            if !binary.is_empty()
+                && (rest.contains(':') || get_config().get_repository(binary).is_none()) =>
+        {
+            (rest, Some(binary))
+        }
+        _ => (query, None),
+    }
+}
+
+/// Removes other versions of the packages in use, then the least recently
+/// used entries until the cache fits in the size limit.
+pub fn evict_run_cache(run_cache: &Path, in_use: &[&Path], limit: u64) {
+    let Ok(dir) = fs::read_dir(run_cache) else {
+        return;
+    };
+
+    let in_use_meta: Vec<RunCacheEntry> = in_use
+        .iter()
+        .filter_map(|entry_dir| RunCacheEntry::read(entry_dir))
+        .collect();
+
+    let mut entries = Vec::new();
+    let mut total_size = 0;
+    for entry in dir.flatten() {
+        let path = entry.path();
+        // leftovers from when runs were cached as bare files
+        if !path.is_dir() {
+            fs::remove_file(&path).ok();
+            continue;
+        }
+        if in_use.contains(&path.as_path()) {
+            total_size += calculate_dir_size(&path).unwrap_or_default();
+            continue;
+        }
+
+        let meta = RunCacheEntry::read(&path);
+        if meta.as_ref().is_some_and(|meta| {
+            in_use_meta
+                .iter()
+                .any(|in_use| meta.has_same_package(in_use))
+        }) {
+            fs::remove_dir_all(&path).ok();
+            continue;
+        }
+
+        let size = calculate_dir_size(&path).unwrap_or_default();
+        total_size += size;
+        entries.push((
+            path,
+            size,
+            meta.map(|meta| meta.last_used).unwrap_or_default(),
+        ));
+    }
+
+    entries.sort_by_key(|(_, _, last_used)| *last_used);
+    for (path, size, _) in entries {
+        if total_size <= limit {
+            break;
+        }
+        if fs::remove_dir_all(&path).is_ok() {
+            total_size -= size;
+        }
+    }
+}
+
 pub async fn run_package(
     command: &[String],
     yes: bool,
     repo_name: Option<&str>,
     pkg_id: Option<&str>,
-) -> SoarResult<()> {
+    no_cache: bool,
+) -> SoarResult<i32> {
     let state = AppState::new();
-    let cache_bin = state.config().get_cache_path()?.join("bin");
+    let config = state.config();
+    let run_cache = config.get_run_cache_path()?;
 
-    let package_name = &command[0];
+    let (package_name, binary) = split_binary(&command[0]);
 
-    let query = PackageQuery::try_from(package_name.as_str())?;
+    let query = PackageQuery::try_from(package_name)?;
     let package_name = &query.name.unwrap_or_else(|| package_name.to_string());
-    let repo_name = query.repo_name.as_deref().or_else(|| repo_name);
-    let pkg_id = query.pkg_id.as_deref().or_else(|| pkg_id);
+    let repo_name = query.repo_name.as_deref().or(repo_name);
+    let pkg_id = query.pkg_id.as_deref().or(pkg_id);
     let version = query.version.as_deref();
 
     let args = if command.len() > 1 {
@@ -44,111 +374,105 @@ pub async fn run_package(
         &[]
     };
 
-    let output_path = cache_bin.join(package_name);
-    if !output_path.exists() {
-        let repo_db = state.repo_db().await?;
+    let repo_db = state.repo_db().await?;
 
-        let mut builder = PackageQueryBuilder::new(repo_db.clone())
-            .where_and("pkg_name", FilterCondition::Eq(package_name.clone()));
+    let mut builder = PackageQueryBuilder::new(repo_db.clone())
+        .where_and("pkg_name", FilterCondition::Eq(package_name.clone()));
 
-        if let Some(repo_name) = repo_name {
-            builder = builder.where_and("repo_name", FilterCondition::Eq(repo_name.to_string()));
-        }
+    if let Some(repo_name) = repo_name {
+        builder = builder.where_and("repo_name", FilterCondition::Eq(repo_name.to_string()));
+    }
 
-        if let Some(pkg_id) = pkg_id {
-            builder = builder.where_and("pkg_id", FilterCondition::Eq(pkg_id.to_string()));
-        }
+    if let Some(pkg_id) = pkg_id {
+        builder = builder.where_and("pkg_id", FilterCondition::Eq(pkg_id.to_string()));
+    }
 
-        if let Some(version) = version {
-            builder = builder.where_and("version", FilterCondition::Eq(version.to_string()));
-        }
+    if let Some(version) = version {
+        builder = builder.where_and("version", FilterCondition::Eq(version.to_string()));
+    }
 
-        let packages: Vec<Package> = builder.load()?.items;
+    let packages: Vec<Package> = builder.load()?.items;
 
-        let package = match packages.len() {
-            0 => return Err(SoarError::PackageNotFound(package_name.clone())),
-            1 => packages.into_iter().next(),
-            _ if yes => packages.into_iter().next(),
-            _ => select_package_interactively(packages, package_name)?,
-        }
-        .unwrap();
-
-        fs::create_dir_all(&cache_bin)
-            .with_context(|| format!("creating directory {}", cache_bin.display()))?;
-
-        let progress_bar = create_progress_bar();
-        let progress_callback = Arc::new(move |state| {
-            progress::handle_progress(state, &progress_bar);
-        });
-
-        if let Some(url) = package.ghcr_blob {
-            let options = OciDownloadOptions {
-                url: url.to_string(),
-                output_path: Some(output_path.to_string_lossy().to_string()),
-                progress_callback: Some(progress_callback.clone()),
-                api: None,
-                concurrency: Some(1),
-                regexes: Vec::new(),
-                globs: Vec::new(),
-                exclude_keywords: Vec::new(),
-                match_keywords: Vec::new(),
-                exact_case: false,
-                file_mode: FileMode::ForceOverwrite,
-            };
-
-            let mut downloader = OciDownloader::new(options);
-
-            downloader.download_oci().await?;
-        } else {
-            let downloader = Downloader::default();
-            let extract_dir = get_extract_dir(&cache_bin);
-            let options = DownloadOptions {
-                url: package.download_url.clone(),
-                output_path: Some(output_path.to_string_lossy().to_string()),
-                progress_callback: Some(progress_callback),
-                extract_archive: true,
-                extract_dir: Some(extract_dir.to_string_lossy().to_string()),
-                file_mode: FileMode::ForceOverwrite,
-                prompt: None,
-            };
-
-            let file_name = downloader.download(options).await?;
-            let extract_path = PathBuf::from(&extract_dir);
-            if extract_path.exists() {
-                fs::remove_file(file_name).ok();
-
-                for entry in fs::read_dir(&extract_path)
-                    .with_context(|| format!("reading {} directory", extract_path.display()))?
-                {
-                    let entry = entry.with_context(|| {
-                        format!("reading entry from directory {}", extract_path.display())
-                    })?;
-                    let from = entry.path();
-                    let to = cache_bin.join(entry.file_name());
-                    fs::rename(&from, &to).with_context(|| {
-                        format!("renaming {} to {}", from.display(), to.display())
-                    })?;
-                }
-
-                fs::remove_dir_all(&extract_path).ok();
-            }
-        }
+    let package = match packages.len() {
+        0 => return Err(SoarError::PackageNotFound(package_name.clone())),
+        1 => packages.into_iter().next(),
+        _ if yes => packages.into_iter().next(),
+        _ => select_package_interactively(packages, package_name)?,
+    }
+    .unwrap();
+
+    let mut _temp_dir = None;
+    let package_dir = if no_cache {
+        let temp_dir = _temp_dir.insert(TempRunDir::new("soar-run")?);
+        fetch_package(&package, temp_dir.path()).await?;
+        temp_dir.path().to_path_buf()
+    } else {
+        // runs used to be cached as bare files under cache/bin
+        fs::remove_dir_all(config.get_cache_path()?.join("bin")).ok();
+
+        let entry_dir = cached_package(&run_cache, &package).await?;
+        evict_run_cache(&run_cache, &[&entry_dir], config.run_cache_limit());
+        entry_dir
+    };
 
-        let checksum = calculate_checksum(&output_path)?;
-        if let Some(bsum) = package.bsum {
-            if checksum != bsum {
-                let response = interactive_ask("Invalid checksum. Do you want to continue (y/N)?")?;
-                if !response.to_lowercase().starts_with("y") {
-                    return Err(SoarError::InvalidChecksum);
-                }
+    let (executable, args) = resolve_executable(&package, &package_dir, binary, args)?;
+
+    let status = Command::new(&executable)
+        .args(args)
+        .status()
+        .with_context(|| format!("executing command {}", executable.display()))?;
+
+    Ok(status.code().unwrap_or(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process;
+
+    use super::*;
+
+    fn cache_entry(run_cache: &Path, name: &str, pkg_name: Option<&str>, last_used: u64) {
+        let entry_dir = run_cache.join(name);
+        fs::create_dir_all(&entry_dir).unwrap();
+        fs::write(entry_dir.join("binary"), vec![0u8; 1000]).unwrap();
+        if let Some(pkg_name) = pkg_name {
+            RunCacheEntry {
+                repo_name: "repo".into(),
+                pkg_id: format!("{}-id", pkg_name),
+                pkg_name: pkg_name.into(),
+                version: name.into(),
+                bsum: None,
+                source: String::new(),
+                last_used,
             }
+            .write(&entry_dir)
+            .unwrap();
         }
     }
 
-    Command::new(&output_path)
-        .args(args)
-        .status()
-        .with_context(|| format!("executing command {}", output_path.display()))?;
+    #[test]
+    fn evicts_other_versions_and_least_recently_used() {
+        let run_cache = env::temp_dir().join(format!("soar-run-cache-{}", process::id()));
+        let _ = fs::remove_dir_all(&run_cache);
+        fs::create_dir_all(&run_cache).unwrap();
 
-    Ok(())
+        cache_entry(&run_cache, "untracked", None, 0);
+        cache_entry(&run_cache, "oldest", Some("a"), 10);
+        cache_entry(&run_cache, "recent", Some("b"), 20);
+        cache_entry(&run_cache, "old-version", Some("c"), 30);
+        cache_entry(&run_cache, "in-use", Some("c"), 5);
+        fs::write(run_cache.join("legacy"), "").unwrap();
+
+        // room for two entries with their metadata
+        evict_run_cache(&run_cache, &[&run_cache.join("in-use")], 2500);
+
+        let mut remaining: Vec<String> = fs::read_dir(&run_cache)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        remaining.sort();
+        assert_eq!(remaining, vec!["in-use", "recent"]);
+
+        fs::remove_dir_all(run_cache).unwrap();
+    }
 }
@@ -0,0 +1,243 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use nu_ansi_term::Color::{Blue, Cyan, Green, Magenta, Red};
+use rusqlite::Connection;
+use soar_core::{
+    database::{
+        models::{InstalledPackage, Package, SandboxProfile},
+        packages::{FilterCondition, PackageQueryBuilder},
+    },
+    error::ErrorContext,
+    package::query::PackageQuery,
+    sandbox::{
+        apply_sandbox, delete_sandbox_profile, get_sandbox_profile, remove_sandbox, run_sandboxed,
+        save_sandbox_profile, SandboxOptions,
+    },
+    utils::build_path,
+    SoarResult,
+};
+use tracing::{info, warn};
+
+use crate::{state::AppState, utils::Colored};
+
+#[derive(Debug, Default)]
+pub struct SandboxChanges {
+    pub allow: Vec<String>,
+    pub allow_ro: Vec<String>,
+    pub deny: Vec<String>,
+    pub network: Option<bool>,
+    pub isolate_home: Option<bool>,
+    pub disable: bool,
+}
+
+fn absolute_path(path: &str) -> SoarResult<String> {
+    let path = build_path(path)?;
+    let path = if path.is_absolute() {
+        path
+    } else {
+        env::current_dir()
+            .with_context(|| "retrieving current directory".into())?
+            .join(path)
+    };
+    Ok(path.to_string_lossy().into_owned())
+}
+
+fn current_exe() -> SoarResult<PathBuf> {
+    env::current_exe().with_context(|| "retrieving current executable".into())
+}
+
+pub async fn configure_sandbox(package: &str, changes: SandboxChanges) -> SoarResult<()> {
+    let state = AppState::new();
+    let core_db = state.core_db()?;
+
+    let query = PackageQuery::try_from(package)?;
+    let builder = query
+        .apply_filters(PackageQueryBuilder::new(core_db.clone()))
+        .where_and("is_installed", FilterCondition::Eq("1".to_string()));
+    let installed_pkgs = builder.load_installed()?.items;
+
+    if installed_pkgs.is_empty() {
+        warn!("Package {} is not installed.", package);
+        return Ok(());
+    }
+
+    let soar_exe = current_exe()?;
+    let allow = changes
+        .allow
+        .iter()
+        .map(|path| absolute_path(path))
+        .collect::<SoarResult<Vec<_>>>()?;
+    let allow_ro = changes
+        .allow_ro
+        .iter()
+        .map(|path| absolute_path(path))
+        .collect::<SoarResult<Vec<_>>>()?;
+    let deny = changes
+        .deny
+        .iter()
+        .map(|path| absolute_path(path))
+        .collect::<SoarResult<Vec<_>>>()?;
+
+    for pkg in installed_pkgs {
+        let conn = core_db.lock()?;
+
+        if changes.disable {
+            remove_sandbox(&conn, &pkg)?;
+            delete_sandbox_profile(&conn, &pkg.repo_name, &pkg.pkg_id, &pkg.pkg_name)?;
+            info!(
+                "Disabled sandbox for {}#{}:{}",
+                Colored(Blue, &pkg.pkg_name),
+                Colored(Cyan, &pkg.pkg_id),
+                Colored(Green, &pkg.repo_name)
+            );
+            continue;
+        }
+
+        let mut profile = get_sandbox_profile(&conn, &pkg.repo_name, &pkg.pkg_id, &pkg.pkg_name)?
+            .unwrap_or_else(|| SandboxProfile {
+                repo_name: pkg.repo_name.clone(),
+                pkg_id: pkg.pkg_id.clone(),
+                pkg_name: pkg.pkg_name.clone(),
+                allow: Vec::new(),
+                allow_ro: Vec::new(),
+                network: true,
+                isolate_home: true,
+            });
+
+        for path in &allow {
+            profile.allow_ro.retain(|p| p != path);
+            if !profile.allow.contains(path) {
+                profile.allow.push(path.clone());
+            }
+        }
+        for path in &allow_ro {
+            profile.allow.retain(|p| p != path);
+            if !profile.allow_ro.contains(path) {
+                profile.allow_ro.push(path.clone());
+            }
+        }
+        profile.allow.retain(|p| !deny.contains(p));
+        profile.allow_ro.retain(|p| !deny.contains(p));
+        if let Some(network) = changes.network {
+            profile.network = network;
+        }
+        if let Some(isolate_home) = changes.isolate_home {
+            profile.isolate_home = isolate_home;
+        }
+
+        save_sandbox_profile(&conn, &profile)?;
+        apply_sandbox(&conn, &pkg, &profile, &soar_exe)?;
+
+        info!(
+            pkg_name = profile.pkg_name,
+            pkg_id = profile.pkg_id,
+            repo_name = profile.repo_name,
+            allow = ?profile.allow,
+            allow_ro = ?profile.allow_ro,
+            network = profile.network,
+            isolate_home = profile.isolate_home,
+            "Sandbox for {}#{}:{}",
+            Colored(Blue, &profile.pkg_name),
+            Colored(Cyan, &profile.pkg_id),
+            Colored(Green, &profile.repo_name)
+        );
+        let enabled = |value: bool| {
+            if value {
+                Colored(Green, "enabled")
+            } else {
+                Colored(Red, "disabled")
+            }
+        };
+        info!("  Network: {}", enabled(profile.network));
+        info!("  Home isolation: {}", enabled(profile.isolate_home));
+        for path in &profile.allow {
+            info!("  Read-write: {}", Colored(Magenta, path));
+        }
+        for path in &profile.allow_ro {
+            info!("  Read-only: {}", Colored(Magenta, path));
+        }
+    }
+
+    Ok(())
+}
+
+/// Re-creates the launchers of a sandboxed package, after its bin links were
+/// replaced by an install.
+pub fn reapply_sandbox<P: AsRef<Path>>(
+    core_db: &Arc<Mutex<Connection>>,
+    package: &Package,
+    install_dir: P,
+) -> SoarResult<()> {
+    let profile = {
+        let conn = core_db.lock()?;
+        get_sandbox_profile(
+            &conn,
+            &package.repo_name,
+            &package.pkg_id,
+            &package.pkg_name,
+        )?
+    };
+    let Some(profile) = profile else {
+        return Ok(());
+    };
+
+    let installed = PackageQueryBuilder::new(core_db.clone())
+        .where_and(
+            "installed_path",
+            FilterCondition::Eq(install_dir.as_ref().to_string_lossy().into_owned()),
+        )
+        .limit(1)
+        .load_installed()?
+        .items
+        .into_iter()
+        .next();
+
+    if let Some(installed) = installed {
+        let conn = core_db.lock()?;
+        apply_sandbox(&conn, &installed, &profile, &current_exe()?)?;
+    }
+
+    Ok(())
+}
+
+/// Re-creates the launchers of the installed package if it is sandboxed.
+pub fn reapply_installed_sandbox(conn: &Connection, package: &InstalledPackage) -> SoarResult<()> {
+    if let Some(profile) =
+        get_sandbox_profile(conn, &package.repo_name, &package.pkg_id, &package.pkg_name)?
+    {
+        apply_sandbox(conn, package, &profile, &current_exe()?)?;
+    }
+    Ok(())
+}
+
+pub struct SandboxExecArgs {
+    pub package_dir: String,
+    pub allow: Vec<String>,
+    pub allow_ro: Vec<String>,
+    pub no_network: bool,
+    pub home: Option<String>,
+    pub argv0: Option<String>,
+    pub command: Vec<String>,
+}
+
+pub fn exec_sandboxed(args: SandboxExecArgs) -> SoarResult<i32> {
+    let options = SandboxOptions {
+        package_dir: PathBuf::from(args.package_dir),
+        allow: args.allow.into_iter().map(PathBuf::from).collect(),
+        allow_ro: args.allow_ro.into_iter().map(PathBuf::from).collect(),
+        network: !args.no_network,
+        home: args.home.map(PathBuf::from),
+    };
+
+    let (command, command_args) = args.command.split_first().unwrap();
+    run_sandboxed(
+        &options,
+        Path::new(command),
+        args.argv0.as_deref(),
+        command_args,
+    )
+}
@@ -0,0 +1,193 @@
+use std::{
+    env,
+    os::unix,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{Arc, Mutex},
+};
+
+use nu_ansi_term::Color::{Blue, Cyan, Green};
+use rusqlite::Connection;
+use soar_core::{
+    database::{
+        models::Package,
+        packages::{PackageQueryBuilder, ProvideStrategy},
+    },
+    error::{ErrorContext, SoarError},
+    package::query::PackageQuery,
+    SoarResult,
+};
+use tracing::{info, warn};
+
+use crate::{
+    run::{cached_package, evict_run_cache, TempRunDir},
+    state::AppState,
+    utils::{select_package_interactively, Colored},
+};
+
+/// Resolves the queries to packages, the same way as on install. Packages of
+/// a family are all selected if only the pkg_id is given.
+fn resolve_shell_packages(
+    repo_db: &Arc<Mutex<Connection>>,
+    packages: &[String],
+    yes: bool,
+) -> SoarResult<Vec<Package>> {
+    let mut resolved = Vec::new();
+
+    for package in packages {
+        let mut query = PackageQuery::try_from(package.as_str())?;
+        let builder = PackageQueryBuilder::new(repo_db.clone());
+
+        if query.pkg_id.as_deref() == Some("all") {
+            let pkgs: Vec<Package> = query.apply_filters(builder.clone()).load()?.items;
+            let name = query.name.clone().unwrap_or(package.clone());
+            let pkg = match pkgs.len() {
+                0 => return Err(SoarError::PackageNotFound(package.clone())),
+                1 => pkgs.into_iter().next(),
+                _ => select_package_interactively(pkgs, &name)?,
+            }
+            .unwrap();
+            query.pkg_id = Some(pkg.pkg_id);
+            query.name = None;
+        }
+
+        let pkgs: Vec<Package> = query.apply_filters(builder).load()?.items;
+        if pkgs.is_empty() {
+            return Err(SoarError::PackageNotFound(package.clone()));
+        }
+
+        if query.name.is_none() && query.pkg_id.is_some() {
+            resolved.extend(pkgs);
+            continue;
+        }
+
+        let pkg = match pkgs.len() {
+            1 => pkgs.into_iter().next(),
+            _ if yes => pkgs.into_iter().next(),
+            _ => select_package_interactively(pkgs, package)?,
+        }
+        .unwrap();
+        resolved.push(pkg);
+    }
+
+    Ok(resolved)
+}
+
+/// Links the package binaries into the bin directory, keeping binaries of the
+/// packages listed first on conflicts.
+fn link_shell_binaries(package: &Package, package_dir: &Path, bin_dir: &Path) -> SoarResult<()> {
+    let mut links: Vec<(PathBuf, String)> = Vec::new();
+
+    match package.provides.as_deref() {
+        Some(provides) if !provides.is_empty() => {
+            for provide in provides {
+                let real_path = package_dir.join(&provide.name);
+                if let (Some(target), Some(_)) = (&provide.target, &provide.strategy) {
+                    links.push((real_path.clone(), target.clone()));
+                }
+                let needs_original = matches!(
+                    (provide.target.as_ref(), provide.strategy.as_ref()),
+                    (Some(_), Some(ProvideStrategy::KeepBoth)) | (None, _)
+                );
+                if needs_original {
+                    links.push((real_path, provide.name.clone()));
+                }
+            }
+        }
+        _ => links.push((
+            package_dir.join(&package.pkg_name),
+            package.pkg_name.clone(),
+        )),
+    }
+
+    let mut linked = 0;
+    for (real_path, name) in links {
+        if !real_path.is_file() {
+            continue;
+        }
+        let link = bin_dir.join(&name);
+        if link.symlink_metadata().is_ok() {
+            warn!(
+                "{} from {}#{} is shadowed by another package",
+                name, package.pkg_name, package.pkg_id
+            );
+            continue;
+        }
+        unix::fs::symlink(&real_path, &link).with_context(|| {
+            format!(
+                "creating symlink {} -> {}",
+                real_path.display(),
+                link.display()
+            )
+        })?;
+        linked += 1;
+    }
+
+    if linked == 0 {
+        warn!(
+            "{}#{} has no binaries to add to PATH",
+            package.pkg_name, package.pkg_id
+        );
+    }
+
+    Ok(())
+}
+
+/// Spawns a shell with the packages in PATH, without installing them. The
+/// packages are kept in the run cache, their links are removed on exit.
+/// Returns the exit code of the shell.
+pub async fn enter_shell(packages: &[String], yes: bool, command: Option<&str>) -> SoarResult<i32> {
+    let state = AppState::new();
+    let config = state.config();
+    let repo_db = state.repo_db().await?;
+    let run_cache = config.get_run_cache_path()?;
+
+    let packages = resolve_shell_packages(repo_db, packages, yes)?;
+
+    let mut entries = Vec::new();
+    for package in packages {
+        let entry_dir = cached_package(&run_cache, &package).await?;
+        entries.push((entry_dir, package));
+    }
+
+    let in_use: Vec<&Path> = entries
+        .iter()
+        .map(|(entry_dir, _)| entry_dir.as_path())
+        .collect();
+    evict_run_cache(&run_cache, &in_use, config.run_cache_limit());
+
+    let bin_dir = TempRunDir::new("soar-shell")?;
+    for (entry_dir, package) in &entries {
+        link_shell_binaries(package, entry_dir, bin_dir.path())?;
+        info!(
+            pkg_name = package.pkg_name,
+            pkg_id = package.pkg_id,
+            repo_name = package.repo_name,
+            version = package.version,
+            "Added {}#{}:{} ({})",
+            Colored(Blue, &package.pkg_name),
+            Colored(Cyan, &package.pkg_id),
+            Colored(Green, &package.repo_name),
+            package.version
+        );
+    }
+
+    let path = env::join_paths(
+        std::iter::once(bin_dir.path().to_path_buf())
+            .chain(env::split_paths(&env::var_os("PATH").unwrap_or_default())),
+    )
+    .map_err(|err| SoarError::Custom(format!("Failed to set PATH: {}", err)))?;
+
+    let shell = env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string());
+    let mut cmd = Command::new(&shell);
+    cmd.env("PATH", path);
+    if let Some(command) = command {
+        cmd.arg("-c").arg(command);
+    }
+
+    let status = cmd
+        .status()
+        .with_context(|| format!("executing shell {}", shell))?;
+
+    Ok(status.code().unwrap_or(1))
+}
@@ -6,9 +6,9 @@ use std::{
 
 use nu_ansi_term::Color::{Blue, Green, Magenta, Red};
 use once_cell::sync::OnceCell;
-use rusqlite::{params, Connection};
+use rusqlite::{params, Connection, OpenFlags};
 use soar_core::{
-    config::{get_config, Config, Repository},
+    config::{get_config, has_write_access, is_system_mode, Config, Repository},
     constants::CORE_MIGRATIONS,
     database::{
         connection::Database,
@@ -17,6 +17,7 @@ use soar_core::{
         packages::{FilterCondition, PackageQueryBuilder},
     },
     error::{ErrorContext, SoarError},
+    lock::StoreLock,
     metadata::fetch_metadata,
     SoarResult,
 };
@@ -172,17 +173,36 @@ impl AppState {
             })
             .collect();
 
-        Database::new_multi(repo_paths.as_ref())
+        let db = Database::new_multi(repo_paths.as_ref())?;
+        if has_write_access() {
+            db.ensure_search_index()?;
+        }
+        Ok(db)
     }
 
     fn create_core_db(&self) -> SoarResult<Database> {
         let core_db_file = self.inner.config.get_db_path()?.join("soar.db");
+        if !has_write_access() {
+            let conn =
+                Connection::open_with_flags(&core_db_file, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+            return Ok(Database {
+                conn: Arc::new(Mutex::new(conn)),
+            });
+        }
         if !core_db_file.exists() {
             File::create(&core_db_file)
                 .with_context(|| format!("creating database file {}", core_db_file.display()))?;
         }
 
         let conn = Connection::open(&core_db_file)?;
+        // readers without write access to the directory can't open WAL
+        // databases, so the system-wide database keeps the rollback journal
+        let journal_mode = if is_system_mode() { "DELETE" } else { "WAL" };
+        let _: String = conn.query_row(
+            &format!("PRAGMA journal_mode = {}", journal_mode),
+            [],
+            |row| row.get(0),
+        )?;
         let mut manager = MigrationManager::new(conn)?;
         manager.migrate_from_dir(CORE_MIGRATIONS)?;
         Database::new(&core_db_file)
@@ -193,7 +213,26 @@ impl AppState {
     }
 
     pub async fn repo_db(&self) -> SoarResult<&Arc<Mutex<Connection>>> {
-        self.init_repo_dbs(false).await?;
+        // repositories of the system-wide installation are synced by root
+        if has_write_access() {
+            // read-only commands don't hold the store lock; if another soar
+            // process holds it, the metadata is used as it is on disk
+            let _lock = if StoreLock::is_held() {
+                None
+            } else {
+                match StoreLock::try_acquire(self.inner.config.get_db_path()?)? {
+                    Some(lock) => Some(lock),
+                    None => return self.local_repo_db(),
+                }
+            };
+            self.init_repo_dbs(false).await?;
+        }
+        self.local_repo_db()
+    }
+
+    /// Returns the metadata databases as they are on disk, without syncing
+    /// stale repositories.
+    pub fn local_repo_db(&self) -> SoarResult<&Arc<Mutex<Connection>>> {
         self.inner
             .repo_db
             .get_or_try_init(|| self.create_repo_db())
@@ -171,6 +171,7 @@ pub async fn update_packages(
         None,
         None,
         false,
+        false,
     );
 
     perform_update(ctx, update_targets, core_db.clone(), keep).await?;
@@ -9,12 +9,16 @@ use soar_core::{
         models::{InstalledPackage, Package},
         packages::{FilterCondition, PackageQueryBuilder, SortDirection},
     },
-    package::formats::common::integrate_package,
+    package::{
+        files::{collect_package_files, record_package_files},
+        formats::common::{integrate_package, symlink_shell_integration},
+    },
     SoarResult,
 };
 use tracing::info;
 
 use crate::{
+    sandbox::reapply_installed_sandbox,
     state::AppState,
     utils::{get_valid_selection, has_desktop_integration, mangle_package_symlinks, Colored},
 };
@@ -76,30 +80,18 @@ pub async fn use_alternate_package(name: &str) -> SoarResult<()> {
         ..
     } = &selected_package;
 
-    let mut conn = db.lock().unwrap();
-
-    let tx = conn.transaction()?;
-
-    {
-        let mut stmt = prepare_and_bind!(
-            tx,
-            "UPDATE packages
-            SET
-                unlinked = true
-            WHERE
-                pkg_name = $pkg_name
-                AND pkg_id != $pkg_id
-                AND checksum != $checksum
-            "
-        );
-        stmt.raw_execute()?;
-    }
-
-    let bin_dir = get_config().get_bin_path()?;
+    let bin_dir = get_config().get_profile_bin_path(&selected_package.profile)?;
     let install_dir = PathBuf::from(&selected_package.installed_path);
 
-    let _ = mangle_package_symlinks(&install_dir, &bin_dir, selected_package.provides.as_deref())
-        .await?;
+    let symlinks = mangle_package_symlinks(
+        &install_dir,
+        &bin_dir,
+        selected_package.provides.as_deref(),
+        &selected_package.pkg_name,
+        db,
+    )
+    .await?;
+    let mut created_paths: Vec<PathBuf> = symlinks.into_iter().map(|(_, link)| link).collect();
 
     // TODO: handle portable_dirs
     let repo_db = state.repo_db().await?;
@@ -117,26 +109,62 @@ pub async fn use_alternate_package(name: &str) -> SoarResult<()> {
         .load()?
         .items;
 
-    if pkg.iter().all(|p| has_desktop_integration(&p)) {
-        integrate_package(&install_dir, &selected_package, None, None, None, None).await?;
+    if pkg.iter().all(has_desktop_integration) {
+        created_paths.extend(
+            integrate_package(
+                &install_dir,
+                &bin_dir,
+                &selected_package,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await?,
+        );
     }
+    created_paths.extend(symlink_shell_integration(&install_dir)?);
 
     {
-        let mut stmt = prepare_and_bind!(
-            tx,
-            "UPDATE packages
-            SET
-                unlinked = false
-            WHERE
-                pkg_name = $pkg_name
-                AND pkg_id == $pkg_id
-                AND checksum == $checksum"
-        );
-        stmt.raw_execute()?;
+        let mut conn = db.lock()?;
+        let files = collect_package_files(&install_dir, &created_paths)?;
+        record_package_files(&conn, selected_package.id, &files)?;
+        reapply_installed_sandbox(&conn, &selected_package)?;
+
+        let tx = conn.transaction()?;
+
+        {
+            let mut stmt = prepare_and_bind!(
+                tx,
+                "UPDATE packages
+                SET
+                    unlinked = true
+                WHERE
+                    pkg_name = $pkg_name
+                    AND pkg_id != $pkg_id
+                    AND checksum != $checksum
+                "
+            );
+            stmt.raw_execute()?;
+        }
+
+        {
+            let mut stmt = prepare_and_bind!(
+                tx,
+                "UPDATE packages
+                SET
+                    unlinked = false
+                WHERE
+                    pkg_name = $pkg_name
+                    AND pkg_id == $pkg_id
+                    AND checksum == $checksum"
+            );
+            stmt.raw_execute()?;
+        }
+
+        tx.commit()?;
     }
 
-    tx.commit()?;
-
     info!("Switched to {}#{}", pkg_name, pkg_id);
 
     Ok(())
@@ -2,29 +2,36 @@ use std::{
     collections::HashSet,
     fmt::Display,
     fs,
-    io::Write,
+    io::{BufReader, IsTerminal, Read, Write},
     os::unix,
     path::{Path, PathBuf},
-    sync::{LazyLock, RwLock},
+    sync::{Arc, LazyLock, Mutex, RwLock},
 };
 
 use indicatif::HumanBytes;
+use minisign_verify::{PublicKey, Signature};
 use nu_ansi_term::Color::{self, Blue, Cyan, Green, LightRed, Magenta, Red};
+use rusqlite::Connection;
 use serde::Serialize;
 use soar_core::{
-    config::get_config,
+    config::{get_config, BinConflictPolicy},
     database::{
-        models::{Package, PackageExt},
-        packages::{PackageProvide, ProvideStrategy},
+        models::{InstalledPackage, Package, PackageExt},
+        packages::{PackageProvide, PackageQueryBuilder, ProvideStrategy},
     },
     error::{ErrorContext, SoarError},
-    package::install::InstallTarget,
+    package::{
+        files::find_path_owner,
+        formats::{archive::package_bin_dirs, script::is_script},
+        install::InstallTarget,
+        provenance::pubkey_fingerprint,
+    },
     repositories::get_platform_repositories,
     utils::get_platform,
     SoarResult,
 };
 use soar_dl::utils::{is_elf, FileMode};
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 pub static COLOR: LazyLock<RwLock<bool>> = LazyLock::new(|| RwLock::new(true));
 
@@ -169,10 +176,88 @@ pub fn get_file_mode(skip_existing: bool, force_overwrite: bool) -> FileMode {
     }
 }
 
+/// Finds the installed package owning the entry in the bin directory.
+fn bin_entry_owner(
+    path: &Path,
+    core_db: &Arc<Mutex<Connection>>,
+) -> SoarResult<Option<InstalledPackage>> {
+    let owner_id = {
+        let conn = core_db.lock()?;
+        find_path_owner(&conn, path)?
+    };
+
+    let installed = PackageQueryBuilder::new(core_db.clone())
+        .load_installed()?
+        .items;
+
+    // fallback for packages installed before files were recorded
+    let real_path = fs::read_link(path).ok();
+    Ok(installed.into_iter().find(|package| match owner_id {
+        Some(id) => package.id == id,
+        None => real_path
+            .as_ref()
+            .is_some_and(|real_path| real_path.starts_with(&package.installed_path)),
+    }))
+}
+
+/// Decides whether the existing entry in the bin directory can be replaced by
+/// the package, according to the configured conflict policy.
+fn resolve_bin_conflict(
+    path: &Path,
+    pkg_name: &str,
+    core_db: &Arc<Mutex<Connection>>,
+) -> SoarResult<bool> {
+    if !path.is_symlink() && !path.exists() {
+        return Ok(true);
+    }
+
+    let owner = match bin_entry_owner(path, core_db)? {
+        // alternate versions of the same package take over each other's links
+        Some(owner) if owner.pkg_name == pkg_name => return Ok(true),
+        Some(owner) => format!("{}#{}:{}", owner.pkg_name, owner.pkg_id, owner.repo_name),
+        // stale link left behind by a removed package
+        None if !path.exists() => return Ok(true),
+        None => "a file not managed by soar".to_string(),
+    };
+
+    match get_config().bin_conflict.unwrap_or_default() {
+        BinConflictPolicy::Overwrite => {
+            warn!(
+                "Overwriting {} provided by {}",
+                Colored(Blue, path.display()),
+                Colored(Red, &owner)
+            );
+            Ok(true)
+        }
+        BinConflictPolicy::KeepExisting => {
+            warn!(
+                "Skipped linking {}, already provided by {}",
+                Colored(Blue, path.display()),
+                Colored(Red, &owner)
+            );
+            Ok(false)
+        }
+        BinConflictPolicy::Prompt if std::io::stdin().is_terminal() => {
+            let response = interactive_ask(&format!(
+                "{} is already provided by {}. Overwrite? [y/N] ",
+                Colored(Blue, path.display()),
+                Colored(Red, &owner)
+            ))?;
+            Ok(response.to_lowercase().starts_with('y'))
+        }
+        BinConflictPolicy::Prompt | BinConflictPolicy::Fail => Err(SoarError::BinaryConflict {
+            path: path.display().to_string(),
+            owner,
+        }),
+    }
+}
+
 pub async fn mangle_package_symlinks(
     install_dir: &Path,
     bin_dir: &Path,
     provides: Option<&[PackageProvide]>,
+    pkg_name: &str,
+    core_db: &Arc<Mutex<Connection>>,
 ) -> SoarResult<Vec<(PathBuf, PathBuf)>> {
     let mut symlinks = Vec::new();
 
@@ -191,11 +276,10 @@ pub async fn mangle_package_symlinks(
             }
         };
 
-        let needs_original_symlink = match (provide.target.as_ref(), provide.strategy.clone()) {
-            (Some(_), Some(ProvideStrategy::KeepBoth)) => true,
-            (None, _) => true,
-            _ => false,
-        };
+        let needs_original_symlink = matches!(
+            (provide.target.as_ref(), provide.strategy.clone()),
+            (Some(_), Some(ProvideStrategy::KeepBoth)) | (None, _)
+        );
 
         if needs_original_symlink {
             let original_path = bin_dir.join(&provide.name);
@@ -205,6 +289,9 @@ pub async fn mangle_package_symlinks(
         }
 
         for target_path in symlink_targets {
+            if !resolve_bin_conflict(&target_path, pkg_name, core_db)? {
+                continue;
+            }
             if target_path.is_symlink() || target_path.is_file() {
                 std::fs::remove_file(&target_path)
                     .with_context(|| format!("removing provide {}", target_path.display()))?;
@@ -227,46 +314,180 @@ pub async fn mangle_package_symlinks(
         } else {
             (false, install_dir)
         };
-        for entry in fs::read_dir(binaries_dir).with_context(|| {
-            format!(
-                "reading install directory {} for ELF detection",
-                install_dir.display()
-            )
-        })? {
-            let path = entry
-                .with_context(|| {
-                    format!(
-                        "reading entry in directory {} for ELF detection",
-                        install_dir.display()
-                    )
-                })?
-                .path();
-            if path.is_file() && (is_syms || is_elf(&path).await) {
-                if let Some(file_name) = path.file_name() {
-                    let symlink_target_path = bin_dir.join(file_name);
-                    if symlink_target_path.is_symlink() || symlink_target_path.is_file() {
-                        std::fs::remove_file(&symlink_target_path).with_context(|| {
-                            format!(
-                                "removing existing file/symlink at {}",
-                                symlink_target_path.display()
-                            )
-                        })?;
-                    }
-                    unix::fs::symlink(&path, &symlink_target_path).with_context(|| {
+        // executables of trees, like tarballs or .deb payloads, are in bin dirs
+        let mut binaries_dirs = vec![(binaries_dir.to_path_buf(), false)];
+        if !is_syms {
+            binaries_dirs.extend(
+                package_bin_dirs(install_dir)
+                    .into_iter()
+                    .map(|dir| (dir, true)),
+            );
+        }
+
+        for (binaries_dir, is_bin_dir) in binaries_dirs {
+            for entry in fs::read_dir(&binaries_dir).with_context(|| {
+                format!(
+                    "reading install directory {} for ELF detection",
+                    binaries_dir.display()
+                )
+            })? {
+                let path = entry
+                    .with_context(|| {
+                        format!(
+                            "reading entry in directory {} for ELF detection",
+                            binaries_dir.display()
+                        )
+                    })?
+                    .path();
+                if !path.is_file() {
+                    continue;
+                }
+                let Some(file_name) = path.file_name() else {
+                    continue;
+                };
+                // only the main binary may be a script outside of bin dirs
+                let is_executable = is_syms
+                    || is_elf(&path).await
+                    || ((is_bin_dir || file_name == pkg_name) && is_script(&path));
+                if !is_executable {
+                    continue;
+                }
+
+                let symlink_target_path = bin_dir.join(file_name);
+                if !processed_paths.insert(symlink_target_path.clone()) {
+                    continue;
+                }
+                if !resolve_bin_conflict(&symlink_target_path, pkg_name, core_db)? {
+                    continue;
+                }
+                if symlink_target_path.is_symlink() || symlink_target_path.is_file() {
+                    std::fs::remove_file(&symlink_target_path).with_context(|| {
                         format!(
-                            "creating ELF symlink {} -> {}",
-                            path.display(),
+                            "removing existing file/symlink at {}",
                             symlink_target_path.display()
                         )
                     })?;
-                    symlinks.push((path.clone(), symlink_target_path.clone()));
                 }
+                unix::fs::symlink(&path, &symlink_target_path).with_context(|| {
+                    format!(
+                        "creating ELF symlink {} -> {}",
+                        path.display(),
+                        symlink_target_path.display()
+                    )
+                })?;
+                symlinks.push((path.clone(), symlink_target_path.clone()));
             }
         }
     }
     Ok(symlinks)
 }
 
+/// Loads the minisign public key stored in the repository directory, if any.
+pub fn repository_pubkey(repository_path: &Path) -> SoarResult<Option<PublicKey>> {
+    let pubkey_file = repository_path.join("minisign.pub");
+    if !pubkey_file.exists() {
+        return Ok(None);
+    }
+
+    let pubkey = PublicKey::from_base64(
+        fs::read_to_string(&pubkey_file)
+            .with_context(|| format!("reading minisign key from {}", pubkey_file.display()))?
+            .trim(),
+    )
+    .map_err(|err| {
+        SoarError::Custom(format!(
+            "Failed to load public key from {}: {}",
+            pubkey_file.display(),
+            err
+        ))
+    })?;
+
+    Ok(Some(pubkey))
+}
+
+pub fn verify_signature(pubkey: &PublicKey, file: &Path, signature_file: &Path) -> SoarResult<()> {
+    let signature = Signature::from_file(signature_file).map_err(|err| {
+        SoarError::Custom(format!(
+            "Failed to load signature file from {}: {}",
+            signature_file.display(),
+            err
+        ))
+    })?;
+    let mut stream_verifier = pubkey
+        .verify_stream(&signature)
+        .map_err(|err| SoarError::Custom(format!("Failed to setup stream verifier: {}", err)))?;
+
+    let reader = fs::File::open(file)
+        .with_context(|| format!("opening file {} for signature verification", file.display()))?;
+    let mut buf_reader = BufReader::new(reader);
+
+    let mut buffer = [0u8; 8192];
+    loop {
+        match buf_reader
+            .read(&mut buffer)
+            .with_context(|| format!("reading to buffer from {}", file.display()))?
+        {
+            0 => break,
+            n => {
+                stream_verifier.update(&buffer[..n]);
+            }
+        }
+    }
+
+    stream_verifier.finalize().map_err(|_| {
+        SoarError::Custom(format!(
+            "Signature verification failed for {}",
+            file.display()
+        ))
+    })
+}
+
+pub enum SignatureCheck {
+    /// Signatures were verified, with the fingerprint of the repository key
+    Verified(Option<String>),
+    /// Verification is enabled but the repository has no public key
+    MissingPubkey,
+    /// Verification is disabled for the repository
+    Disabled,
+}
+
+/// Verifies the signature files in the package directory against the public
+/// key of the package repository.
+pub fn verify_package_signatures(
+    repo_name: &str,
+    package_dir: &Path,
+) -> SoarResult<SignatureCheck> {
+    let config = get_config();
+    let Some(repository) = config.get_repository(repo_name) else {
+        return Ok(SignatureCheck::Disabled);
+    };
+    if !repository.signature_verification() {
+        return Ok(SignatureCheck::Disabled);
+    }
+
+    let repository_path = repository.get_path()?;
+    let Some(pubkey) = repository_pubkey(&repository_path)? else {
+        return Ok(SignatureCheck::MissingPubkey);
+    };
+
+    let entries = fs::read_dir(package_dir)
+        .with_context(|| format!("reading package directory {}", package_dir.display()))?;
+    for entry in entries {
+        let path = entry
+            .with_context(|| format!("reading entry from directory {}", package_dir.display()))?
+            .path();
+        let is_signature_file = path.extension().map_or_else(|| false, |ext| ext == "sig");
+        let original_file = path.with_extension("");
+        if is_signature_file && path.is_file() && original_file.is_file() {
+            verify_signature(&pubkey, &original_file, &path)?;
+        }
+    }
+
+    Ok(SignatureCheck::Verified(pubkey_fingerprint(
+        &repository_path,
+    )))
+}
+
 pub fn parse_default_repos_arg(arg: &str) -> SoarResult<String> {
     let repo = arg.trim().to_lowercase();
     let platform = get_platform();
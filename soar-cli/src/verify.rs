@@ -0,0 +1,309 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use minisign_verify::PublicKey;
+use nu_ansi_term::Color::{Blue, Cyan, Green, Red, Yellow};
+use rusqlite::Connection;
+use soar_core::{
+    config::get_config,
+    database::{
+        models::{InstalledPackage, PackageFileKind},
+        packages::PackageQueryBuilder,
+    },
+    package::{
+        files::get_package_files, formats::appimage::APPIMAGE_EXTRACT_DIR,
+        provenance::get_provenance, query::PackageQuery,
+    },
+    utils::calculate_checksum,
+    SoarResult,
+};
+use tracing::{info, warn};
+
+use crate::{
+    state::AppState,
+    utils::{repository_pubkey, verify_signature, Colored},
+};
+
+#[derive(Debug, Clone)]
+pub enum VerifyIssue {
+    Incomplete,
+    MissingFile(PathBuf),
+    ChecksumMismatch {
+        path: PathBuf,
+        expected: String,
+        actual: String,
+    },
+    InvalidSignature(PathBuf),
+    DanglingSymlink(PathBuf),
+    ForeignSymlink {
+        path: PathBuf,
+        target: PathBuf,
+    },
+}
+
+impl VerifyIssue {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            VerifyIssue::Incomplete => "incomplete",
+            VerifyIssue::MissingFile(_) => "missing",
+            VerifyIssue::ChecksumMismatch { .. } => "checksum",
+            VerifyIssue::InvalidSignature(_) => "signature",
+            VerifyIssue::DanglingSymlink(_) => "dangling-symlink",
+            VerifyIssue::ForeignSymlink { .. } => "foreign-symlink",
+        }
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            VerifyIssue::Incomplete => None,
+            VerifyIssue::MissingFile(path)
+            | VerifyIssue::ChecksumMismatch { path, .. }
+            | VerifyIssue::InvalidSignature(path)
+            | VerifyIssue::DanglingSymlink(path)
+            | VerifyIssue::ForeignSymlink { path, .. } => Some(path),
+        }
+    }
+}
+
+impl Display for VerifyIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VerifyIssue::Incomplete => write!(f, "Installation is incomplete"),
+            VerifyIssue::MissingFile(path) => write!(f, "Missing file {}", path.display()),
+            VerifyIssue::ChecksumMismatch {
+                path,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Checksum mismatch for {} (expected {}, got {})",
+                path.display(),
+                expected,
+                actual
+            ),
+            VerifyIssue::InvalidSignature(path) => {
+                write!(f, "Invalid signature for {}", path.display())
+            }
+            VerifyIssue::DanglingSymlink(path) => {
+                write!(f, "Symlink {} points to a missing file", path.display())
+            }
+            VerifyIssue::ForeignSymlink { path, target } => write!(
+                f,
+                "Symlink {} points outside the package to {}",
+                path.display(),
+                target.display()
+            ),
+        }
+    }
+}
+
+fn symlink_target(path: &Path) -> Option<PathBuf> {
+    let target = fs::read_link(path).ok()?;
+    if target.is_absolute() {
+        Some(target)
+    } else {
+        path.parent().map(|parent| parent.join(target))
+    }
+}
+
+/// Verifies the retained signatures in the install directory, except the one
+/// of `replaced`, a file replaced since it was verified.
+fn verify_signatures(
+    install_dir: &Path,
+    pubkey: &PublicKey,
+    replaced: Option<&Path>,
+    issues: &mut Vec<VerifyIssue>,
+) {
+    let Ok(entries) = fs::read_dir(install_dir) else {
+        return;
+    };
+
+    for path in entries.filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+        let is_signature_file = path.extension().is_some_and(|ext| ext == "sig");
+        let original_file = path.with_extension("");
+        if is_signature_file
+            && original_file.is_file()
+            && replaced != Some(original_file.as_path())
+            && verify_signature(pubkey, &original_file, &path).is_err()
+        {
+            issues.push(VerifyIssue::InvalidSignature(original_file));
+        }
+    }
+}
+
+/// Checks the installed package against its recorded checksum, retained
+/// signatures and recorded files.
+pub fn verify_package(
+    conn: &Connection,
+    package: &InstalledPackage,
+    pubkey: Option<&PublicKey>,
+) -> SoarResult<Vec<VerifyIssue>> {
+    let mut issues = Vec::new();
+    let install_dir = PathBuf::from(&package.installed_path);
+
+    if !package.is_installed {
+        issues.push(VerifyIssue::Incomplete);
+    }
+
+    if !install_dir.is_dir() {
+        issues.push(VerifyIssue::MissingFile(install_dir));
+        return Ok(issues);
+    }
+
+    // checks the file the checksum was validated against on install, there's
+    // none if it belonged to a downloaded archive
+    let real_bin = install_dir.join(&package.pkg_name);
+    // extracted AppImages are replaced by a launcher
+    let is_extracted = install_dir.join(APPIMAGE_EXTRACT_DIR).is_dir();
+    let checksum_file = get_provenance(conn, package.id)?
+        .and_then(|provenance| provenance.checksum_file)
+        .map(PathBuf::from)
+        .filter(|path| path.is_file() && !(is_extracted && *path == real_bin));
+    if let (Some(expected), Some(path)) = (&package.checksum, checksum_file) {
+        let actual = calculate_checksum(&path)?;
+        if actual != *expected {
+            issues.push(VerifyIssue::ChecksumMismatch {
+                path,
+                expected: expected.clone(),
+                actual,
+            });
+        }
+    }
+
+    if let Some(pubkey) = pubkey {
+        let replaced = is_extracted.then_some(real_bin.as_path());
+        verify_signatures(&install_dir, pubkey, replaced, &mut issues);
+    }
+
+    for file in get_package_files(conn, package.id)? {
+        let path = PathBuf::from(&file.path);
+        if fs::symlink_metadata(&path).is_err() {
+            issues.push(VerifyIssue::MissingFile(path));
+            continue;
+        }
+
+        // links inside the package, like portable directories, may point anywhere
+        if file.kind != PackageFileKind::Symlink || path.starts_with(&install_dir) {
+            continue;
+        }
+
+        match symlink_target(&path) {
+            Some(target) if !target.starts_with(&install_dir) => {
+                issues.push(VerifyIssue::ForeignSymlink { path, target });
+            }
+            Some(target) if !target.exists() => {
+                issues.push(VerifyIssue::DanglingSymlink(path));
+            }
+            Some(_) => {}
+            None => issues.push(VerifyIssue::MissingFile(path)),
+        }
+    }
+
+    Ok(issues)
+}
+
+/// Loads the public key used to verify retained signatures of the repository's
+/// packages, if signature verification is enabled for it.
+pub fn signature_pubkey(
+    repo_name: &str,
+    cache: &mut HashMap<String, Option<PublicKey>>,
+) -> SoarResult<Option<PublicKey>> {
+    if let Some(pubkey) = cache.get(repo_name) {
+        return Ok(pubkey.clone());
+    }
+
+    let pubkey = match get_config().get_repository(repo_name) {
+        Some(repository) if repository.signature_verification() => {
+            repository_pubkey(&repository.get_path()?)?
+        }
+        _ => None,
+    };
+    cache.insert(repo_name.to_string(), pubkey.clone());
+
+    Ok(pubkey)
+}
+
+pub async fn verify_packages(package: Option<String>) -> SoarResult<bool> {
+    let state = AppState::new();
+    let core_db = state.core_db()?;
+
+    let builder = PackageQueryBuilder::new(core_db.clone());
+    let builder = match package {
+        Some(ref package) => PackageQuery::try_from(package.as_str())?.apply_filters(builder),
+        None => builder,
+    };
+    let installed_pkgs = builder.load_installed()?.items;
+
+    if installed_pkgs.is_empty() {
+        match package {
+            Some(package) => warn!("Package {} is not installed.", package),
+            None => info!("No installed packages to verify."),
+        }
+        return Ok(true);
+    }
+
+    let mut pubkeys = HashMap::new();
+    let mut failed_count = 0;
+
+    for installed_pkg in &installed_pkgs {
+        let pubkey = signature_pubkey(&installed_pkg.repo_name, &mut pubkeys)?;
+        let issues = {
+            let conn = core_db.lock()?;
+            verify_package(&conn, installed_pkg, pubkey.as_ref())?
+        };
+
+        info!(
+            pkg_name = installed_pkg.pkg_name,
+            pkg_id = installed_pkg.pkg_id,
+            repo_name = installed_pkg.repo_name,
+            version = installed_pkg.version,
+            ok = issues.is_empty(),
+            "{} {}#{}:{}",
+            if issues.is_empty() {
+                Colored(Green, "[OK]    ")
+            } else {
+                Colored(Red, "[FAILED]")
+            },
+            Colored(Blue, &installed_pkg.pkg_name),
+            Colored(Cyan, &installed_pkg.pkg_id),
+            Colored(Green, &installed_pkg.repo_name)
+        );
+
+        if !issues.is_empty() {
+            failed_count += 1;
+        }
+
+        for issue in issues {
+            info!(
+                pkg_name = installed_pkg.pkg_name,
+                pkg_id = installed_pkg.pkg_id,
+                repo_name = installed_pkg.repo_name,
+                issue = issue.kind(),
+                path = issue.path().map(|path| path.display().to_string()),
+                "  {}",
+                Colored(Yellow, &issue)
+            );
+        }
+    }
+
+    info!(
+        verified = installed_pkgs.len(),
+        failed = failed_count,
+        "\nVerified {} packages, {} failed",
+        installed_pkgs.len(),
+        Colored(if failed_count > 0 { Red } else { Green }, failed_count)
+    );
+
+    if failed_count > 0 {
+        info!(
+            "Failed packages can be repaired using command: {}",
+            Colored(Green, "soar repair")
+        );
+    }
+
+    Ok(failed_count == 0)
+}
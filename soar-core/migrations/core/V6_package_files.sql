@@ -0,0 +1,10 @@
+CREATE TABLE package_files (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  package_id INTEGER NOT NULL,
+  path TEXT NOT NULL,
+  kind TEXT NOT NULL,
+  FOREIGN KEY (package_id) REFERENCES packages (id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_package_files_package_id ON package_files (package_id);
+CREATE INDEX idx_package_files_path ON package_files (path);
@@ -0,0 +1,14 @@
+CREATE TABLE package_provenance (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  package_id INTEGER NOT NULL UNIQUE,
+  repo_name TEXT NOT NULL,
+  download_url TEXT NOT NULL,
+  ghcr_digest TEXT,
+  build_id TEXT,
+  build_action TEXT,
+  pubkey_fingerprint TEXT,
+  signature_verified BOOLEAN NOT NULL DEFAULT false,
+  signatures JSONB,
+  recorded_date TEXT NOT NULL,
+  FOREIGN KEY (package_id) REFERENCES packages (id) ON DELETE CASCADE
+);
@@ -0,0 +1,11 @@
+CREATE TABLE sandbox_profiles (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  repo_name TEXT NOT NULL,
+  pkg_id TEXT NOT NULL,
+  pkg_name TEXT NOT NULL,
+  allow JSONB,
+  allow_ro JSONB,
+  network BOOLEAN NOT NULL DEFAULT true,
+  isolate_home BOOLEAN NOT NULL DEFAULT true,
+  UNIQUE (repo_name, pkg_id, pkg_name)
+);
@@ -0,0 +1 @@
+ALTER TABLE package_provenance ADD COLUMN checksum_file TEXT;
@@ -1,22 +1,27 @@
 use std::{
     collections::{HashMap, HashSet},
     fs,
-    path::PathBuf,
-    sync::{LazyLock, RwLock},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        LazyLock, RwLock,
+    },
 };
 
 use documented::{Documented, DocumentedFields};
+use nix::unistd::geteuid;
 use serde::{de::Error, Deserialize, Serialize};
-use toml_edit::{DocumentMut, Item};
+use toml_edit::{value, DocumentMut, Item, Table};
 use tracing::{info, warn};
 
 use crate::{
+    constants::{SYSTEM_BIN_PATH, SYSTEM_CONFIG_PATH, SYSTEM_ROOT_PATH},
     error::{ConfigError, SoarError},
     repositories::get_platform_repositories,
     toml::{annotate_toml_array_of_tables, annotate_toml_table},
     utils::{
-        build_path, default_install_patterns, get_platform, home_config_path, home_data_path,
-        parse_duration,
+        build_path, default_install_patterns, fuse_available, get_platform, home_cache_path,
+        home_config_path, home_data_path, parse_duration, parse_size,
     },
     SoarResult,
 };
@@ -146,19 +151,22 @@ pub struct Config {
     /// List of configured repositories.
     pub repositories: Vec<Repository>,
 
-    /// Path to the local cache directory.
+    /// Path to the local cache directory. Only applies to the default profile.
     /// Default: $SOAR_ROOT/cache
     pub cache_path: Option<String>,
 
-    /// Path where the Soar package database is stored.
+    /// Path where the Soar package database is stored. Only applies to the
+    /// default profile.
     /// Default: $SOAR_ROOT/db
     pub db_path: Option<String>,
 
-    /// Directory where binary symlinks are placed.
-    /// Default: $SOAR_ROOT/bin
+    /// Directory where binary symlinks are placed. Only applies to the default
+    /// profile.
+    /// Default: $SOAR_ROOT/bin, or /usr/local/bin in system mode
     pub bin_path: Option<String>,
 
-    /// Path to the local clone of all repositories.
+    /// Path to the local clone of all repositories. Only applies to the
+    /// default profile.
     /// Default: $SOAR_ROOT/packages
     pub repositories_path: Option<String>,
 
@@ -194,11 +202,47 @@ pub struct Config {
 
     /// Global override for sync interval
     pub sync_interval: Option<String>,
+
+    /// What to do when a binary link is already provided by another package.
+    /// Allowed values: fail, prompt, keep-existing, overwrite
+    /// Default: prompt
+    pub bin_conflict: Option<BinConflictPolicy>,
+
+    /// Refuse to install packages without a blake3 checksum.
+    /// Default: false
+    pub require_checksum: Option<bool>,
+
+    /// Maximum size of the cache used by `soar run`, e.g. 500MB.
+    /// Least recently used packages are evicted above it.
+    /// Default: 1GB
+    pub run_cache_size: Option<String>,
+
+    /// Extract AppImages on install and run them from the extracted
+    /// directory, for systems without FUSE.
+    /// Default: true if /dev/fuse is unavailable, false otherwise
+    pub appimage_extract: Option<bool>,
+}
+
+/// Policy for binary links in `bin_path` owned by another package.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum BinConflictPolicy {
+    /// Abort the installation
+    Fail,
+    /// Ask whether to overwrite, failing if not interactive
+    #[default]
+    Prompt,
+    /// Keep the existing link and skip linking the binary
+    KeepExisting,
+    /// Replace the existing link
+    Overwrite,
 }
 
 pub static CONFIG: LazyLock<RwLock<Option<Config>>> = LazyLock::new(|| RwLock::new(None));
 pub static CURRENT_PROFILE: LazyLock<RwLock<Option<String>>> = LazyLock::new(|| RwLock::new(None));
 
+pub static SYSTEM_MODE: AtomicBool = AtomicBool::new(false);
+
 pub static CONFIG_PATH: LazyLock<RwLock<PathBuf>> = LazyLock::new(|| {
     RwLock::new(match std::env::var("SOAR_CONFIG") {
         Ok(path_str) => PathBuf::from(path_str),
@@ -208,6 +252,32 @@ pub static CONFIG_PATH: LazyLock<RwLock<PathBuf>> = LazyLock::new(|| {
     })
 });
 
+/// Switches to the system-wide store shared by all users, configured by
+/// `/etc/soar/config.toml`.
+pub fn enable_system_mode() {
+    SYSTEM_MODE.store(true, Ordering::Relaxed);
+    let mut config_path = CONFIG_PATH.write().unwrap();
+    *config_path = PathBuf::from(SYSTEM_CONFIG_PATH);
+}
+
+pub fn is_system_mode() -> bool {
+    SYSTEM_MODE.load(Ordering::Relaxed)
+}
+
+/// Whether the system config should be used because the user has no config
+/// of their own.
+pub fn system_config_available() -> bool {
+    std::env::var("SOAR_CONFIG").is_err()
+        && !CONFIG_PATH.read().unwrap().exists()
+        && Path::new(SYSTEM_CONFIG_PATH).exists()
+}
+
+/// Whether the store can be modified. The system store is only writable by
+/// root, other users can only read it.
+pub fn has_write_access() -> bool {
+    !is_system_mode() || geteuid().is_root()
+}
+
 pub fn init() -> Result<()> {
     let config = Config::new()?;
     let mut global_config = CONFIG.write().unwrap();
@@ -255,8 +325,18 @@ pub fn set_current_profile(name: &str) -> Result<()> {
 
 impl Config {
     pub fn default_config<T: AsRef<str>>(external: bool, selected_repos: &[T]) -> Self {
-        let soar_root =
-            std::env::var("SOAR_ROOT").unwrap_or_else(|_| format!("{}/soar", home_data_path()));
+        let soar_root = std::env::var("SOAR_ROOT").unwrap_or_else(|_| {
+            if is_system_mode() {
+                SYSTEM_ROOT_PATH.to_string()
+            } else {
+                format!("{}/soar", home_data_path())
+            }
+        });
+        let bin_path = if is_system_mode() {
+            SYSTEM_BIN_PATH.to_string()
+        } else {
+            format!("{}/bin", soar_root)
+        };
 
         let default_profile = Profile {
             root_path: soar_root.clone(),
@@ -277,10 +357,7 @@ impl Config {
             if repo_info.is_core || external || selected_set.contains(repo_info.name) {
                 repositories.push(Repository {
                     name: repo_info.name.to_string(),
-                    url: format!(
-                        "{}",
-                        repo_info.url_template.replace("{}", &current_platform)
-                    ),
+                    url: repo_info.url_template.replace("{}", &current_platform),
                     pubkey: repo_info.pubkey.map(String::from),
                     desktop_integration: repo_info.desktop_integration,
                     enabled: repo_info.enabled,
@@ -316,7 +393,7 @@ impl Config {
             profile: HashMap::from([(default_profile_name.clone(), default_profile)]),
             default_profile: default_profile_name,
 
-            bin_path: Some(format!("{}/bin", soar_root)),
+            bin_path: Some(bin_path),
             cache_path: Some(format!("{}/cache", soar_root)),
             db_path: Some(format!("{}/db", soar_root)),
             repositories_path: Some(format!("{}/repos", soar_root)),
@@ -332,6 +409,10 @@ impl Config {
             signature_verification: None,
             desktop_integration: None,
             sync_interval: None,
+            bin_conflict: Some(BinConflictPolicy::default()),
+            require_checksum: Some(false),
+            run_cache_size: Some("1GB".to_string()),
+            appimage_extract: None,
         }
     }
 
@@ -378,6 +459,13 @@ impl Config {
         self.ghcr_concurrency.get_or_insert(8);
         self.search_limit.get_or_insert(20);
         self.cross_repo_updates.get_or_insert(false);
+        self.bin_conflict.get_or_insert_default();
+        self.require_checksum.get_or_insert(false);
+        self.run_cache_size.get_or_insert_with(|| "1GB".to_string());
+        if is_system_mode() {
+            self.bin_path
+                .get_or_insert_with(|| SYSTEM_BIN_PATH.to_string());
+        }
 
         let mut seen_repos = HashSet::new();
 
@@ -431,24 +519,64 @@ impl Config {
             .ok_or(ConfigError::MissingProfile(name.to_string()))
     }
 
+    /// Returns the profile selected with `--profile`, or the default profile.
+    pub fn current_profile(&self) -> Result<&Profile> {
+        self.get_profile(&get_current_profile())
+    }
+
+    /// Top-level path overrides only apply to the default profile, other
+    /// profiles keep everything under their root.
+    fn path_override<'a>(&self, path: &'a Option<String>, profile_name: &str) -> Option<&'a str> {
+        path.as_deref()
+            .filter(|_| profile_name == self.default_profile)
+    }
+
     pub fn get_bin_path(&self) -> SoarResult<PathBuf> {
+        self.get_profile_bin_path(&get_current_profile())
+    }
+
+    pub fn get_profile_bin_path(&self, profile_name: &str) -> SoarResult<PathBuf> {
         if let Ok(env_path) = std::env::var("SOAR_BIN") {
             return build_path(&env_path);
         }
-        if let Some(bin_path) = &self.bin_path {
+        if let Some(bin_path) = self.path_override(&self.bin_path, profile_name) {
             return build_path(bin_path);
         }
-        self.default_profile()?.get_bin_path()
+        self.get_profile(profile_name)?.get_bin_path()
     }
 
     pub fn get_db_path(&self) -> SoarResult<PathBuf> {
+        self.get_profile_db_path(&get_current_profile())
+    }
+
+    pub fn get_profile_db_path(&self, profile_name: &str) -> SoarResult<PathBuf> {
         if let Ok(env_path) = std::env::var("SOAR_DB") {
             return build_path(&env_path);
         }
-        if let Some(soar_db) = &self.db_path {
+        if let Some(soar_db) = self.path_override(&self.db_path, profile_name) {
             return build_path(soar_db);
         }
-        self.default_profile()?.get_db_path()
+        self.get_profile(profile_name)?.get_db_path()
+    }
+
+    pub fn get_run_cache_path(&self) -> SoarResult<PathBuf> {
+        if !has_write_access() {
+            return Ok(PathBuf::from(home_cache_path()).join("soar").join("run"));
+        }
+        Ok(self.get_cache_path()?.join("run"))
+    }
+
+    /// Size limit of the run cache in bytes.
+    pub fn run_cache_limit(&self) -> u64 {
+        self.run_cache_size
+            .as_deref()
+            .and_then(parse_size)
+            .unwrap_or(1000 * 1000 * 1000)
+    }
+
+    /// Whether AppImages should be extracted on install.
+    pub fn extract_appimages(&self) -> bool {
+        self.appimage_extract.unwrap_or_else(|| !fuse_available())
     }
 
     pub fn get_packages_path(&self, profile_name: Option<String>) -> SoarResult<PathBuf> {
@@ -463,20 +591,22 @@ impl Config {
         if let Ok(env_path) = std::env::var("SOAR_CACHE") {
             return build_path(&env_path);
         }
-        if let Some(soar_cache) = &self.cache_path {
+        if let Some(soar_cache) = self.path_override(&self.cache_path, &get_current_profile()) {
             return build_path(soar_cache);
         }
-        self.get_profile(&get_current_profile())?.get_cache_path()
+        self.current_profile()?.get_cache_path()
     }
 
     pub fn get_repositories_path(&self) -> SoarResult<PathBuf> {
         if let Ok(env_path) = std::env::var("SOAR_REPOSITORIES") {
             return build_path(&env_path);
         }
-        if let Some(repositories_path) = &self.repositories_path {
+        if let Some(repositories_path) =
+            self.path_override(&self.repositories_path, &get_current_profile())
+        {
             return build_path(repositories_path);
         }
-        self.default_profile()?.get_repositories_path()
+        self.current_profile()?.get_repositories_path()
     }
 
     pub fn get_repository(&self, repo_name: &str) -> Option<&Repository> {
@@ -490,7 +620,7 @@ impl Config {
             return global_override;
         }
         self.get_repository(repo_name)
-            .map_or(false, |repo| repo.desktop_integration.unwrap_or(false))
+            .is_some_and(|repo| repo.desktop_integration.unwrap_or(false))
     }
 
     pub fn save(&self) -> Result<()> {
@@ -532,6 +662,110 @@ impl Config {
     }
 }
 
+/// Loads the configuration file as an editable document, keeping its comments.
+/// Falls back to the annotated current configuration if there's no file.
+fn load_config_document() -> Result<DocumentMut> {
+    let config_path = CONFIG_PATH.read().unwrap().to_path_buf();
+    match fs::read_to_string(&config_path) {
+        Ok(content) => content
+            .parse::<DocumentMut>()
+            .map_err(|e| ConfigError::TomlDeError(toml::de::Error::custom(e.to_string()))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => get_config().to_annotated_document(),
+        Err(err) => Err(ConfigError::IoError(err)),
+    }
+}
+
+/// Writes the edited document to the configuration file and reloads it.
+fn save_config_document(doc: &DocumentMut) -> Result<()> {
+    let config_path = CONFIG_PATH.read().unwrap().to_path_buf();
+
+    let mut config: Config = toml::from_str(&doc.to_string())?;
+    config.resolve()?;
+
+    if let Some(parent) = config_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&config_path, doc.to_string())?;
+    init()
+}
+
+pub fn add_profile(name: &str, profile: &Profile) -> Result<()> {
+    if get_config().profile.contains_key(name) {
+        return Err(ConfigError::ProfileAlreadyExists(name.to_string()));
+    }
+
+    let mut doc = load_config_document()?;
+    let profiles = doc
+        .entry("profile")
+        .or_insert_with(|| {
+            let mut table = Table::new();
+            table.set_implicit(true);
+            Item::Table(table)
+        })
+        .as_table_mut()
+        .ok_or_else(|| ConfigError::Custom("'profile' must be a table".to_string()))?;
+
+    let mut table = Table::new();
+    table.decor_mut().set_prefix("\n");
+    table.insert("root_path", value(&profile.root_path));
+    if let Some(ref packages_path) = profile.packages_path {
+        table.insert("packages_path", value(packages_path));
+    }
+    annotate_toml_table::<Profile>(&mut table, false)?;
+    profiles.insert(name, Item::Table(table));
+
+    save_config_document(&doc)
+}
+
+pub fn remove_profile(name: &str) -> Result<()> {
+    let config = get_config();
+    config.get_profile(name)?;
+    if config.default_profile == name {
+        return Err(ConfigError::DefaultProfileRemoval(name.to_string()));
+    }
+
+    let mut doc = load_config_document()?;
+    if let Some(profiles) = doc.get_mut("profile").and_then(Item::as_table_mut) {
+        profiles.remove(name);
+    }
+
+    save_config_document(&doc)
+}
+
+/// Makes the profile the default one. Top-level path overrides that only
+/// restate the paths of the previous default profile are dropped, so they
+/// don't carry over to the new default profile.
+pub fn set_default_profile(name: &str) -> Result<()> {
+    let config = get_config();
+    config.get_profile(name)?;
+    let previous = config.default_profile()?;
+
+    let derived = [
+        ("bin_path", previous.get_bin_path()),
+        ("db_path", previous.get_db_path()),
+        ("cache_path", previous.get_cache_path()),
+        ("repositories_path", previous.get_repositories_path()),
+    ];
+
+    let mut doc = load_config_document()?;
+    for (key, derived_path) in derived {
+        let Ok(derived_path) = derived_path else {
+            continue;
+        };
+        let redundant = doc
+            .get(key)
+            .and_then(Item::as_str)
+            .and_then(|path| build_path(path).ok())
+            .is_some_and(|path| path == derived_path);
+        if redundant {
+            doc.remove(key);
+        }
+    }
+    doc["default_profile"] = value(name);
+
+    save_config_document(&doc)
+}
+
 pub fn generate_default_config<T: AsRef<str>>(external: bool, repos: &[T]) -> Result<()> {
     let config_path = CONFIG_PATH.read().unwrap().to_path_buf();
 
@@ -5,6 +5,13 @@ pub const APPIMAGE_MAGIC_BYTES: [u8; 4] = [0x41, 0x49, 0x02, 0x00];
 pub const FLATIMAGE_MAGIC_BYTES: [u8; 4] = [0x46, 0x49, 0x01, 0x00];
 pub const RUNIMAGE_MAGIC_BYTES: [u8; 4] = [0x52, 0x49, 0x02, 0x00];
 pub const WRAPPE_MAGIC_BYTES: [u8; 8] = [0x50, 0x45, 0x33, 0x44, 0x41, 0x54, 0x41, 0x00];
+pub const AR_MAGIC_BYTES: [u8; 8] = [0x21, 0x3c, 0x61, 0x72, 0x63, 0x68, 0x3e, 0x0a];
+pub const RPM_MAGIC_BYTES: [u8; 4] = [0xed, 0xab, 0xee, 0xdb];
+pub const SHEBANG_MAGIC_BYTES: [u8; 2] = [0x23, 0x21];
+pub const TAR_MAGIC_BYTES: [u8; 5] = [0x75, 0x73, 0x74, 0x61, 0x72];
+pub const GZIP_MAGIC_BYTES: [u8; 2] = [0x1f, 0x8b];
+pub const XZ_MAGIC_BYTES: [u8; 6] = [0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x00];
+pub const BZIP2_MAGIC_BYTES: [u8; 3] = [0x42, 0x5a, 0x68];
 
 pub const PNG_MAGIC_BYTES: [u8; 8] = [0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a];
 pub const SVG_MAGIC_BYTES: [u8; 4] = [0x3c, 0x73, 0x76, 0x67];
@@ -16,5 +23,13 @@ pub const ZST_MAGIC_BYTES: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
 pub const CAP_SYS_ADMIN: i32 = 21;
 pub const CAP_MKNOD: i32 = 27;
 
+pub const SYSTEM_CONFIG_PATH: &str = "/etc/soar/config.toml";
+pub const SYSTEM_ROOT_PATH: &str = "/opt/soar";
+pub const SYSTEM_BIN_PATH: &str = "/usr/local/bin";
+/// Icons, MIME types, metainfo and shell integration of the system-wide
+/// installation, kept apart from the files of the system package manager.
+pub const SYSTEM_DATA_PATH: &str = "/usr/local/share";
+pub const SYSTEM_DESKTOP_PATH: &str = "/usr/share/applications";
+
 pub const METADATA_MIGRATIONS: Dir = include_dir!("$CARGO_MANIFEST_DIR/migrations/metadata");
 pub const CORE_MIGRATIONS: Dir = include_dir!("$CARGO_MANIFEST_DIR/migrations/core");
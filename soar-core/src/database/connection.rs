@@ -5,9 +5,14 @@ use std::{
 
 use rusqlite::Connection;
 
-use crate::error::SoarError;
+use crate::{config::is_system_mode, error::SoarError};
 
-use super::{models::RemotePackage, repository::PackageRepository, statements::DbStatements};
+use super::{
+    models::RemotePackage,
+    repository::PackageRepository,
+    search::{build_search_index, has_search_index},
+    statements::DbStatements,
+};
 
 type Result<T> = std::result::Result<T, SoarError>;
 
@@ -42,6 +47,22 @@ impl Database {
         Ok(Database { conn })
     }
 
+    /// Builds the search index for every attached database that doesn't have one yet.
+    pub fn ensure_search_index(&self) -> Result<()> {
+        let conn = self.conn.lock()?;
+        let schemas: Vec<String> = conn
+            .prepare("PRAGMA database_list")?
+            .query_map([], |row| row.get(1))?
+            .collect::<rusqlite::Result<_>>()?;
+
+        for schema in schemas.iter().filter(|schema| *schema != "temp") {
+            if !has_search_index(&conn, schema)? {
+                build_search_index(&conn, schema)?;
+            }
+        }
+        Ok(())
+    }
+
     pub fn from_remote_metadata(&self, metadata: &[RemotePackage], repo_name: &str) -> Result<()> {
         let mut guard = self.conn.lock().unwrap();
         let _: String = guard.query_row("PRAGMA journal_mode = WAL", [], |row| row.get(0))?;
@@ -53,6 +74,12 @@ impl Database {
             repo.import_packages(metadata)?;
         }
         tx.commit()?;
+
+        // readers without write access to the directory can't open WAL databases
+        if is_system_mode() {
+            let _: String =
+                guard.query_row("PRAGMA journal_mode = DELETE", [], |row| row.get(0))?;
+        }
         Ok(())
     }
 }
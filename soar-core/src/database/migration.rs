@@ -68,15 +68,13 @@ impl MigrationManager {
 
         migrations.sort_by_key(|m| m.version);
 
-        let mut expected_version = 1;
-        for migration in &migrations {
+        for (expected_version, migration) in (1..).zip(migrations.iter()) {
             if migration.version != expected_version {
                 return Err(SoarError::Custom(format!(
                     "Invalid migration sequence. Expected version {}, found {}",
                     expected_version, migration.version
                 )));
             }
-            expected_version += 1;
         }
 
         Ok(migrations)
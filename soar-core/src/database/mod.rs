@@ -3,4 +3,5 @@ pub mod migration;
 pub mod models;
 pub mod packages;
 pub mod repository;
+pub mod search;
 pub mod statements;
@@ -251,6 +251,138 @@ impl FromRow for InstalledPackage {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PackageFileKind {
+    File,
+    Directory,
+    Symlink,
+}
+
+impl PackageFileKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PackageFileKind::File => "file",
+            PackageFileKind::Directory => "dir",
+            PackageFileKind::Symlink => "symlink",
+        }
+    }
+}
+
+impl Display for PackageFileKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+/// A file, directory or symlink created by installing a package.
+#[derive(Debug, Clone)]
+pub struct PackageFile {
+    pub package_id: u64,
+    pub path: String,
+    pub kind: PackageFileKind,
+}
+
+impl FromRow for PackageFile {
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        let kind = match row.get::<_, String>("kind")?.as_str() {
+            "dir" => PackageFileKind::Directory,
+            "symlink" => PackageFileKind::Symlink,
+            _ => PackageFileKind::File,
+        };
+
+        Ok(PackageFile {
+            package_id: row.get("package_id")?,
+            path: row.get("path")?,
+            kind,
+        })
+    }
+}
+
+/// A minisign signature retained alongside the installed file it signs.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PackageSignature {
+    pub file: String,
+    pub signature: String,
+}
+
+/// Where an installed package came from and how it was verified.
+#[derive(Debug, Clone, Serialize)]
+pub struct PackageProvenance {
+    pub package_id: u64,
+    pub repo_name: String,
+    pub download_url: String,
+    pub ghcr_digest: Option<String>,
+    pub build_id: Option<String>,
+    pub build_action: Option<String>,
+    pub pubkey_fingerprint: Option<String>,
+    pub signature_verified: bool,
+    pub signatures: Vec<PackageSignature>,
+    pub recorded_date: String,
+    /// File the package checksum was validated against, if it's installed as
+    /// downloaded.
+    pub checksum_file: Option<String>,
+}
+
+impl FromRow for PackageProvenance {
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        let signatures = row
+            .get::<_, Option<String>>("signatures")?
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+
+        Ok(PackageProvenance {
+            package_id: row.get("package_id")?,
+            repo_name: row.get("repo_name")?,
+            download_url: row.get("download_url")?,
+            ghcr_digest: row.get("ghcr_digest")?,
+            build_id: row.get("build_id")?,
+            build_action: row.get("build_action")?,
+            pubkey_fingerprint: row.get("pubkey_fingerprint")?,
+            signature_verified: row.get("signature_verified")?,
+            signatures,
+            recorded_date: row.get("recorded_date")?,
+            checksum_file: row.get("checksum_file")?,
+        })
+    }
+}
+
+/// Restrictions applied when running binaries of an installed package.
+#[derive(Debug, Clone, Serialize)]
+pub struct SandboxProfile {
+    pub repo_name: String,
+    pub pkg_id: String,
+    pub pkg_name: String,
+    /// Paths the package can read and write.
+    pub allow: Vec<String>,
+    /// Paths the package can only read.
+    pub allow_ro: Vec<String>,
+    pub network: bool,
+    /// Use the portable home, config and share directories instead of the
+    /// user's home.
+    pub isolate_home: bool,
+}
+
+impl FromRow for SandboxProfile {
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        let parse_paths = |idx: &str| -> rusqlite::Result<Vec<String>> {
+            let value: Option<String> = row.get(idx)?;
+            Ok(value
+                .and_then(|s| serde_json::from_str(&s).ok())
+                .unwrap_or_default())
+        };
+
+        Ok(SandboxProfile {
+            repo_name: row.get("repo_name")?,
+            pkg_id: row.get("pkg_id")?,
+            pkg_name: row.get("pkg_name")?,
+            allow: parse_paths("allow")?,
+            allow_ro: parse_paths("allow_ro")?,
+            network: row.get("network")?,
+            isolate_home: row.get("isolate_home")?,
+        })
+    }
+}
+
 #[derive(Deserialize)]
 #[serde(untagged)]
 enum FlexiBool {
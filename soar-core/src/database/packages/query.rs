@@ -19,6 +19,8 @@ pub struct PackageQueryBuilder {
     shards: Option<Vec<String>>,
     page: u32,
     select_columns: Vec<String>,
+    search: Option<String>,
+    maintainer: Option<FilterCondition>,
 }
 
 impl PackageQueryBuilder {
@@ -31,6 +33,8 @@ impl PackageQueryBuilder {
             shards: None,
             page: 1,
             select_columns: Vec::new(),
+            search: None,
+            maintainer: None,
         }
     }
 
@@ -103,6 +107,36 @@ impl PackageQueryBuilder {
         self
     }
 
+    /// Filters on the elements of a JSON array column (e.g. `tags`, `licenses`).
+    pub fn json_array_where_and(mut self, field: &str, condition: FilterCondition) -> Self {
+        let where_clause = self.build_subquery_where_clause("value", condition);
+        let query = format!(
+            "EXISTS (SELECT 1 FROM json_each({}) WHERE {})",
+            field, where_clause
+        );
+
+        self.filters.push(QueryFilter {
+            field: query,
+            condition: FilterCondition::None,
+            logical_op: Some(LogicalOp::And),
+        });
+        self
+    }
+
+    /// Restricts results to packages with a maintainer whose name or contact
+    /// matches the condition.
+    pub fn maintainer(mut self, condition: FilterCondition) -> Self {
+        self.maintainer = Some(condition);
+        self
+    }
+
+    /// Restricts results to packages matching the FTS5 `MATCH` expression, ranked
+    /// by relevance weighted with the package popularity.
+    pub fn search(mut self, expression: String) -> Self {
+        self.search = Some(expression);
+        self
+    }
+
     pub fn database(mut self, db: Arc<Mutex<Connection>>) -> Self {
         self.db = db;
         self
@@ -260,9 +294,11 @@ impl PackageQueryBuilder {
                 } else {
                     self.select_columns.join(",")
                 };
+                let (search_score, search_join) = self.build_search_clause(shard, &mut params);
+                let maintainer_join = self.build_maintainer_clause(shard);
                 let select_clause = format!(
                     "SELECT
-                        {cols}, r.name AS repo_name,
+                        {cols}, r.name AS repo_name{search_score},
                         json_group_array(
                             json_object(
                                 'name', m.name,
@@ -272,6 +308,8 @@ impl PackageQueryBuilder {
                      FROM
                          {0}.packages p
                          JOIN {0}.repository r
+                         {search_join}
+                         {maintainer_join}
                          LEFT JOIN {0}.package_maintainers pm ON p.id = pm.package_id
                          LEFT JOIN {0}.maintainers m ON m.id = pm.maintainer_id
                     ",
@@ -289,8 +327,8 @@ impl PackageQueryBuilder {
         let combined_query = shard_queries.join("\nUNION ALL\n");
         let mut final_query = format!("WITH results AS ({}) SELECT * FROM results", combined_query);
 
-        if !self.sort_fields.is_empty() {
-            let sort_clauses: Vec<String> = self
+        if !self.sort_fields.is_empty() || self.search.is_some() {
+            let mut sort_clauses: Vec<String> = self
                 .sort_fields
                 .iter()
                 .map(|(field, direction)| {
@@ -304,6 +342,9 @@ impl PackageQueryBuilder {
                     )
                 })
                 .collect();
+            if self.search.is_some() {
+                sort_clauses.push("search_score ASC".to_string());
+            }
             final_query.push_str(" ORDER BY ");
             final_query.push_str(&sort_clauses.join(", "));
         }
@@ -328,8 +369,10 @@ impl PackageQueryBuilder {
         let shard_queries: Vec<String> = shards
             .iter()
             .map(|shard| {
+                let (_, search_join) = self.build_search_clause(shard, &mut params);
+                let maintainer_join = self.build_maintainer_clause(shard);
                 let select_clause = format!(
-                    "SELECT COUNT(1) as cnt, r.name as repo_name FROM {0}.packages p JOIN {0}.repository r",
+                    "SELECT COUNT(1) as cnt, r.name as repo_name FROM {0}.packages p JOIN {0}.repository r {search_join} {maintainer_join}",
                     shard
                 );
 
@@ -346,6 +389,52 @@ impl PackageQueryBuilder {
         (query, params)
     }
 
+    /// Returns the score column and join clause restricting a shard to the
+    /// full-text matches, if a search is set.
+    fn build_search_clause(
+        &self,
+        shard: &str,
+        params: &mut Vec<Box<dyn rusqlite::ToSql>>,
+    ) -> (String, String) {
+        let Some(ref expression) = self.search else {
+            return (String::new(), String::new());
+        };
+        params.push(Box::new(expression.clone()));
+
+        // fts rank is negative bm25, so boosting popular packages means scaling it up
+        let score = ", fts.relevance * (1.0 + CASE
+                WHEN p.download_count > 0 THEN p.download_count / (p.download_count + 1000.0)
+                WHEN p.rank > 0 THEN 1.0 / (p.rank + 1)
+                ELSE 0
+            END) AS search_score"
+            .to_string();
+        let join = format!(
+            "JOIN (
+                SELECT rowid, rank AS relevance FROM {shard}.packages_fts
+                WHERE packages_fts MATCH ?
+            ) fts ON fts.rowid = p.id"
+        );
+        (score, join)
+    }
+
+    /// Returns the join clause restricting a shard to packages with a matching
+    /// maintainer, if a maintainer filter is set.
+    fn build_maintainer_clause(&self, shard: &str) -> String {
+        let Some(ref condition) = self.maintainer else {
+            return String::new();
+        };
+        let name_clause = self.build_subquery_where_clause("m.name", condition.clone());
+        let contact_clause = self.build_subquery_where_clause("m.contact", condition.clone());
+
+        format!(
+            "JOIN (
+                SELECT DISTINCT pm.package_id FROM {shard}.package_maintainers pm
+                JOIN {shard}.maintainers m ON m.id = pm.maintainer_id
+                WHERE {name_clause} OR {contact_clause}
+            ) mf ON mf.package_id = p.id"
+        )
+    }
+
     pub fn load_installed(&self) -> SoarResult<PaginatedResponse<InstalledPackage>> {
         let conn = self.db.lock().map_err(|_| SoarError::PoisonError)?;
         let (query, params) = self.build_installed_query()?;
@@ -440,7 +529,7 @@ impl PackageQueryBuilder {
             return String::new();
         }
 
-        let conditions: Vec<String> = self
+        let conditions: Vec<(usize, Option<LogicalOp>, String)> = self
             .filters
             .iter()
             .enumerate()
@@ -506,52 +595,62 @@ impl PackageQueryBuilder {
                     FilterCondition::None => filter.field.to_string(),
                 };
 
-                if idx > 0 {
-                    match filter.logical_op {
-                        Some(LogicalOp::And) => format!("AND {}", condition),
-                        Some(LogicalOp::Or) => format!("OR {}", condition),
-                        None => condition,
-                    }
-                } else {
-                    condition
-                }
+                (idx, filter.logical_op.clone(), condition)
             })
             .collect();
-        format!("WHERE {}", conditions.join(" "))
+
+        // filters are combined left to right in the order they were added, so
+        // `a OR b AND c` means `(a OR b) AND c`
+        let clause =
+            conditions
+                .into_iter()
+                .fold(String::new(), |clause, (idx, logical_op, condition)| {
+                    if idx == 0 {
+                        return condition;
+                    }
+                    match logical_op {
+                        Some(LogicalOp::And) => format!("({}) AND {}", clause, condition),
+                        Some(LogicalOp::Or) => format!("({}) OR {}", clause, condition),
+                        None => format!("{} {}", clause, condition),
+                    }
+                });
+        format!("WHERE {}", clause)
     }
 
     fn build_subquery_where_clause(&self, value: &str, condition: FilterCondition) -> String {
+        // values are inlined into the subquery, so quotes need to be escaped
+        let escape = |val: &str| val.replace('\'', "''");
         match condition {
             FilterCondition::Eq(val) => {
-                format!("{} = '{}'", value, val)
+                format!("{} = '{}'", value, escape(&val))
             }
             FilterCondition::Ne(val) => {
-                format!("{} != '{}'", value, val)
+                format!("{} != '{}'", value, escape(&val))
             }
             FilterCondition::Gt(val) => {
-                format!("{} > '{}'", value, val)
+                format!("{} > '{}'", value, escape(&val))
             }
             FilterCondition::Gte(val) => {
-                format!("{} >= '{}'", value, val)
+                format!("{} >= '{}'", value, escape(&val))
             }
             FilterCondition::Lt(val) => {
-                format!("{} < '{}'", value, val)
+                format!("{} < '{}'", value, escape(&val))
             }
             FilterCondition::Lte(val) => {
-                format!("{} <= '{}'", value, val)
+                format!("{} <= '{}'", value, escape(&val))
             }
             FilterCondition::Like(val) => {
-                format!("{} LIKE '%{}%'", value, val)
+                format!("{} LIKE '%{}%'", value, escape(&val))
             }
             FilterCondition::ILike(val) => {
-                format!("LOWER({}) LIKE LOWER('%{}%')", value, val)
+                format!("LOWER({}) LIKE LOWER('%{}%')", value, escape(&val))
             }
             FilterCondition::In(vals) => {
                 format!(
                     "{} IN ({})",
                     value,
                     vals.iter()
-                        .map(|v| format!("'{}'", v))
+                        .map(|v| format!("'{}'", escape(v)))
                         .collect::<Vec<String>>()
                         .join(",")
                 )
@@ -561,13 +660,18 @@ impl PackageQueryBuilder {
                     "{} NOT IN ({})",
                     value,
                     vals.iter()
-                        .map(|v| format!("'{}'", v))
+                        .map(|v| format!("'{}'", escape(v)))
                         .collect::<Vec<String>>()
                         .join(",")
                 )
             }
             FilterCondition::Between(start, end) => {
-                format!("{} BETWEEN '{}' AND '{}'", value, start, end)
+                format!(
+                    "{} BETWEEN '{}' AND '{}'",
+                    value,
+                    escape(&start),
+                    escape(&end)
+                )
             }
             FilterCondition::IsNull => {
                 format!("{} IS NULL", value)
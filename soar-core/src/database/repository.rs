@@ -1,7 +1,10 @@
 use regex::Regex;
 use rusqlite::{params, Result, Transaction};
 
-use super::{models::RemotePackage, packages::PackageProvide, statements::DbStatements};
+use super::{
+    models::RemotePackage, packages::PackageProvide, search::build_search_index,
+    statements::DbStatements,
+};
 
 pub struct PackageRepository<'a> {
     tx: &'a Transaction<'a>,
@@ -28,6 +31,8 @@ impl<'a> PackageRepository<'a> {
         for package in metadata {
             self.insert_package(package)?;
         }
+
+        build_search_index(self.tx, "main")?;
         Ok(())
     }
 
@@ -0,0 +1,267 @@
+use rusqlite::{Connection, Result};
+
+/// Columns indexed for full-text search, in the order used for `bm25` weights.
+const SEARCH_COLUMNS: &[(&str, f64)] = &[
+    ("pkg_name", 10.0),
+    ("pkg_id", 5.0),
+    ("pkg", 5.0),
+    ("app_id", 3.0),
+    ("description", 1.0),
+    ("tags", 2.0),
+    ("categories", 2.0),
+];
+
+/// Creates (if missing) and repopulates the FTS5 index over the `packages` table
+/// of the given schema.
+///
+/// This is used both for metadata imported from JSON and for prebuilt SQLite
+/// metadata downloaded from remote, which doesn't ship with the index.
+pub fn build_search_index(conn: &Connection, schema: &str) -> Result<()> {
+    let columns = SEARCH_COLUMNS
+        .iter()
+        .map(|(col, _)| *col)
+        .collect::<Vec<_>>()
+        .join(", ");
+    let weights = SEARCH_COLUMNS
+        .iter()
+        .map(|(_, weight)| format!("{:.1}", weight))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    conn.execute_batch(&format!(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS {schema}.packages_fts USING fts5(
+            {columns},
+            prefix = '2 3',
+            tokenize = 'unicode61 remove_diacritics 2'
+        );
+        CREATE VIRTUAL TABLE IF NOT EXISTS {schema}.packages_fts_vocab
+            USING fts5vocab(packages_fts, 'row');
+        DELETE FROM {schema}.packages_fts;
+        INSERT INTO {schema}.packages_fts (packages_fts, rank)
+            VALUES ('rank', 'bm25({weights})');
+        INSERT INTO {schema}.packages_fts (rowid, {columns})
+        SELECT
+            id, pkg_name, pkg_id, pkg, app_id, description,
+            (SELECT group_concat(value, ' ') FROM json_each(tags)),
+            (SELECT group_concat(value, ' ') FROM json_each(categories))
+        FROM {schema}.packages;"
+    ))
+}
+
+/// Checks whether the given schema already has a search index.
+pub fn has_search_index(conn: &Connection, schema: &str) -> Result<bool> {
+    conn.query_row(
+        &format!(
+            "SELECT EXISTS (
+                SELECT 1 FROM {schema}.sqlite_master
+                WHERE type = 'table' AND name = 'packages_fts'
+            )"
+        ),
+        [],
+        |row| row.get(0),
+    )
+}
+
+/// Splits a free-form search query into lowercase terms suitable for FTS.
+pub fn tokenize(query: &str) -> Vec<String> {
+    query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|term| !term.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Builds an FTS5 `MATCH` expression requiring all terms, matching each term as prefix.
+pub fn match_expression(terms: &[String]) -> String {
+    terms
+        .iter()
+        .map(|term| format!("\"{}\"*", term.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" AND ")
+}
+
+/// Replaces each term with the closest indexed term across all attached databases,
+/// allowing a small number of typos proportional to the term length.
+///
+/// Returns `None` if no term could be corrected.
+pub fn correct_terms(conn: &Connection, terms: &[String]) -> Result<Option<Vec<String>>> {
+    let shards: Vec<String> = conn
+        .prepare("PRAGMA database_list")?
+        .query_map([], |row| row.get(1))?
+        .collect::<Result<_>>()?;
+
+    let mut corrected = Vec::with_capacity(terms.len());
+    let mut changed = false;
+
+    for term in terms {
+        let max_distance = match term.chars().count() {
+            0..=3 => 0,
+            4..=7 => 1,
+            _ => 2,
+        };
+
+        let mut best: Option<(usize, i64, String)> = None;
+        if max_distance > 0 {
+            for shard in &shards {
+                if !has_search_index(conn, shard)? {
+                    continue;
+                }
+                let mut stmt = conn.prepare(&format!(
+                    "SELECT term, doc FROM {shard}.packages_fts_vocab
+                    WHERE length(term) BETWEEN ?1 AND ?2"
+                ))?;
+                let len = term.chars().count() as i64;
+                let rows = stmt.query_map(
+                    [len - max_distance as i64, len + max_distance as i64],
+                    |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)),
+                )?;
+
+                for row in rows {
+                    let (candidate, docs) = row?;
+                    let distance = edit_distance(term, &candidate);
+                    if distance > max_distance {
+                        continue;
+                    }
+                    let is_better = best.as_ref().is_none_or(|(best_distance, best_docs, _)| {
+                        distance < *best_distance
+                            || (distance == *best_distance && docs > *best_docs)
+                    });
+                    if is_better {
+                        best = Some((distance, docs, candidate));
+                    }
+                }
+            }
+        }
+
+        match best {
+            Some((distance, _, candidate)) if distance > 0 => {
+                changed = true;
+                corrected.push(candidate);
+            }
+            _ => corrected.push(term.clone()),
+        }
+    }
+
+    Ok(changed.then_some(corrected))
+}
+
+/// Optimal string alignment distance, i.e. Levenshtein distance that also counts
+/// adjacent transpositions as a single edit.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut dist = vec![vec![0; b.len() + 1]; a.len() + 1];
+
+    for (i, row) in dist.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in dist[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            dist[i][j] = (dist[i - 1][j] + 1)
+                .min(dist[i][j - 1] + 1)
+                .min(dist[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                dist[i][j] = dist[i][j].min(dist[i - 2][j - 2] + 1);
+            }
+        }
+    }
+
+    dist[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terms(terms: &[&str]) -> Vec<String> {
+        terms.iter().map(|term| term.to_string()).collect()
+    }
+
+    fn indexed_db(packages: &[(&str, &str)]) -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE packages (
+                id INTEGER PRIMARY KEY,
+                pkg_name TEXT,
+                pkg_id TEXT,
+                pkg TEXT,
+                app_id TEXT,
+                description TEXT,
+                tags JSONB,
+                categories JSONB
+            );",
+        )
+        .unwrap();
+        for (pkg_name, description) in packages {
+            conn.execute(
+                "INSERT INTO packages (pkg_name, pkg_id, pkg, description, tags, categories)
+                VALUES (?1, ?1, ?1, ?2, '[]', '[]')",
+                [pkg_name, description],
+            )
+            .unwrap();
+        }
+        build_search_index(&conn, "main").unwrap();
+        conn
+    }
+
+    #[test]
+    fn counts_edits() {
+        assert_eq!(edit_distance("firefox", "firefox"), 0);
+        assert_eq!(edit_distance("firefox", "frefox"), 1);
+        assert_eq!(edit_distance("frefox", "firefox"), 1);
+        assert_eq!(edit_distance("firefox", "firefoxx"), 1);
+        assert_eq!(edit_distance("firefox", "firefix"), 1);
+        assert_eq!(edit_distance("firefox", "fierfox"), 1);
+        assert_eq!(edit_distance("firefox", "ifrefxo"), 2);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("ab", "ba"), 1);
+    }
+
+    #[test]
+    fn quotes_match_terms() {
+        assert_eq!(
+            match_expression(&terms(&["fire", "fox"])),
+            "\"fire\"* AND \"fox\"*"
+        );
+        assert_eq!(match_expression(&terms(&["OR"])), "\"OR\"*");
+        assert_eq!(match_expression(&terms(&["a\"b"])), "\"a\"\"b\"*");
+
+        let conn = indexed_db(&[("firefox", "web browser")]);
+        for term in ["AND", "NOT", "NEAR", "\"", "*", "col:fire", "^web", "(x"] {
+            let expression = match_expression(&terms(&[term]));
+            conn.query_row(
+                "SELECT count(*) FROM packages_fts WHERE packages_fts MATCH ?1",
+                [&expression],
+                |row| row.get::<_, i64>(0),
+            )
+            .unwrap_or_else(|err| panic!("{} failed to match: {}", expression, err));
+        }
+    }
+
+    #[test]
+    fn corrects_misspelled_terms() {
+        let conn = indexed_db(&[
+            ("firefox", "web browser"),
+            ("zathura", "document viewer"),
+            ("evince", "document viewer"),
+        ]);
+
+        assert_eq!(
+            correct_terms(&conn, &terms(&["frefox"])).unwrap(),
+            Some(terms(&["firefox"]))
+        );
+        assert_eq!(
+            correct_terms(&conn, &terms(&["documnet", "viewer"])).unwrap(),
+            Some(terms(&["document", "viewer"]))
+        );
+        // short terms aren't corrected
+        assert_eq!(correct_terms(&conn, &terms(&["wbe"])).unwrap(), None);
+        assert_eq!(correct_terms(&conn, &terms(&["browser"])).unwrap(), None);
+        assert_eq!(correct_terms(&conn, &terms(&["xyzzyx"])).unwrap(), None);
+    }
+}
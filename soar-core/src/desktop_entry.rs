@@ -0,0 +1,499 @@
+use std::fmt::{self, Display};
+
+use crate::{error::SoarError, SoarResult};
+
+pub const MAIN_GROUP: &str = "Desktop Entry";
+const ACTION_GROUP_PREFIX: &str = "Desktop Action ";
+
+#[derive(Debug, Clone)]
+enum Line {
+    /// Comments and blank lines, kept as they are.
+    Raw(String),
+    Entry {
+        key: String,
+        locale: Option<String>,
+        value: String,
+    },
+}
+
+#[derive(Debug, Clone)]
+struct Group {
+    name: String,
+    lines: Vec<Line>,
+}
+
+/// A parsed desktop entry file. Values are kept in their escaped form, and
+/// comments, localized keys and the order of groups and keys are preserved
+/// when it's written back.
+#[derive(Debug, Clone)]
+pub struct DesktopEntry {
+    header: Vec<String>,
+    groups: Vec<Group>,
+}
+
+fn invalid(message: String) -> SoarError {
+    SoarError::InvalidDesktopEntry(message)
+}
+
+fn is_valid_key(key: &str) -> bool {
+    !key.is_empty() && key.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-')
+}
+
+impl DesktopEntry {
+    pub fn parse(content: &str) -> SoarResult<Self> {
+        let mut header = Vec::new();
+        let mut groups: Vec<Group> = Vec::new();
+
+        for (idx, line) in content.lines().enumerate() {
+            let trimmed = line.trim();
+
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                match groups.last_mut() {
+                    Some(group) => group.lines.push(Line::Raw(line.to_string())),
+                    None => header.push(line.to_string()),
+                }
+                continue;
+            }
+
+            if let Some(name) = trimmed
+                .strip_prefix('[')
+                .and_then(|name| name.strip_suffix(']'))
+            {
+                if groups.iter().any(|group| group.name == name) {
+                    return Err(invalid(format!("duplicate group [{}]", name)));
+                }
+                groups.push(Group {
+                    name: name.to_string(),
+                    lines: Vec::new(),
+                });
+                continue;
+            }
+
+            let Some(group) = groups.last_mut() else {
+                return Err(invalid(format!("line {} is outside of a group", idx + 1)));
+            };
+            let Some((key, value)) = trimmed.split_once('=') else {
+                return Err(invalid(format!("line {} is not a key-value pair", idx + 1)));
+            };
+
+            let key = key.trim_end();
+            let (key, locale) = match key.split_once('[') {
+                Some((key, locale)) => match locale.strip_suffix(']') {
+                    Some(locale) if !locale.is_empty() => (key, Some(locale.to_string())),
+                    _ => return Err(invalid(format!("invalid locale in key {}", key))),
+                },
+                None => (key, None),
+            };
+            if !is_valid_key(key) {
+                return Err(invalid(format!("invalid key {}", key)));
+            }
+
+            group.lines.push(Line::Entry {
+                key: key.to_string(),
+                locale,
+                value: value.trim_start().to_string(),
+            });
+        }
+
+        Ok(Self { header, groups })
+    }
+
+    fn group(&self, name: &str) -> Option<&Group> {
+        self.groups.iter().find(|group| group.name == name)
+    }
+
+    /// Returns the names of the groups, in order.
+    pub fn groups(&self) -> impl Iterator<Item = &str> {
+        self.groups.iter().map(|group| group.name.as_str())
+    }
+
+    /// Returns the unlocalized value of the key in the group.
+    pub fn get(&self, group: &str, key: &str) -> Option<&str> {
+        self.group(group)?.lines.iter().find_map(|line| match line {
+            Line::Entry {
+                key: k,
+                locale: None,
+                value,
+            } if k == key => Some(value.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Returns the value of the key for the locale (`lang_COUNTRY@MODIFIER`),
+    /// falling back the same way as the specification, down to the
+    /// unlocalized value.
+    pub fn get_localized(&self, group: &str, key: &str, locale: &str) -> Option<&str> {
+        let locale = locale.split('.').next().unwrap_or(locale);
+        let (locale, modifier) = match locale.split_once('@') {
+            Some((locale, modifier)) => (locale, Some(modifier)),
+            None => (locale, None),
+        };
+        let (lang, country) = match locale.split_once('_') {
+            Some((lang, country)) => (lang, Some(country)),
+            None => (locale, None),
+        };
+
+        let mut candidates = Vec::new();
+        if let (Some(country), Some(modifier)) = (country, modifier) {
+            candidates.push(format!("{lang}_{country}@{modifier}"));
+        }
+        if let Some(country) = country {
+            candidates.push(format!("{lang}_{country}"));
+        }
+        if let Some(modifier) = modifier {
+            candidates.push(format!("{lang}@{modifier}"));
+        }
+        candidates.push(lang.to_string());
+
+        let group = self.group(group)?;
+        candidates
+            .iter()
+            .find_map(|candidate| {
+                group.lines.iter().find_map(|line| match line {
+                    Line::Entry {
+                        key: k,
+                        locale: Some(l),
+                        value,
+                    } if k == key && l == candidate => Some(value.as_str()),
+                    _ => None,
+                })
+            })
+            .or_else(|| self.get(group.name.as_str(), key))
+    }
+
+    /// Returns the values of a list key, e.g. `MimeType` or `Actions`.
+    pub fn get_list(&self, group: &str, key: &str) -> Vec<&str> {
+        self.get(group, key)
+            .map(|value| {
+                value
+                    .split(';')
+                    .map(str::trim)
+                    .filter(|item| !item.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Sets the unlocalized value of the key, adding it to the group if it
+    /// doesn't exist.
+    pub fn set(&mut self, group: &str, key: &str, value: &str) {
+        let Some(group) = self.groups.iter_mut().find(|g| g.name == group) else {
+            return;
+        };
+
+        for line in group.lines.iter_mut() {
+            if let Line::Entry {
+                key: k,
+                locale: None,
+                value: v,
+            } = line
+            {
+                if k == key {
+                    *v = value.to_string();
+                    return;
+                }
+            }
+        }
+
+        let position = group
+            .lines
+            .iter()
+            .rposition(|line| matches!(line, Line::Entry { .. }))
+            .map_or(0, |idx| idx + 1);
+        group.lines.insert(
+            position,
+            Line::Entry {
+                key: key.to_string(),
+                locale: None,
+                value: value.to_string(),
+            },
+        );
+    }
+
+    /// Sets the value of the key and of all its localized variants.
+    pub fn set_all_locales(&mut self, group: &str, key: &str, value: &str) {
+        if let Some(group) = self.groups.iter_mut().find(|g| g.name == group) {
+            for line in group.lines.iter_mut() {
+                if let Line::Entry {
+                    key: k, value: v, ..
+                } = line
+                {
+                    if k == key {
+                        *v = value.to_string();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the names of the `[Desktop Action ...]` groups.
+    pub fn action_groups(&self) -> Vec<String> {
+        self.groups()
+            .filter(|name| name.starts_with(ACTION_GROUP_PREFIX))
+            .map(String::from)
+            .collect()
+    }
+
+    /// Checks the keys required by the specification.
+    pub fn validate(&self) -> SoarResult<()> {
+        match self.groups.first() {
+            Some(group) if group.name == MAIN_GROUP => {}
+            _ => return Err(invalid(format!("first group must be [{}]", MAIN_GROUP))),
+        }
+
+        let entry_type = self
+            .get(MAIN_GROUP, "Type")
+            .ok_or_else(|| invalid("missing required key Type".into()))?;
+        if self.get(MAIN_GROUP, "Name").is_none() {
+            return Err(invalid("missing required key Name".into()));
+        }
+
+        let dbus_activatable = self.get(MAIN_GROUP, "DBusActivatable") == Some("true");
+        match entry_type {
+            "Application" if !dbus_activatable && self.get(MAIN_GROUP, "Exec").is_none() => {
+                return Err(invalid("missing required key Exec".into()));
+            }
+            "Link" if self.get(MAIN_GROUP, "URL").is_none() => {
+                return Err(invalid("missing required key URL".into()));
+            }
+            _ => {}
+        }
+
+        for action in self.get_list(MAIN_GROUP, "Actions") {
+            let group = format!("{ACTION_GROUP_PREFIX}{action}");
+            if self.group(&group).is_none() {
+                return Err(invalid(format!("missing group [{}]", group)));
+            }
+            if self.get(&group, "Name").is_none() {
+                return Err(invalid(format!("missing required key Name in [{}]", group)));
+            }
+            if !dbus_activatable && self.get(&group, "Exec").is_none() {
+                return Err(invalid(format!("missing required key Exec in [{}]", group)));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for DesktopEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in &self.header {
+            writeln!(f, "{}", line)?;
+        }
+        for group in &self.groups {
+            writeln!(f, "[{}]", group.name)?;
+            for line in &group.lines {
+                match line {
+                    Line::Raw(raw) => writeln!(f, "{}", raw)?,
+                    Line::Entry {
+                        key,
+                        locale: Some(locale),
+                        value,
+                    } => writeln!(f, "{}[{}]={}", key, locale, value)?,
+                    Line::Entry {
+                        key,
+                        locale: None,
+                        value,
+                    } => writeln!(f, "{}={}", key, value)?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Splits the first argument of an Exec value. Returns the unquoted argument
+/// and the number of bytes it spans.
+fn split_exec_arg(exec: &str) -> (String, usize) {
+    let leading = exec.len() - exec.trim_start().len();
+    let rest = &exec[leading..];
+
+    if let Some(quoted) = rest.strip_prefix('"') {
+        let mut arg = String::new();
+        let mut chars = quoted.char_indices();
+        while let Some((idx, c)) = chars.next() {
+            match c {
+                '\\' => {
+                    // `\\` is the string level escape of a quoting backslash
+                    if let Some((_, next)) = chars.next() {
+                        if next == '\\' {
+                            if let Some((_, escaped)) = chars.next() {
+                                arg.push(escaped);
+                            }
+                        } else {
+                            arg.push(next);
+                        }
+                    }
+                }
+                '"' => return (arg, leading + idx + 2),
+                _ => arg.push(c),
+            }
+        }
+        (arg, exec.len())
+    } else {
+        let end = rest.find([' ', '\t']).unwrap_or(rest.len());
+        (rest[..end].to_string(), leading + end)
+    }
+}
+
+/// Quotes the argument if needed, escaping it for a desktop entry value.
+fn quote_exec_arg(arg: &str) -> String {
+    const RESERVED: &[char] = &[
+        ' ', '\t', '\n', '"', '\'', '\\', '>', '<', '~', '|', '&', ';', '$', '*', '?', '#', '(',
+        ')', '`',
+    ];
+    if !arg.contains(RESERVED) {
+        return arg.to_string();
+    }
+
+    let mut quoted = String::from("\"");
+    for c in arg.chars() {
+        match c {
+            '"' | '`' | '$' => {
+                quoted.push_str("\\\\");
+                quoted.push(c);
+            }
+            '\\' => quoted.push_str("\\\\\\\\"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn is_env_assignment(arg: &str) -> bool {
+    arg.split_once('=').is_some_and(|(name, _)| {
+        !name.is_empty()
+            && !name.starts_with(|c: char| c.is_ascii_digit())
+            && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_')
+    })
+}
+
+/// Replaces the program of an Exec value, keeping its arguments and field
+/// codes. Programs run through `env` keep their environment assignments.
+pub fn rewrite_exec(exec: &str, program: &str) -> String {
+    let mut offset = 0;
+
+    let (first, len) = split_exec_arg(exec);
+    if first == "env" || first.ends_with("/env") {
+        offset = len;
+        loop {
+            let (arg, len) = split_exec_arg(&exec[offset..]);
+            if !is_env_assignment(&arg) {
+                break;
+            }
+            offset += len;
+        }
+    }
+
+    let (_, len) = split_exec_arg(&exec[offset..]);
+    let leading = exec[offset..].len() - exec[offset..].trim_start().len();
+
+    format!(
+        "{}{}{}",
+        &exec[..offset + leading],
+        quote_exec_arg(program),
+        &exec[offset + len..]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{quote_exec_arg, rewrite_exec, split_exec_arg, DesktopEntry, MAIN_GROUP};
+
+    #[test]
+    fn split_plain_arg() {
+        assert_eq!(split_exec_arg("app %U"), ("app".to_string(), 3));
+        assert_eq!(split_exec_arg("  app"), ("app".to_string(), 5));
+    }
+
+    #[test]
+    fn split_quoted_arg() {
+        assert_eq!(
+            split_exec_arg(r#""/opt/my app/bin" %F"#),
+            ("/opt/my app/bin".to_string(), 17)
+        );
+        // `\\"` unescapes to a quote, `\\\\` to a backslash
+        assert_eq!(
+            split_exec_arg(r#""a\\"b\\\\c" x"#),
+            (r#"a"b\c"#.to_string(), 12)
+        );
+    }
+
+    #[test]
+    fn quote_arg() {
+        assert_eq!(quote_exec_arg("/usr/bin/app"), "/usr/bin/app");
+        assert_eq!(quote_exec_arg("/my apps/app"), r#""/my apps/app""#);
+        assert_eq!(quote_exec_arg(r#"a"b"#), r#""a\\"b""#);
+        assert_eq!(quote_exec_arg(r"a\b"), r#""a\\\\b""#);
+    }
+
+    #[test]
+    fn quoted_arg_round_trips() {
+        for arg in [r#"/a b/"c"/$d"#, r"/a\b/c d", "/plain"] {
+            assert_eq!(split_exec_arg(&quote_exec_arg(arg)).0, arg);
+        }
+    }
+
+    #[test]
+    fn rewrite_program() {
+        assert_eq!(rewrite_exec("app %U", "/bin/app"), "/bin/app %U");
+        assert_eq!(
+            rewrite_exec(r#""/opt/my app/app" --new-window %u"#, "/bin/app"),
+            "/bin/app --new-window %u"
+        );
+        assert_eq!(rewrite_exec("app %U", "/my bin/app"), r#""/my bin/app" %U"#);
+    }
+
+    #[test]
+    fn rewrite_program_run_through_env() {
+        assert_eq!(
+            rewrite_exec("env VAR=x prog %U", "/bin/app"),
+            "env VAR=x /bin/app %U"
+        );
+        assert_eq!(
+            rewrite_exec("/usr/bin/env A=1 B=2 prog", "/bin/app"),
+            "/usr/bin/env A=1 B=2 /bin/app"
+        );
+    }
+
+    #[test]
+    fn localized_keys() {
+        let content = "[Desktop Entry]\n\
+            Type=Application\n\
+            Name=Files\n\
+            Name[de]=Dateien\n\
+            Name[sr@latin]=Datoteke\n\
+            Icon=files\n\
+            Icon[de]=dateien\n\
+            Exec=files %U\n";
+        let mut entry = DesktopEntry::parse(content).unwrap();
+        entry.validate().unwrap();
+
+        assert_eq!(entry.get(MAIN_GROUP, "Name"), Some("Files"));
+        assert_eq!(
+            entry.get_localized(MAIN_GROUP, "Name", "de_DE.UTF-8"),
+            Some("Dateien")
+        );
+        assert_eq!(
+            entry.get_localized(MAIN_GROUP, "Name", "sr_RS@latin"),
+            Some("Datoteke")
+        );
+        assert_eq!(entry.get_localized(MAIN_GROUP, "Name", "fr"), Some("Files"));
+
+        entry.set_all_locales(MAIN_GROUP, "Icon", "files-soar");
+        let written = entry.to_string();
+        assert!(written.contains("Icon=files-soar\n"));
+        assert!(written.contains("Icon[de]=files-soar\n"));
+        assert!(written.contains("Name[de]=Dateien\n"));
+    }
+
+    #[test]
+    fn invalid_entries() {
+        assert!(DesktopEntry::parse("Name=app\n").is_err());
+        assert!(DesktopEntry::parse("[Desktop Entry]\nName[]=app\n").is_err());
+        let entry = DesktopEntry::parse("[Desktop Entry]\nType=Application\nName=app\n").unwrap();
+        assert!(entry.validate().is_err());
+    }
+}
@@ -33,6 +33,12 @@ pub enum ConfigError {
     #[error("Profile '{0}' does not exist")]
     MissingProfile(String),
 
+    #[error("Profile '{0}' already exists")]
+    ProfileAlreadyExists(String),
+
+    #[error("Profile '{0}' is the default profile and can't be removed")]
+    DefaultProfileRemoval(String),
+
     #[error("{0}")]
     Custom(String),
 }
@@ -99,12 +105,41 @@ pub enum SoarError {
     #[error("Invalid checksum detected")]
     InvalidChecksum,
 
+    #[error("{package} - Invalid checksum for {path}: expected {expected}, got {actual}")]
+    ChecksumMismatch {
+        package: String,
+        path: String,
+        expected: String,
+        actual: String,
+    },
+
+    #[error("{0} - Blake3 checksum not found and checksums are required")]
+    MissingChecksum(String),
+
     #[error("Configuration file already exists")]
     ConfigAlreadyExists,
 
     #[error("Invalid package query: {0}")]
     InvalidPackageQuery(String),
 
+    #[error("Invalid desktop entry: {0}")]
+    InvalidDesktopEntry(String),
+
+    #[error("Sandbox is unavailable: {0}")]
+    SandboxUnavailable(String),
+
+    #[error("Modifying the system-wide installation requires root privileges")]
+    SystemModeRequiresRoot,
+
+    #[error("Another soar process is running (pid {0}), use --wait to wait for it")]
+    StoreLocked(String),
+
+    #[error("Delta update failed: {0}")]
+    DeltaUpdate(String),
+
+    #[error("{path} is already provided by {owner}")]
+    BinaryConflict { path: String, owner: String },
+
     #[error("{0}")]
     Custom(String),
 
@@ -3,11 +3,15 @@ use error::SoarError;
 pub mod config;
 pub mod constants;
 pub mod database;
+pub mod desktop_entry;
 pub mod error;
+pub mod lock;
 pub mod metadata;
 pub mod package;
 pub mod repositories;
+pub mod sandbox;
 pub mod toml;
 pub mod utils;
+pub mod xdg;
 
 pub type SoarResult<T> = std::result::Result<T, SoarError>;
@@ -0,0 +1,99 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::Path,
+    process,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use nix::{
+    errno::Errno,
+    fcntl::{Flock, FlockArg},
+};
+use tracing::info;
+
+use crate::{
+    error::{ErrorContext, SoarError},
+    SoarResult,
+};
+
+static HELD: AtomicBool = AtomicBool::new(false);
+
+/// Exclusive advisory lock on the database directory, held by commands that
+/// modify the store. It's released when dropped.
+pub struct StoreLock {
+    _lock: Flock<File>,
+}
+
+impl StoreLock {
+    /// Acquires the lock. If another soar process holds it, waits for it when
+    /// `wait` is set, or fails with the pid of the process otherwise.
+    pub fn acquire<P: AsRef<Path>>(db_dir: P, wait: bool) -> SoarResult<Self> {
+        match Self::lock(db_dir.as_ref(), wait)? {
+            Ok(lock) => Ok(lock),
+            Err(pid) => Err(SoarError::StoreLocked(pid)),
+        }
+    }
+
+    /// Acquires the lock without waiting. Returns `None` if another soar
+    /// process holds it.
+    pub fn try_acquire<P: AsRef<Path>>(db_dir: P) -> SoarResult<Option<Self>> {
+        Ok(Self::lock(db_dir.as_ref(), false)?.ok())
+    }
+
+    /// Whether this process holds the lock.
+    pub fn is_held() -> bool {
+        HELD.load(Ordering::SeqCst)
+    }
+
+    /// Locks the file, returning the pid of the process holding the lock if it
+    /// can't be acquired without waiting.
+    fn lock(db_dir: &Path, wait: bool) -> SoarResult<Result<Self, String>> {
+        let lock_path = db_dir.join("soar.lock");
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .with_context(|| format!("opening lock file {}", lock_path.display()))?;
+
+        let lock = match Flock::lock(file, FlockArg::LockExclusiveNonblock) {
+            Ok(lock) => lock,
+            Err((file, Errno::EWOULDBLOCK)) => {
+                let pid = fs::read_to_string(&lock_path)
+                    .ok()
+                    .map(|pid| pid.trim().to_string())
+                    .filter(|pid| !pid.is_empty())
+                    .unwrap_or_else(|| "unknown".to_string());
+
+                if !wait {
+                    return Ok(Err(pid));
+                }
+
+                info!(
+                    "Waiting for another soar process (pid {}) to finish...",
+                    pid
+                );
+                Flock::lock(file, FlockArg::LockExclusive).map_err(|(_, err)| lock_error(err))?
+            }
+            Err((_, err)) => return Err(lock_error(err)),
+        };
+
+        lock.set_len(0)
+            .and_then(|_| (&*lock).write_all(process::id().to_string().as_bytes()))
+            .with_context(|| format!("writing pid to lock file {}", lock_path.display()))?;
+
+        HELD.store(true, Ordering::SeqCst);
+        Ok(Ok(Self { _lock: lock }))
+    }
+}
+
+impl Drop for StoreLock {
+    fn drop(&mut self) {
+        HELD.store(false, Ordering::SeqCst);
+    }
+}
+
+fn lock_error(err: Errno) -> SoarError {
+    SoarError::Custom(format!("Failed to lock the database: {}", err))
+}
@@ -12,7 +12,10 @@ use tracing::info;
 use crate::{
     config::Repository,
     constants::{METADATA_MIGRATIONS, SQLITE_MAGIC_BYTES, ZST_MAGIC_BYTES},
-    database::{connection::Database, migration::MigrationManager, models::RemotePackage},
+    database::{
+        connection::Database, migration::MigrationManager, models::RemotePackage,
+        search::build_search_index,
+    },
     error::{ErrorContext, SoarError},
     utils::calc_magic_bytes,
     SoarResult,
@@ -155,6 +158,7 @@ pub async fn fetch_metadata(repo: Repository, force: bool) -> SoarResult<Option<
         if magic_bytes == SQLITE_MAGIC_BYTES {
             fs::rename(&tmp_path, &metadata_db)
                 .with_context(|| format!("renaming {} to {}", tmp_path, metadata_db.display()))?;
+            build_search_index(&Connection::open(&metadata_db)?, "main")?;
         } else {
             let tmp_file = File::open(&tmp_path)
                 .with_context(|| format!("opening temporary file {}", tmp_path))?;
@@ -178,6 +182,8 @@ pub async fn fetch_metadata(repo: Repository, force: bool) -> SoarResult<Option<
         writer
             .write_all(&content)
             .with_context(|| format!("writing to metadata file {}", metadata_db.display()))?;
+        drop(writer);
+        build_search_index(&Connection::open(&metadata_db)?, "main")?;
     } else {
         let remote_metadata: Vec<RemotePackage> =
             serde_json::from_slice(&content).map_err(|err| {
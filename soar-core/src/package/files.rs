@@ -0,0 +1,306 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use rusqlite::{params, Connection};
+
+use crate::{
+    database::models::{FromRow, PackageFile, PackageFileKind},
+    error::ErrorContext,
+    SoarResult,
+};
+
+fn file_kind(path: &Path) -> Option<PackageFileKind> {
+    let metadata = fs::symlink_metadata(path).ok()?;
+    let file_type = metadata.file_type();
+    if file_type.is_symlink() {
+        Some(PackageFileKind::Symlink)
+    } else if file_type.is_dir() {
+        Some(PackageFileKind::Directory)
+    } else {
+        Some(PackageFileKind::File)
+    }
+}
+
+fn walk_dir(dir: &Path, files: &mut Vec<(PathBuf, PackageFileKind)>) -> SoarResult<()> {
+    for entry in
+        fs::read_dir(dir).with_context(|| format!("reading directory {}", dir.display()))?
+    {
+        let path = entry
+            .with_context(|| format!("reading entry from directory {}", dir.display()))?
+            .path();
+
+        let Some(kind) = file_kind(&path) else {
+            continue;
+        };
+        files.push((path.clone(), kind));
+
+        if kind == PackageFileKind::Directory {
+            walk_dir(&path, files)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Collects every entry in the install directory, along with the given paths
+/// created outside of it (bin, desktop and icon symlinks, portable directories).
+pub fn collect_package_files<P: AsRef<Path>>(
+    install_dir: P,
+    extra_paths: &[PathBuf],
+) -> SoarResult<Vec<(PathBuf, PackageFileKind)>> {
+    let install_dir = install_dir.as_ref();
+    let mut files = vec![(install_dir.to_path_buf(), PackageFileKind::Directory)];
+
+    if install_dir.is_dir() {
+        walk_dir(install_dir, &mut files)?;
+    }
+
+    for path in extra_paths {
+        if files.iter().any(|(existing, _)| existing == path) {
+            continue;
+        }
+        if let Some(kind) = file_kind(path) {
+            files.push((path.clone(), kind));
+        }
+    }
+
+    Ok(files)
+}
+
+/// Replaces the recorded files of the package. Paths recorded for other packages
+/// are taken over, as they've been overwritten by this package.
+pub fn record_package_files(
+    conn: &Connection,
+    package_id: u64,
+    files: &[(PathBuf, PackageFileKind)],
+) -> SoarResult<()> {
+    conn.execute(
+        "DELETE FROM package_files WHERE package_id = ?",
+        params![package_id],
+    )?;
+
+    let mut release_stmt =
+        conn.prepare("DELETE FROM package_files WHERE path = ? AND package_id != ?")?;
+    let mut insert_stmt =
+        conn.prepare("INSERT INTO package_files (package_id, path, kind) VALUES (?, ?, ?)")?;
+
+    for (path, kind) in files {
+        let path = path.to_string_lossy();
+        release_stmt.execute(params![path, package_id])?;
+        insert_stmt.execute(params![package_id, path, kind.as_str()])?;
+    }
+
+    Ok(())
+}
+
+pub fn get_package_files(conn: &Connection, package_id: u64) -> SoarResult<Vec<PackageFile>> {
+    let mut stmt = conn.prepare(
+        "SELECT package_id, path, kind FROM package_files
+        WHERE package_id = ?
+        ORDER BY path",
+    )?;
+    let files = stmt
+        .query_map(params![package_id], PackageFile::from_row)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(files)
+}
+
+/// Returns the id of the installed package owning the path, if any. Paths that
+/// aren't recorded themselves are looked up again with symlinks resolved.
+pub fn find_path_owner<P: AsRef<Path>>(conn: &Connection, path: P) -> SoarResult<Option<u64>> {
+    let path = path.as_ref();
+    let mut stmt = conn.prepare("SELECT package_id FROM package_files WHERE path = ? LIMIT 1")?;
+
+    let mut rows = stmt.query_map(params![path.to_string_lossy()], |row| row.get(0))?;
+    if let Some(owner) = rows.next().transpose()? {
+        return Ok(Some(owner));
+    }
+    drop(rows);
+
+    match fs::canonicalize(path) {
+        Ok(real_path) if real_path != path => {
+            let mut rows =
+                stmt.query_map(params![real_path.to_string_lossy()], |row| row.get(0))?;
+            Ok(rows.next().transpose()?)
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Removes the recorded links of the package outside of its install directory,
+/// skipping links that have since been taken over. Returns `false` if the
+/// package has no recorded files, i.e. it was installed before files were
+/// recorded.
+pub fn remove_recorded_links<P: AsRef<Path>>(
+    conn: &Connection,
+    package_id: u64,
+    installed_path: P,
+) -> SoarResult<bool> {
+    let installed_path = installed_path.as_ref();
+    let files = get_package_files(conn, package_id)?;
+    if files.is_empty() {
+        return Ok(false);
+    }
+
+    for file in files {
+        let path = PathBuf::from(&file.path);
+        if file.kind != PackageFileKind::Symlink || path.starts_with(installed_path) {
+            continue;
+        }
+        if fs::read_link(&path).is_ok_and(|target| target.starts_with(installed_path)) {
+            fs::remove_file(&path)
+                .with_context(|| format!("removing symlink {}", path.display()))?;
+        }
+    }
+
+    Ok(true)
+}
+
+pub fn delete_package_files(conn: &Connection, package_id: u64) -> SoarResult<()> {
+    conn.execute(
+        "DELETE FROM package_files WHERE package_id = ?",
+        params![package_id],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, os::unix::fs::symlink, process};
+
+    use super::*;
+
+    fn files_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        // the packages the files belong to aren't needed here
+        conn.execute_batch("PRAGMA foreign_keys = OFF;").unwrap();
+        conn.execute_batch(include_str!("../../migrations/core/V6_package_files.sql"))
+            .unwrap();
+        conn
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("soar-files-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        // resolved so paths compare equal to canonicalized ones
+        fs::canonicalize(dir).unwrap()
+    }
+
+    fn recorded_paths(conn: &Connection, package_id: u64) -> Vec<String> {
+        get_package_files(conn, package_id)
+            .unwrap()
+            .into_iter()
+            .map(|file| file.path)
+            .collect()
+    }
+
+    #[test]
+    fn collects_and_records_files() {
+        let dir = temp_dir("collect");
+        let install_dir = dir.join("pkg");
+        fs::create_dir_all(install_dir.join("share")).unwrap();
+        fs::write(install_dir.join("pkg"), "").unwrap();
+        fs::write(install_dir.join("share/icon.png"), "").unwrap();
+        let bin_link = dir.join("bin-pkg");
+        symlink(install_dir.join("pkg"), &bin_link).unwrap();
+
+        let files = collect_package_files(
+            &install_dir,
+            &[
+                bin_link.clone(),
+                install_dir.join("pkg"),
+                dir.join("missing"),
+            ],
+        )
+        .unwrap();
+        let mut kinds: Vec<_> = files
+            .iter()
+            .map(|(path, kind)| (path.strip_prefix(&dir).unwrap().to_path_buf(), *kind))
+            .collect();
+        kinds.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            kinds,
+            vec![
+                (PathBuf::from("bin-pkg"), PackageFileKind::Symlink),
+                (PathBuf::from("pkg"), PackageFileKind::Directory),
+                (PathBuf::from("pkg/pkg"), PackageFileKind::File),
+                (PathBuf::from("pkg/share"), PackageFileKind::Directory),
+                (PathBuf::from("pkg/share/icon.png"), PackageFileKind::File),
+            ]
+        );
+
+        let conn = files_db();
+        record_package_files(&conn, 1, &files).unwrap();
+        assert_eq!(recorded_paths(&conn, 1).len(), 5);
+
+        // recording again replaces the previous files
+        record_package_files(&conn, 1, &files[..1]).unwrap();
+        assert_eq!(
+            recorded_paths(&conn, 1),
+            vec![install_dir.to_string_lossy().to_string()]
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn takes_over_paths_of_other_packages() {
+        let conn = files_db();
+        let shared = PathBuf::from("/bin/shared");
+        let own = PathBuf::from("/bin/own");
+
+        record_package_files(
+            &conn,
+            1,
+            &[
+                (shared.clone(), PackageFileKind::Symlink),
+                (own.clone(), PackageFileKind::Symlink),
+            ],
+        )
+        .unwrap();
+        record_package_files(&conn, 2, &[(shared.clone(), PackageFileKind::Symlink)]).unwrap();
+
+        assert_eq!(find_path_owner(&conn, &shared).unwrap(), Some(2));
+        assert_eq!(find_path_owner(&conn, &own).unwrap(), Some(1));
+        assert_eq!(recorded_paths(&conn, 1), vec!["/bin/own"]);
+        assert_eq!(find_path_owner(&conn, "/bin/other").unwrap(), None);
+    }
+
+    #[test]
+    fn finds_owner_through_symlinks() {
+        let dir = temp_dir("owner");
+        let install_dir = dir.join("pkg");
+        fs::create_dir_all(&install_dir).unwrap();
+        let binary = install_dir.join("pkg");
+        fs::write(&binary, "").unwrap();
+        let bin_link = dir.join("bin-pkg");
+        symlink(&binary, &bin_link).unwrap();
+        let other_link = dir.join("other-pkg");
+        symlink(&binary, &other_link).unwrap();
+        let linked_dir = dir.join("linked");
+        symlink(&install_dir, &linked_dir).unwrap();
+
+        let conn = files_db();
+        record_package_files(
+            &conn,
+            1,
+            &collect_package_files(&install_dir, std::slice::from_ref(&bin_link)).unwrap(),
+        )
+        .unwrap();
+        // a link recorded for another package is owned by it, not by its target
+        record_package_files(&conn, 2, &[(other_link.clone(), PackageFileKind::Symlink)]).unwrap();
+
+        assert_eq!(find_path_owner(&conn, &bin_link).unwrap(), Some(1));
+        assert_eq!(find_path_owner(&conn, &other_link).unwrap(), Some(2));
+        assert_eq!(
+            find_path_owner(&conn, linked_dir.join("pkg")).unwrap(),
+            Some(1)
+        );
+        assert_eq!(find_path_owner(&conn, dir.join("missing")).unwrap(), None);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,32 +1,354 @@
-use std::{fs, path::Path};
+use std::{
+    ffi::OsStr,
+    fs::{self, File, Permissions},
+    os::unix::fs::PermissionsExt,
+    path::{Component, Path, PathBuf},
+};
 
-use squishy::{appimage::AppImage, EntryKind};
+use goblin::elf::Elf;
+use memmap2::Mmap;
+use squishy::{appimage::AppImage, EntryKind, SquashFS};
+use tracing::warn;
 
 use crate::{
-    constants::PNG_MAGIC_BYTES, database::models::PackageExt, error::ErrorContext,
-    utils::calc_magic_bytes, SoarResult,
+    constants::PNG_MAGIC_BYTES,
+    database::models::PackageExt,
+    desktop_entry::{DesktopEntry, MAIN_GROUP},
+    error::{ErrorContext, SoarError},
+    utils::{calc_magic_bytes, create_symlink, shell_quote},
+    SoarResult,
+};
+
+use super::common::{
+    hicolor_store, mime_store, shell_integration_path, symlink_desktop, symlink_hicolor_icons,
+    symlink_icon, symlink_mime_packages,
 };
 
-use super::common::{symlink_desktop, symlink_icon};
+const MAX_SYMLINK_DEPTH: usize = 8;
+
+/// Directory in the install directory an AppImage is extracted into, to run
+/// it without FUSE.
+pub const APPIMAGE_EXTRACT_DIR: &str = ".squashfs-root";
+
+/// Returns the icon name of the desktop file in the AppImage.
+fn desktop_icon_name(appimage: &AppImage) -> Option<String> {
+    let entry = appimage.find_desktop()?;
+    let content = appimage.squashfs.read_file(&entry.path).ok()?;
+    DesktopEntry::parse(&String::from_utf8_lossy(&content))
+        .ok()?
+        .get(MAIN_GROUP, "Icon")
+        .map(String::from)
+}
+
+fn is_icon_size(size: &str) -> bool {
+    if size == "scalable" {
+        return true;
+    }
+    let size = size.split_once('@').map_or(size, |(size, _)| size);
+    size.split_once('x').is_some_and(|(w, h)| {
+        !w.is_empty()
+            && !h.is_empty()
+            && w.bytes().all(|b| b.is_ascii_digit())
+            && h.bytes().all(|b| b.is_ascii_digit())
+    })
+}
+
+/// Returns the size and extension of an icon of the hicolor theme named
+/// `icon_name`, if the path is one.
+fn hicolor_icon_path(path: &Path, icon_name: &str) -> Option<(String, String)> {
+    let relative = path.strip_prefix("/usr/share/icons/hicolor").ok()?;
+    let components: Vec<&OsStr> = relative.iter().collect();
+    let [size, apps, file_name] = components[..] else {
+        return None;
+    };
+    let size = size.to_string_lossy();
+    let file_name = Path::new(file_name);
+    if apps != "apps" || !is_icon_size(&size) || file_name.file_stem()? != icon_name {
+        return None;
+    }
+    let ext = file_name
+        .extension()
+        .and_then(OsStr::to_str)
+        .filter(|ext| matches!(*ext, "png" | "svg"))?;
+
+    Some((size.into_owned(), ext.to_string()))
+}
+
+/// Returns the kind of the file an entry points to, following relative
+/// symlinks, which are common between the icon sizes.
+fn resolve_file<'a>(
+    squashfs: &'a SquashFS,
+    path: &Path,
+    kind: &EntryKind<'a>,
+) -> Option<EntryKind<'a>> {
+    let mut path = path.to_path_buf();
+    let mut kind = kind.clone();
+
+    for _ in 0..MAX_SYMLINK_DEPTH {
+        let link = match kind {
+            EntryKind::File(_) => return Some(kind),
+            EntryKind::Symlink(link) => link,
+            _ => return None,
+        };
+
+        // link targets are reported with a leading slash, even relative ones
+        let link = link.strip_prefix("/").unwrap_or(&link);
+        let target = if link.has_root() {
+            link.to_path_buf()
+        } else {
+            path.parent()?.join(link)
+        };
+
+        let mut normalized = PathBuf::from("/");
+        for component in target.components() {
+            match component {
+                Component::ParentDir => {
+                    normalized.pop();
+                }
+                Component::Normal(name) => normalized.push(name),
+                _ => {}
+            }
+        }
+
+        let entry = squashfs.entries().find(|entry| entry.path == normalized)?;
+        path = normalized;
+        kind = entry.kind;
+    }
+
+    None
+}
+
+/// Extracts the icons of every size shipped in the hicolor theme of the
+/// AppImage into the hicolor store. Returns whether any icon was found.
+fn extract_hicolor_icons(
+    appimage: &AppImage,
+    install_dir: &Path,
+    pkg_name: &str,
+    icon_name: &str,
+) -> SoarResult<bool> {
+    let squashfs = &appimage.squashfs;
+    let store = hicolor_store(install_dir);
+    let mut extracted = false;
+
+    for entry in squashfs.entries() {
+        let Some((size, ext)) = hicolor_icon_path(&entry.path, icon_name) else {
+            continue;
+        };
+
+        let Some(EntryKind::File(basic_file)) = resolve_file(squashfs, &entry.path, &entry.kind)
+        else {
+            continue;
+        };
+
+        let size_dir = store.join(size);
+        fs::create_dir_all(&size_dir)
+            .with_context(|| format!("creating directory {}", size_dir.display()))?;
+        squashfs.write_file(basic_file, size_dir.join(format!("{pkg_name}.{ext}")))?;
+        extracted = true;
+    }
+
+    Ok(extracted)
+}
+
+/// Extracts the shared-mime-info package files of the AppImage into the mime
+/// store. Returns whether any was found.
+fn extract_mime_packages(appimage: &AppImage, install_dir: &Path) -> SoarResult<bool> {
+    let squashfs = &appimage.squashfs;
+    let store = mime_store(install_dir);
+    let mut extracted = false;
+
+    for entry in squashfs.entries() {
+        let Ok(file_name) = entry.path.strip_prefix("/usr/share/mime/packages") else {
+            continue;
+        };
+        if file_name.components().count() != 1 || file_name.extension() != Some(OsStr::new("xml")) {
+            continue;
+        }
+        let Some(EntryKind::File(basic_file)) = resolve_file(squashfs, &entry.path, &entry.kind)
+        else {
+            continue;
+        };
+
+        fs::create_dir_all(&store)
+            .with_context(|| format!("creating directory {}", store.display()))?;
+        squashfs.write_file(basic_file, store.join(file_name))?;
+        extracted = true;
+    }
+
+    Ok(extracted)
+}
+
+/// Extracts the shell completions and man pages of the AppImage into `share`
+/// in the install directory, to be linked with the other shell integration
+/// files.
+fn extract_shell_integration(appimage: &AppImage, install_dir: &Path) -> SoarResult<()> {
+    let squashfs = &appimage.squashfs;
+
+    for entry in squashfs.entries() {
+        let Ok(relative) = entry.path.strip_prefix("/usr") else {
+            continue;
+        };
+        let Some(share_path) = shell_integration_path(relative) else {
+            continue;
+        };
+        let Some(EntryKind::File(basic_file)) = resolve_file(squashfs, &entry.path, &entry.kind)
+        else {
+            continue;
+        };
+
+        let dest = install_dir.join("share").join(share_path);
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("creating directory {}", parent.display()))?;
+        }
+        squashfs.write_file(basic_file, &dest)?;
+    }
+
+    Ok(())
+}
+
+/// Reads the update information embedded in the `.upd_info` section of the
+/// AppImage runtime, e.g. `zsync|https://example.com/app.AppImage.zsync`.
+///
+/// squishy only reads the SquashFS image, not the sections of the runtime,
+/// so the ELF is parsed with goblin as squishy does to find the image.
+pub fn read_update_info<P: AsRef<Path>>(path: P) -> SoarResult<Option<String>> {
+    let path = path.as_ref();
+    let file = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+    // SAFETY: the file is only read, and installed AppImages aren't modified
+    let data = unsafe { Mmap::map(&file) }
+        .with_context(|| format!("mapping {} into memory", path.display()))?;
+
+    let Ok(elf) = Elf::parse(&data) else {
+        return Ok(None);
+    };
+    let section = elf
+        .section_headers
+        .iter()
+        .find(|header| elf.shdr_strtab.get_at(header.sh_name) == Some(".upd_info"));
+    let Some(range) = section.and_then(|header| header.file_range()) else {
+        return Ok(None);
+    };
+    let Some(content) = data.get(range) else {
+        return Ok(None);
+    };
+
+    let end = content
+        .iter()
+        .position(|&b| b == 0)
+        .unwrap_or(content.len());
+    let update_info = String::from_utf8_lossy(&content[..end]).trim().to_string();
+    Ok((!update_info.is_empty()).then_some(update_info))
+}
+
+/// Extracts the AppImage into [`APPIMAGE_EXTRACT_DIR`] in the install
+/// directory and replaces it with a launcher running its `AppRun`, so it can
+/// be run on systems without FUSE.
+///
+/// The AppImage itself is not kept, so the launcher leaves `APPIMAGE` unset:
+/// apps use it to re-run or update the AppImage, which would point them at
+/// the launcher instead.
+pub fn extract_appimage<P: AsRef<Path>>(install_dir: P, file_path: P) -> SoarResult<()> {
+    let install_dir = install_dir.as_ref();
+    let file_path = file_path.as_ref();
+    let appimage = AppImage::new(None, &file_path, None)?;
+    let squashfs = &appimage.squashfs;
+    let app_dir = install_dir.join(APPIMAGE_EXTRACT_DIR);
+
+    if app_dir.exists() {
+        fs::remove_dir_all(&app_dir)
+            .with_context(|| format!("removing directory {}", app_dir.display()))?;
+    }
+
+    for entry in squashfs.entries() {
+        let Ok(relative) = entry.path.strip_prefix("/") else {
+            continue;
+        };
+        let dest = app_dir.join(relative);
+
+        match entry.kind {
+            EntryKind::Directory => {
+                fs::create_dir_all(&dest)
+                    .with_context(|| format!("creating directory {}", dest.display()))?;
+            }
+            EntryKind::File(basic_file) => {
+                if let Some(parent) = dest.parent() {
+                    fs::create_dir_all(parent)
+                        .with_context(|| format!("creating directory {}", parent.display()))?;
+                }
+                squashfs.write_file_with_permissions(basic_file, &dest, entry.header)?;
+            }
+            EntryKind::Symlink(ref link) => {
+                // link targets are reported with a leading slash, even relative ones
+                let link = link.strip_prefix("/").unwrap_or(link);
+                create_symlink(link, &dest)?;
+            }
+            EntryKind::Unknown => {}
+        }
+    }
+
+    let app_run = app_dir.join("AppRun");
+    if !app_run.exists() {
+        return Err(SoarError::Custom(format!(
+            "{} has no AppRun",
+            file_path.display()
+        )));
+    }
+
+    let launcher = format!(
+        "#!/bin/sh\n\
+        APPDIR={}\n\
+        ARGV0=\"$0\"\n\
+        OWD=\"$PWD\"\n\
+        export APPDIR ARGV0 OWD\n\
+        unset APPIMAGE\n\
+        exec \"$APPDIR/AppRun\" \"$@\"\n",
+        shell_quote(app_dir.to_string_lossy()),
+    );
+    fs::remove_file(file_path).with_context(|| format!("removing {}", file_path.display()))?;
+    fs::write(file_path, launcher).with_context(|| format!("writing {}", file_path.display()))?;
+    fs::set_permissions(file_path, Permissions::from_mode(0o755))
+        .with_context(|| format!("setting permissions of {}", file_path.display()))?;
+
+    Ok(())
+}
 
 pub async fn integrate_appimage<P: AsRef<Path>, T: PackageExt>(
     install_dir: P,
     file_path: P,
+    bin_dir: &Path,
     package: &T,
     has_icon: bool,
     has_desktop: bool,
-) -> SoarResult<()> {
-    if has_icon && has_desktop {
-        return Ok(());
-    }
-
+) -> SoarResult<Vec<PathBuf>> {
+    let mut links = Vec::new();
     let install_dir = install_dir.as_ref();
+    let has_mime = mime_store(install_dir).exists();
     let pkg_name = package.pkg_name();
+    let has_appstream = ["metainfo", "appdata"]
+        .iter()
+        .any(|kind| install_dir.join(format!("{pkg_name}.{kind}.xml")).exists());
+    let has_shell_integration = install_dir.join("share").exists();
+    if has_icon && has_desktop && has_mime && has_appstream && has_shell_integration {
+        return Ok(links);
+    }
+
     let appimage = AppImage::new(None, &file_path, None)?;
     let squashfs = &appimage.squashfs;
 
+    if !has_shell_integration {
+        extract_shell_integration(&appimage, install_dir)?;
+    }
+
+    if !has_mime && extract_mime_packages(&appimage, install_dir)? {
+        links.extend(symlink_mime_packages(mime_store(install_dir))?);
+    }
+
     if !has_icon {
-        if let Some(entry) = appimage.find_icon() {
+        let icon_name = desktop_icon_name(&appimage).unwrap_or_else(|| pkg_name.to_string());
+        if extract_hicolor_icons(&appimage, install_dir, pkg_name, &icon_name)? {
+            links.extend(symlink_hicolor_icons(hicolor_store(install_dir))?);
+        } else if let Some(entry) = appimage.find_icon() {
             if let EntryKind::File(basic_file) = entry.kind {
                 let dest = format!("{}/{}.DirIcon", install_dir.display(), pkg_name);
                 let _ = squashfs.write_file(basic_file, &dest);
@@ -41,7 +363,7 @@ pub async fn integrate_appimage<P: AsRef<Path>, T: PackageExt>(
                 fs::rename(&dest, &final_path)
                     .with_context(|| format!("renaming from {} to {}", dest, final_path))?;
 
-                symlink_icon(final_path)?;
+                links.push(symlink_icon(final_path)?);
             }
         }
     }
@@ -51,11 +373,20 @@ pub async fn integrate_appimage<P: AsRef<Path>, T: PackageExt>(
             if let EntryKind::File(basic_file) = entry.kind {
                 let dest = format!("{}/{}.desktop", install_dir.display(), pkg_name);
                 let _ = squashfs.write_file(basic_file, &dest);
-                symlink_desktop(dest, package)?;
+                match symlink_desktop(&dest, bin_dir, package) {
+                    Ok(link) => links.push(link),
+                    Err(SoarError::InvalidDesktopEntry(err)) => {
+                        warn!("Skipping desktop file of the AppImage: {}", err);
+                    }
+                    Err(err) => return Err(err),
+                }
             }
         }
     }
 
+    if has_appstream {
+        return Ok(links);
+    }
     if let Some(entry) = appimage.find_appstream() {
         if let EntryKind::File(basic_file) = entry.kind {
             let file_name = if entry
@@ -73,5 +404,5 @@ pub async fn integrate_appimage<P: AsRef<Path>, T: PackageExt>(
             let _ = squashfs.write_file(basic_file, &dest);
         }
     }
-    Ok(())
+    Ok(links)
 }
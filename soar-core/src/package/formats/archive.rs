@@ -0,0 +1,378 @@
+//! Packages shipped as a tree of files, like tarballs with a `bin/` layout or
+//! the payload of .deb and .rpm packages.
+
+use std::{
+    collections::HashSet,
+    env::consts::ARCH,
+    ffi::OsStr,
+    fs::{self, File},
+    io::{BufRead, BufReader, Read},
+    path::{Component, Path, PathBuf},
+};
+
+use bzip2::read::BzDecoder;
+use flate2::read::GzDecoder;
+use goblin::elf::Elf;
+use memmap2::Mmap;
+use tracing::warn;
+use xz2::read::XzDecoder;
+
+use crate::{
+    constants::{BZIP2_MAGIC_BYTES, GZIP_MAGIC_BYTES, XZ_MAGIC_BYTES, ZST_MAGIC_BYTES},
+    desktop_entry::{DesktopEntry, MAIN_GROUP},
+    error::ErrorContext,
+    utils::{create_symlink, process_dir},
+    SoarResult,
+};
+
+use super::{
+    common::{hicolor_store, mime_store},
+    deb::extract_deb,
+    get_file_type,
+    rpm::extract_rpm,
+    PackageFormat,
+};
+
+/// Directories of executables, relative to the root of a tree.
+const BIN_DIRS: [&str; 3] = ["bin", "usr/bin", "usr/local/bin"];
+
+/// Directories of shared data, relative to the root of a tree.
+const SHARE_DIRS: [&str; 3] = ["share", "usr/share", "usr/local/share"];
+
+/// Wraps the reader with the decompressor matching its magic bytes. Readers
+/// that aren't compressed are returned as is.
+pub(crate) fn decompress<'a, R: BufRead + 'a>(mut reader: R) -> SoarResult<Box<dyn Read + 'a>> {
+    let magic = reader
+        .fill_buf()
+        .with_context(|| "reading magic bytes of archive".to_string())?;
+
+    let reader: Box<dyn Read + 'a> = if magic.starts_with(&GZIP_MAGIC_BYTES) {
+        Box::new(GzDecoder::new(reader))
+    } else if magic.starts_with(&XZ_MAGIC_BYTES) {
+        Box::new(XzDecoder::new(reader))
+    } else if magic.starts_with(&BZIP2_MAGIC_BYTES) {
+        Box::new(BzDecoder::new(reader))
+    } else if magic.starts_with(&ZST_MAGIC_BYTES) {
+        Box::new(
+            zstd::stream::read::Decoder::with_buffer(reader)
+                .with_context(|| "initializing zstd decoder".to_string())?,
+        )
+    } else {
+        Box::new(reader)
+    };
+
+    Ok(reader)
+}
+
+/// Returns the path of an archive entry relative to the directory it's
+/// extracted into, or `None` if it would escape it.
+pub(crate) fn entry_path(name: &str) -> Option<PathBuf> {
+    let mut path = PathBuf::new();
+    for component in Path::new(name).components() {
+        match component {
+            Component::Normal(name) => path.push(name),
+            Component::CurDir | Component::RootDir => {}
+            _ => return None,
+        }
+    }
+    (!path.as_os_str().is_empty()).then_some(path)
+}
+
+/// Unpacks a tar archive into the directory.
+pub(crate) fn unpack_tar<R: Read>(reader: R, dest: &Path) -> SoarResult<()> {
+    let mut archive = tar::Archive::new(reader);
+    archive.set_overwrite(true);
+    archive
+        .unpack(dest)
+        .with_context(|| format!("unpacking tar archive into {}", dest.display()))?;
+    Ok(())
+}
+
+/// Extracts a tar archive, compressed or not, into the install directory.
+pub fn extract_tarball<P: AsRef<Path>>(path: P, install_dir: P) -> SoarResult<()> {
+    let path = path.as_ref();
+    let file = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+    unpack_tar(decompress(BufReader::new(file))?, install_dir.as_ref())
+}
+
+/// Returns the roots of the trees in the install directory: the install
+/// directory itself, and the top-level directories tarballs are usually
+/// wrapped in. The hidden stores are skipped.
+fn tree_roots(install_dir: &Path) -> Vec<PathBuf> {
+    let mut roots = vec![install_dir.to_path_buf()];
+    let Ok(entries) = fs::read_dir(install_dir) else {
+        return roots;
+    };
+
+    let mut dirs: Vec<PathBuf> = entries
+        .flatten()
+        .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect();
+    dirs.sort();
+    roots.extend(dirs);
+
+    roots
+}
+
+/// Returns the existing directories at `relative_dirs` in every tree of the
+/// install directory.
+fn tree_dirs(install_dir: &Path, relative_dirs: &[&str]) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    for root in tree_roots(install_dir) {
+        for relative in relative_dirs {
+            let dir = root.join(relative);
+            if dir.is_dir() && !dirs.contains(&dir) {
+                dirs.push(dir);
+            }
+        }
+    }
+    dirs
+}
+
+/// Returns the directories of executables in the trees of the install
+/// directory, e.g. `bin` of a tarball or `usr/bin` of a .deb payload.
+pub fn package_bin_dirs<P: AsRef<Path>>(install_dir: P) -> Vec<PathBuf> {
+    tree_dirs(install_dir.as_ref(), &BIN_DIRS)
+}
+
+/// Returns the directories of shared data in the trees of the install
+/// directory.
+pub fn package_share_dirs<P: AsRef<Path>>(install_dir: P) -> Vec<PathBuf> {
+    tree_dirs(install_dir.as_ref(), &SHARE_DIRS)
+}
+
+/// Returns the main binary of the package: the file named after the package
+/// in the install directory, or else in the directories of executables of
+/// its trees.
+pub fn find_main_binary<P: AsRef<Path>>(install_dir: P, pkg_name: &str) -> PathBuf {
+    let install_dir = install_dir.as_ref();
+    let bin_path = install_dir.join(pkg_name);
+    if bin_path.exists() {
+        return bin_path;
+    }
+
+    package_bin_dirs(install_dir)
+        .into_iter()
+        .map(|dir| dir.join(pkg_name))
+        .find(|path| path.is_file())
+        .unwrap_or(bin_path)
+}
+
+/// Finds the icon of a desktop entry in the tree: in the hicolor theme, in
+/// pixmaps, or at the path it names. Returns the icon with its size.
+fn find_tree_icons(share_dir: &Path, install_dir: &Path, icon: &str) -> Vec<(String, PathBuf)> {
+    let mut icons = Vec::new();
+
+    if let Some(relative) = icon.strip_prefix('/') {
+        let path = install_dir.join(relative);
+        if path.is_file() {
+            icons.extend(icon_size(&path).map(|size| (size, path)));
+        }
+        return icons;
+    }
+
+    let hicolor = share_dir.join("icons/hicolor");
+    if let Ok(entries) = fs::read_dir(&hicolor) {
+        for entry in entries.flatten() {
+            let size = entry.file_name().to_string_lossy().into_owned();
+            for ext in ["png", "svg"] {
+                let path = entry.path().join("apps").join(format!("{icon}.{ext}"));
+                if path.is_file() {
+                    icons.push((size.clone(), path));
+                }
+            }
+        }
+    }
+
+    if icons.is_empty() {
+        for ext in ["png", "svg"] {
+            let path = share_dir.join("pixmaps").join(format!("{icon}.{ext}"));
+            if path.is_file() {
+                icons.extend(icon_size(&path).map(|size| (size, path)));
+            }
+        }
+    }
+
+    icons
+}
+
+fn icon_size(path: &Path) -> Option<String> {
+    if path.extension() == Some(OsStr::new("svg")) {
+        return Some("scalable".to_string());
+    }
+    let (w, h) = image::image_dimensions(path).ok()?;
+    Some(format!("{w}x{h}"))
+}
+
+/// Links the icons of the desktop entries and the shared-mime-info package
+/// files shipped in the trees of the install directory into the hicolor and
+/// mime stores, to be integrated like the ones of AppImages. Icons are named
+/// after the desktop file, as the entry is rewritten to use that name.
+pub fn integrate_tree<P: AsRef<Path>>(install_dir: P) -> SoarResult<()> {
+    let install_dir = install_dir.as_ref();
+    let hicolor = hicolor_store(install_dir);
+    let mime = mime_store(install_dir);
+
+    for share_dir in package_share_dirs(install_dir) {
+        let Ok(entries) = fs::read_dir(share_dir.join("applications")) else {
+            continue;
+        };
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if path.extension() != Some(OsStr::new("desktop")) {
+                continue;
+            }
+            let Some(icon) = fs::read_to_string(&path)
+                .ok()
+                .and_then(|content| DesktopEntry::parse(&content).ok())
+                .and_then(|entry| entry.get(MAIN_GROUP, "Icon").map(String::from))
+            else {
+                continue;
+            };
+            let desktop_name = path.file_stem().unwrap().to_string_lossy();
+
+            for (size, icon_path) in find_tree_icons(&share_dir, install_dir, &icon) {
+                let ext = icon_path.extension().unwrap().to_string_lossy();
+                let store_path = hicolor.join(size).join(format!("{desktop_name}.{ext}"));
+                create_symlink(&icon_path, &store_path)?;
+            }
+        }
+
+        let Ok(entries) = fs::read_dir(share_dir.join("mime/packages")) else {
+            continue;
+        };
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if path.extension() == Some(OsStr::new("xml")) {
+                create_symlink(&path, &mime.join(path.file_name().unwrap()))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether the host provides the library in one of the default search paths.
+fn host_has_library(name: &str) -> bool {
+    let arch_dir = format!("{ARCH}-linux-gnu");
+    ["/lib", "/lib64", "/usr/lib", "/usr/lib64"]
+        .iter()
+        .flat_map(|dir| [PathBuf::from(dir), Path::new(dir).join(&arch_dir)])
+        .any(|dir| dir.join(name).exists())
+}
+
+/// Checks the binaries of a .deb or .rpm payload can run from the install
+/// directory rather than the prefix they were built for. Returns the issues
+/// found.
+pub fn check_relocation<P: AsRef<Path>>(install_dir: P) -> Vec<String> {
+    let install_dir = install_dir.as_ref();
+
+    let mut shipped_libs = HashSet::new();
+    let mut collect_action = |path: &Path| -> SoarResult<()> {
+        if let Some(name) = path.file_name().and_then(OsStr::to_str) {
+            if name.contains(".so") {
+                shipped_libs.insert(name.to_string());
+            }
+        }
+        Ok(())
+    };
+    let _ = process_dir(install_dir, &mut collect_action);
+
+    let mut issues = Vec::new();
+    for dir in package_bin_dirs(install_dir) {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for path in entries.flatten().map(|entry| entry.path()) {
+            let Ok(file) = File::open(&path) else {
+                continue;
+            };
+            // SAFETY: the payload was just extracted and is only read
+            let Ok(data) = (unsafe { Mmap::map(&file) }) else {
+                continue;
+            };
+            let Ok(elf) = Elf::parse(&data) else {
+                continue;
+            };
+            let name = path.strip_prefix(install_dir).unwrap_or(&path).display();
+
+            if let Some(interpreter) = elf.interpreter {
+                if !Path::new(interpreter).exists() {
+                    issues.push(format!(
+                        "{name} requires the interpreter {interpreter}, which is missing"
+                    ));
+                }
+            }
+
+            let search_paths: Vec<&str> = elf
+                .rpaths
+                .iter()
+                .chain(&elf.runpaths)
+                .flat_map(|paths| paths.split(':'))
+                .collect();
+            for search_path in &search_paths {
+                if let Some(relative) = search_path.strip_prefix('/') {
+                    if !Path::new(search_path).exists() && install_dir.join(relative).exists() {
+                        issues.push(format!(
+                            "{name} looks up libraries in {search_path}, which is only shipped in the package"
+                        ));
+                    }
+                }
+            }
+
+            // shipped libraries are found relative to the binary with $ORIGIN
+            let is_relocatable = search_paths.iter().any(|path| path.contains("ORIGIN"));
+            if !is_relocatable {
+                for library in &elf.libraries {
+                    if shipped_libs.contains(*library) && !host_has_library(library) {
+                        issues.push(format!(
+                            "{name} links to {library}, which is only shipped in the package"
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    issues
+}
+
+/// Unpacks the downloaded package if it's an archive that isn't extracted on
+/// download: a .deb or .rpm payload, or a plain tarball. Payloads are checked
+/// to run outside of the prefix they were built for.
+///
+/// Returns whether the package was unpacked.
+pub fn unpack_package<P: AsRef<Path>>(install_dir: P, pkg_name: &str) -> SoarResult<bool> {
+    let install_dir = install_dir.as_ref();
+    let path = install_dir.join(pkg_name);
+    let Ok(file) = File::open(&path) else {
+        return Ok(false);
+    };
+    let format = get_file_type(&mut BufReader::new(file));
+    if !matches!(
+        format,
+        Ok(PackageFormat::Deb | PackageFormat::Rpm | PackageFormat::Tarball)
+    ) {
+        return Ok(false);
+    }
+
+    // the archive may contain a file of the same name
+    let archive = install_dir.join(format!(".{pkg_name}.archive"));
+    fs::rename(&path, &archive)
+        .with_context(|| format!("renaming {} to {}", path.display(), archive.display()))?;
+
+    match format {
+        Ok(PackageFormat::Deb) => extract_deb(archive.as_path(), install_dir)?,
+        Ok(PackageFormat::Rpm) => extract_rpm(archive.as_path(), install_dir)?,
+        _ => extract_tarball(archive.as_path(), install_dir)?,
+    }
+    fs::remove_file(&archive).with_context(|| format!("removing {}", archive.display()))?;
+
+    if matches!(format, Ok(PackageFormat::Deb | PackageFormat::Rpm)) {
+        for issue in check_relocation(install_dir) {
+            warn!("{} - {}", pkg_name, issue);
+        }
+    }
+
+    Ok(true)
+}
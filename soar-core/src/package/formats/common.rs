@@ -10,18 +10,28 @@ use image::{imageops::FilterType, DynamicImage, GenericImageView};
 use regex::Regex;
 use soar_dl::downloader::{DownloadOptions, Downloader};
 use soar_dl::utils::FileMode;
+use tracing::warn;
 
 use crate::{
-    config::get_config,
     constants::PNG_MAGIC_BYTES,
     database::models::{Package, PackageExt},
+    desktop_entry::{rewrite_exec, DesktopEntry, MAIN_GROUP},
     error::{ErrorContext, SoarError},
-    utils::{calc_magic_bytes, create_symlink, home_data_path, process_dir},
+    utils::{
+        calc_magic_bytes, create_symlink, desktop_dir, icons_dir, integration_data_path,
+        metainfo_dir, mime_dir, process_dir,
+    },
+    xdg::refresh_desktop_integration,
     SoarResult,
 };
 
 use super::{
-    appimage::integrate_appimage, get_file_type, wrappe::setup_wrappe_portable_dir, PackageFormat,
+    appimage::{integrate_appimage, APPIMAGE_EXTRACT_DIR},
+    archive::{find_main_binary, integrate_tree, package_share_dirs},
+    get_file_type,
+    script::integrate_script,
+    wrappe::setup_wrappe_portable_dir,
+    PackageFormat,
 };
 
 const SUPPORTED_DIMENSIONS: &[(u32, u32)] = &[
@@ -67,8 +77,8 @@ pub fn symlink_icon<P: AsRef<Path>>(real_path: P) -> SoarResult<PathBuf> {
     let icon_name = real_path.file_stem().unwrap();
     let ext = real_path.extension();
 
-    let (w, h) = if ext == Some(OsStr::new("svg")) {
-        (128, 128)
+    let size = if ext == Some(OsStr::new("svg")) {
+        "scalable".to_string()
     } else {
         let image = image::open(real_path)?;
         let (orig_w, orig_h) = image.dimensions();
@@ -80,12 +90,12 @@ pub fn symlink_icon<P: AsRef<Path>>(real_path: P) -> SoarResult<PathBuf> {
             normalized_image.save(real_path)?;
         }
 
-        (w, h)
+        format!("{w}x{h}")
     };
 
     let final_path = PathBuf::from(format!(
-        "{}/icons/hicolor/{w}x{h}/apps/{}-soar.{}",
-        home_data_path(),
+        "{}/{size}/apps/{}-soar.{}",
+        icons_dir(),
         icon_name.to_string_lossy(),
         ext.unwrap_or_default().to_string_lossy()
     ));
@@ -94,8 +104,216 @@ pub fn symlink_icon<P: AsRef<Path>>(real_path: P) -> SoarResult<PathBuf> {
     Ok(final_path)
 }
 
-pub fn symlink_desktop<P: AsRef<Path>, T: PackageExt>(
+/// Directory in the install directory holding the icons of every size,
+/// laid out as `<size>/<pkg_name>.<ext>`.
+pub fn hicolor_store<P: AsRef<Path>>(install_dir: P) -> PathBuf {
+    install_dir.as_ref().join(".hicolor")
+}
+
+/// Links the icons of every size in the hicolor store into the icon theme.
+pub fn symlink_hicolor_icons<P: AsRef<Path>>(store: P) -> SoarResult<Vec<PathBuf>> {
+    let store = store.as_ref();
+    let mut links = Vec::new();
+
+    let mut symlink_action = |path: &Path| -> SoarResult<()> {
+        let (Some(size), Some(icon_name), Some(ext)) = (
+            path.parent().and_then(Path::file_name),
+            path.file_stem(),
+            path.extension(),
+        ) else {
+            return Ok(());
+        };
+
+        let final_path = PathBuf::from(format!(
+            "{}/{}/apps/{}-soar.{}",
+            icons_dir(),
+            size.to_string_lossy(),
+            icon_name.to_string_lossy(),
+            ext.to_string_lossy()
+        ));
+        create_symlink(path, &final_path)?;
+        links.push(final_path);
+        Ok(())
+    };
+    process_dir(store, &mut symlink_action)?;
+
+    Ok(links)
+}
+
+/// Directory in the install directory holding the shared-mime-info package
+/// files of the package.
+pub fn mime_store<P: AsRef<Path>>(install_dir: P) -> PathBuf {
+    install_dir.as_ref().join(".mime")
+}
+
+/// Links the shared-mime-info package files in the mime store into the MIME
+/// database.
+pub fn symlink_mime_packages<P: AsRef<Path>>(store: P) -> SoarResult<Vec<PathBuf>> {
+    let mut links = Vec::new();
+
+    let mut symlink_action = |path: &Path| -> SoarResult<()> {
+        if path.extension() != Some(OsStr::new("xml")) {
+            return Ok(());
+        }
+        let final_path = PathBuf::from(format!(
+            "{}/packages/{}-soar.xml",
+            mime_dir(),
+            path.file_stem().unwrap().to_string_lossy()
+        ));
+        create_symlink(path, &final_path)?;
+        links.push(final_path);
+        Ok(())
+    };
+    process_dir(store, &mut symlink_action)?;
+
+    Ok(links)
+}
+
+/// Returns the component name of an AppStream metadata file, if the path is
+/// one.
+pub fn metainfo_name(path: &Path) -> Option<&str> {
+    let file_name = path.file_name()?.to_str()?;
+    file_name
+        .strip_suffix(".metainfo.xml")
+        .or_else(|| file_name.strip_suffix(".appdata.xml"))
+        .filter(|name| !name.is_empty())
+}
+
+/// Links the AppStream metadata file into the metainfo directory. Desktop
+/// launchables are pointed to the integrated desktop files, which are renamed
+/// with the `-soar` suffix.
+pub fn symlink_metainfo<P: AsRef<Path>>(
+    real_path: P,
+    desktop_ids: &[String],
+) -> SoarResult<PathBuf> {
+    let real_path = real_path.as_ref();
+    let name = metainfo_name(real_path).unwrap();
+
+    let content = fs::read_to_string(real_path)
+        .with_context(|| format!("reading content of {}", real_path.display()))?;
+    let re = Regex::new(r#"(<launchable\s+type="desktop-id"\s*>)\s*([^<]+?)\s*(</launchable>)"#)
+        .unwrap();
+    let content = re.replace_all(&content, |caps: &regex::Captures| {
+        let desktop_id = &caps[2];
+        let stem = desktop_id.strip_suffix(".desktop").unwrap_or(desktop_id);
+        let soar_id = format!("{stem}-soar.desktop");
+        let replacement = if desktop_ids.contains(&soar_id) {
+            soar_id
+        } else if let [only] = desktop_ids {
+            only.clone()
+        } else {
+            desktop_id.to_string()
+        };
+        format!("{}{}{}", &caps[1], replacement, &caps[3])
+    });
+    fs::write(real_path, content.as_bytes())
+        .with_context(|| format!("writing metainfo file to {}", real_path.display()))?;
+
+    let final_path = PathBuf::from(format!("{}/{name}-soar.metainfo.xml", metainfo_dir()));
+    create_symlink(real_path, &final_path)?;
+    Ok(final_path)
+}
+
+/// Returns the desktop ids of the integrated desktop files among the links.
+fn linked_desktop_ids(links: &[PathBuf]) -> Vec<String> {
+    let desktop_dir = desktop_dir();
+    links
+        .iter()
+        .filter(|link| link.parent() == Some(Path::new(&desktop_dir)))
+        .filter_map(|link| Some(link.file_name()?.to_string_lossy().into_owned()))
+        .collect()
+}
+
+/// Directories under `share` holding shell completions and man pages, which
+/// are linked into the same layout under the integration data directory.
+pub const SHELL_INTEGRATION_DIRS: [&str; 4] = [
+    "bash-completion/completions",
+    "zsh/site-functions",
+    "fish/vendor_completions.d",
+    "man",
+];
+
+/// Removes the links in the integration directories pointing into the install
+/// directory, for packages installed before their files were recorded.
+pub fn remove_integration_links<P: AsRef<Path>>(install_dir: P) -> SoarResult<()> {
+    let install_dir = install_dir.as_ref();
+    let mut remove_action = |path: &Path| -> SoarResult<()> {
+        if fs::read_link(path).is_ok_and(|target| target.starts_with(install_dir)) {
+            fs::remove_file(path)
+                .with_context(|| format!("removing symlink {}", path.display()))?;
+        }
+        Ok(())
+    };
+
+    process_dir(desktop_dir(), &mut remove_action)?;
+    process_dir(icons_dir(), &mut remove_action)?;
+    process_dir(format!("{}/packages", mime_dir()), &mut remove_action)?;
+    process_dir(metainfo_dir(), &mut remove_action)?;
+    for dir in SHELL_INTEGRATION_DIRS {
+        process_dir(
+            format!("{}/{}", integration_data_path(), dir),
+            &mut remove_action,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Returns the path of a shell completion or man page relative to `share`,
+/// if the path is one.
+pub fn shell_integration_path(path: &Path) -> Option<PathBuf> {
+    let components: Vec<&OsStr> = path.iter().collect();
+    let share = components.iter().rposition(|c| *c == "share")?;
+    let relative: PathBuf = components[share + 1..].iter().collect();
+
+    SHELL_INTEGRATION_DIRS.iter().find_map(|dir| {
+        let rest = relative.strip_prefix(dir).ok()?;
+        let depth = rest.components().count();
+        let is_valid = if *dir == "man" {
+            // man<section>/page, or <lang>/man<section>/page
+            let section = rest.iter().nth(depth.checked_sub(2)?)?.to_string_lossy();
+            (depth == 2 || depth == 3) && section.starts_with("man")
+        } else {
+            depth == 1
+        };
+        is_valid.then(|| relative.clone())
+    })
+}
+
+/// Links the shell completions and man pages shipped in the install directory
+/// into the locations the shells and `man` look them up.
+pub fn symlink_shell_integration<P: AsRef<Path>>(install_dir: P) -> SoarResult<Vec<PathBuf>> {
+    let install_dir = install_dir.as_ref();
+    let data_path = integration_data_path();
+    let mut links = Vec::new();
+
+    let mut symlink_action = |path: &Path| -> SoarResult<()> {
+        let Ok(relative) = path.strip_prefix(install_dir) else {
+            return Ok(());
+        };
+        // skips the stores and the portable dirs
+        if relative
+            .iter()
+            .any(|component| component.to_string_lossy().starts_with('.'))
+        {
+            return Ok(());
+        }
+        let Some(share_path) = shell_integration_path(relative) else {
+            return Ok(());
+        };
+        let final_path = Path::new(&data_path).join(share_path);
+        create_symlink(path, &final_path)?;
+        links.push(final_path);
+        Ok(())
+    };
+    process_dir(install_dir, &mut symlink_action)?;
+
+    Ok(links)
+}
+
+pub fn symlink_desktop<P: AsRef<Path>, B: AsRef<Path>, T: PackageExt>(
     real_path: P,
+    bin_dir: B,
     package: &T,
 ) -> SoarResult<PathBuf> {
     let pkg_name = package.pkg_name();
@@ -104,38 +322,53 @@ pub fn symlink_desktop<P: AsRef<Path>, T: PackageExt>(
         .with_context(|| format!("reading content of desktop file: {}", real_path.display()))?;
     let file_name = real_path.file_stem().unwrap();
 
-    let final_content = {
-        let re = Regex::new(r"(?m)^(Icon|Exec|TryExec)=(.*)").unwrap();
+    let mut entry = DesktopEntry::parse(&content)?;
+    entry.validate()?;
 
-        re.replace_all(&content, |caps: &regex::Captures| match &caps[1] {
-            "Icon" => format!("Icon={}-soar", file_name.to_string_lossy()),
-            "Exec" | "TryExec" => {
-                let value = &caps[0];
-                let bin_path = get_config().get_bin_path().unwrap();
-                let new_value = format!("{}/{}", &bin_path.display(), pkg_name);
+    let icon_name = format!("{}-soar", file_name.to_string_lossy());
+    let original_icon = entry.get(MAIN_GROUP, "Icon").map(String::from);
+    entry.set_all_locales(MAIN_GROUP, "Icon", &icon_name);
 
-                if value.contains("{{pkg_path}}") {
-                    value.replace("{{pkg_path}}", &new_value)
-                } else {
-                    format!("{}={}", &caps[1], new_value)
-                }
-            }
-            _ => unreachable!(),
-        })
-        .to_string()
+    let program = format!("{}/{}", bin_dir.as_ref().display(), pkg_name);
+    let rewrite = |value: &str| {
+        if value.contains("{{pkg_path}}") {
+            value.replace("{{pkg_path}}", &program)
+        } else {
+            rewrite_exec(value, &program)
+        }
     };
 
+    if let Some(try_exec) = entry.get(MAIN_GROUP, "TryExec") {
+        let try_exec = if try_exec.contains("{{pkg_path}}") {
+            try_exec.replace("{{pkg_path}}", &program)
+        } else {
+            program.clone()
+        };
+        entry.set(MAIN_GROUP, "TryExec", &try_exec);
+    }
+
+    let groups = std::iter::once(MAIN_GROUP.to_string()).chain(entry.action_groups());
+    for group in groups {
+        if let Some(exec) = entry.get(&group, "Exec") {
+            let exec = rewrite(exec);
+            entry.set(&group, "Exec", &exec);
+        }
+        if group != MAIN_GROUP && entry.get(&group, "Icon") == original_icon.as_deref() {
+            entry.set_all_locales(&group, "Icon", &icon_name);
+        }
+    }
+
     let mut writer = BufWriter::new(
         File::create(real_path)
             .with_context(|| format!("creating desktop file {}", real_path.display()))?,
     );
     writer
-        .write_all(final_content.as_bytes())
+        .write_all(entry.to_string().as_bytes())
         .with_context(|| format!("writing desktop file to {}", real_path.display()))?;
 
     let final_path = PathBuf::from(format!(
-        "{}/applications/{}-soar.desktop",
-        home_data_path(),
+        "{}/{}-soar.desktop",
+        desktop_dir(),
         file_name.to_string_lossy()
     ));
 
@@ -143,10 +376,11 @@ pub fn symlink_desktop<P: AsRef<Path>, T: PackageExt>(
     Ok(final_path)
 }
 
-pub async fn integrate_remote<P: AsRef<Path>>(
+pub async fn integrate_remote<P: AsRef<Path>, B: AsRef<Path>>(
     package_path: P,
+    bin_dir: B,
     package: &Package,
-) -> SoarResult<()> {
+) -> SoarResult<Vec<PathBuf>> {
     let package_path = package_path.as_ref();
     let icon_url = &package.icon;
     let desktop_url = &package.desktop;
@@ -194,10 +428,39 @@ pub async fn integrate_remote<P: AsRef<Path>>(
         })?;
     }
 
-    symlink_icon(&icon_output_path)?;
-    symlink_desktop(&desktop_output_path, package)?;
+    let mut links = vec![symlink_icon(&icon_output_path)?];
+    match symlink_desktop(&desktop_output_path, bin_dir, package) {
+        Ok(link) => links.push(link),
+        Err(SoarError::InvalidDesktopEntry(err)) => {
+            warn!(
+                "Skipping desktop file {}: {}",
+                desktop_output_path.display(),
+                err
+            );
+        }
+        Err(err) => return Err(err),
+    }
 
-    Ok(())
+    if let Some(appstream_url) = &package.appstream {
+        let appstream_output_path = package_path.join(format!("{}.metainfo.xml", package.pkg_name));
+        let options = DownloadOptions {
+            url: appstream_url.clone(),
+            output_path: Some(appstream_output_path.to_string_lossy().to_string()),
+            progress_callback: None,
+            extract_archive: false,
+            extract_dir: None,
+            file_mode: FileMode::SkipExisting,
+            prompt: None,
+        };
+        downloader.download(options).await?;
+
+        let desktop_ids = linked_desktop_ids(&links);
+        links.push(symlink_metainfo(&appstream_output_path, &desktop_ids)?);
+    }
+
+    refresh_desktop_integration();
+
+    Ok(links)
 }
 
 pub fn create_portable_link<P: AsRef<Path>>(
@@ -205,7 +468,7 @@ pub fn create_portable_link<P: AsRef<Path>>(
     real_path: P,
     pkg_name: &str,
     extension: &str,
-) -> SoarResult<()> {
+) -> SoarResult<PathBuf> {
     let base_dir = env::current_dir()
         .map_err(|_| SoarError::Custom("Error retrieving current directory".into()))?;
     let portable_path = portable_path.as_ref();
@@ -219,7 +482,7 @@ pub fn create_portable_link<P: AsRef<Path>>(
     fs::create_dir_all(&portable_path)
         .with_context(|| format!("creating directory {}", portable_path.display()))?;
     create_symlink(&portable_path, &real_path.as_ref().to_path_buf())?;
-    Ok(())
+    Ok(portable_path)
 }
 
 pub fn setup_portable_dir<P: AsRef<Path>, T: PackageExt>(
@@ -229,8 +492,9 @@ pub fn setup_portable_dir<P: AsRef<Path>, T: PackageExt>(
     portable_home: Option<&str>,
     portable_config: Option<&str>,
     portable_share: Option<&str>,
-) -> SoarResult<()> {
+) -> SoarResult<Vec<PathBuf>> {
     let bin_path = bin_path.as_ref();
+    let mut portable_dirs = Vec::new();
 
     let pkg_name = package.pkg_name();
     let pkg_config = bin_path.with_extension("config");
@@ -245,18 +509,23 @@ pub fn setup_portable_dir<P: AsRef<Path>, T: PackageExt>(
 
     if let Some(portable_home) = portable_home {
         if portable_home.is_empty() {
-            fs::create_dir(&pkg_home).with_context(|| {
+            fs::create_dir_all(&pkg_home).with_context(|| {
                 format!("creating portable home directory {}", pkg_home.display())
             })?;
         } else {
             let portable_home = PathBuf::from(portable_home);
-            create_portable_link(&portable_home, &pkg_home, pkg_name, "home")?;
+            portable_dirs.push(create_portable_link(
+                &portable_home,
+                &pkg_home,
+                pkg_name,
+                "home",
+            )?);
         }
     }
 
     if let Some(portable_config) = portable_config {
         if portable_config.is_empty() {
-            fs::create_dir(&pkg_config).with_context(|| {
+            fs::create_dir_all(&pkg_config).with_context(|| {
                 format!(
                     "creating portable config directory {}",
                     pkg_config.display()
@@ -264,22 +533,32 @@ pub fn setup_portable_dir<P: AsRef<Path>, T: PackageExt>(
             })?;
         } else {
             let portable_config = PathBuf::from(portable_config);
-            create_portable_link(&portable_config, &pkg_config, pkg_name, "config")?;
+            portable_dirs.push(create_portable_link(
+                &portable_config,
+                &pkg_config,
+                pkg_name,
+                "config",
+            )?);
         }
     }
 
     if let Some(portable_share) = portable_share {
         if portable_share.is_empty() {
-            fs::create_dir(&pkg_share).with_context(|| {
+            fs::create_dir_all(&pkg_share).with_context(|| {
                 format!("creating portable share directory {}", pkg_share.display())
             })?;
         } else {
             let portable_share = PathBuf::from(portable_share);
-            create_portable_link(&portable_share, &pkg_share, pkg_name, "config")?;
+            portable_dirs.push(create_portable_link(
+                &portable_share,
+                &pkg_share,
+                pkg_name,
+                "config",
+            )?);
         }
     }
 
-    Ok(())
+    Ok(portable_dirs)
 }
 
 fn create_default_desktop_entry(name: &str, categories: &str) -> Vec<u8> {
@@ -296,25 +575,58 @@ fn create_default_desktop_entry(name: &str, categories: &str) -> Vec<u8> {
     .to_vec()
 }
 
-pub async fn integrate_package<P: AsRef<Path>, T: PackageExt>(
+pub async fn integrate_package<P: AsRef<Path>, B: AsRef<Path>, T: PackageExt>(
     install_dir: P,
+    bin_dir: B,
     package: &T,
     portable: Option<&str>,
     portable_home: Option<&str>,
     portable_config: Option<&str>,
     portable_share: Option<&str>,
-) -> SoarResult<()> {
+) -> SoarResult<Vec<PathBuf>> {
     let install_dir = install_dir.as_ref();
+    let bin_dir = bin_dir.as_ref();
     let pkg_name = package.pkg_name();
-    let bin_path = install_dir.join(pkg_name);
+    let bin_path = find_main_binary(install_dir, pkg_name);
 
     let mut has_desktop = false;
     let mut has_icon = false;
+    let mut created_paths = Vec::new();
+
+    integrate_tree(install_dir)?;
+    // icons shipped in trees are integrated through the hicolor store
+    let share_dirs = package_share_dirs(install_dir);
+
+    let hicolor = hicolor_store(install_dir);
+    if hicolor.is_dir() {
+        has_icon = true;
+        created_paths.extend(symlink_hicolor_icons(&hicolor)?);
+    }
+
+    let mime = mime_store(install_dir);
+    if mime.is_dir() {
+        created_paths.extend(symlink_mime_packages(&mime)?);
+    }
+
+    // the extracted AppImage is integrated from the AppImage itself
+    let app_dir = install_dir.join(APPIMAGE_EXTRACT_DIR);
+
     let mut symlink_action = |path: &Path| -> SoarResult<()> {
+        if path.starts_with(&app_dir) {
+            return Ok(());
+        }
         let ext = path.extension();
         if ext == Some(OsStr::new("desktop")) {
-            has_desktop = true;
-            symlink_desktop(path, package)?;
+            match symlink_desktop(path, bin_dir, package) {
+                Ok(link) => {
+                    has_desktop = true;
+                    created_paths.push(link);
+                }
+                Err(SoarError::InvalidDesktopEntry(err)) => {
+                    warn!("Skipping desktop file {}: {}", path.display(), err);
+                }
+                Err(err) => return Err(err),
+            }
         }
         Ok(())
     };
@@ -322,49 +634,82 @@ pub async fn integrate_package<P: AsRef<Path>, T: PackageExt>(
 
     let mut symlink_action = |path: &Path| -> SoarResult<()> {
         let ext = path.extension();
+        if path.starts_with(&hicolor)
+            || path.starts_with(&app_dir)
+            || share_dirs.iter().any(|dir| path.starts_with(dir))
+        {
+            return Ok(());
+        }
         if ext == Some(OsStr::new("png")) || ext == Some(OsStr::new("svg")) {
             has_icon = true;
-            symlink_icon(path)?;
+            created_paths.push(symlink_icon(path)?);
         }
         Ok(())
     };
     process_dir(install_dir, &mut symlink_action)?;
 
-    let mut reader = BufReader::new(
-        File::open(&bin_path).with_context(|| format!("opening {}", bin_path.display()))?,
-    );
-    let file_type = get_file_type(&mut reader)?;
+    // trees may not have a binary named after the package
+    let file_type = match File::open(&bin_path) {
+        Ok(file) => get_file_type(&mut BufReader::new(file))?,
+        Err(_) => PackageFormat::Unknown,
+    };
 
     match file_type {
         PackageFormat::AppImage | PackageFormat::RunImage => {
             if matches!(file_type, PackageFormat::AppImage) {
-                let _ = integrate_appimage(install_dir, &bin_path, package, has_icon, has_desktop)
-                    .await;
+                if let Ok(links) = integrate_appimage(
+                    install_dir,
+                    &bin_path,
+                    bin_dir,
+                    package,
+                    has_icon,
+                    has_desktop,
+                )
+                .await
+                {
+                    created_paths.extend(links);
+                }
             }
-            setup_portable_dir(
+            created_paths.extend(setup_portable_dir(
                 bin_path,
                 package,
                 portable,
                 portable_home,
                 portable_config,
                 portable_share,
-            )?;
+            )?);
         }
         PackageFormat::FlatImage => {
-            setup_portable_dir(
+            created_paths.extend(setup_portable_dir(
                 format!("{}/.{}", bin_path.parent().unwrap().display(), pkg_name),
                 package,
                 None,
                 None,
                 portable_config,
                 None,
-            )?;
+            )?);
         }
         PackageFormat::Wrappe => {
-            setup_wrappe_portable_dir(&bin_path, pkg_name, portable)?;
+            created_paths.extend(setup_wrappe_portable_dir(&bin_path, pkg_name, portable)?);
         }
+        PackageFormat::Script => integrate_script(&bin_path, pkg_name)?,
         _ => {}
     }
 
-    Ok(())
+    let desktop_ids = linked_desktop_ids(&created_paths);
+    let mut metainfo_files = Vec::new();
+    let mut collect_action = |path: &Path| -> SoarResult<()> {
+        if metainfo_name(path).is_some() && !path.starts_with(&app_dir) {
+            metainfo_files.push(path.to_path_buf());
+        }
+        Ok(())
+    };
+    process_dir(install_dir, &mut collect_action)?;
+    for path in metainfo_files {
+        created_paths.push(symlink_metainfo(path, &desktop_ids)?);
+    }
+
+    refresh_desktop_integration();
+
+    Ok(created_paths)
 }
@@ -0,0 +1,162 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, Read},
+    path::Path,
+};
+
+use crate::{
+    constants::AR_MAGIC_BYTES,
+    error::{ErrorContext, SoarError},
+    SoarResult,
+};
+
+use super::archive::{decompress, unpack_tar};
+
+/// Size of the header of each member of an ar archive.
+const AR_HEADER_SIZE: usize = 60;
+
+/// Extracts the payload of a .deb package, its `data.tar` member, into the
+/// install directory.
+pub fn extract_deb<P: AsRef<Path>>(path: P, install_dir: P) -> SoarResult<()> {
+    let path = path.as_ref();
+    let reader =
+        BufReader::new(File::open(path).with_context(|| format!("opening {}", path.display()))?);
+    unpack_deb(reader, install_dir.as_ref(), path)
+}
+
+/// Unpacks the `data.tar` member of a .deb package read from the reader into
+/// the directory. The path of the package is used in errors.
+fn unpack_deb<R: Read>(mut reader: R, install_dir: &Path, path: &Path) -> SoarResult<()> {
+    let mut magic = [0u8; 8];
+    reader
+        .read_exact(&mut magic)
+        .with_context(|| format!("reading {}", path.display()))?;
+    if magic != AR_MAGIC_BYTES {
+        return Err(SoarError::Custom(format!(
+            "{} is not a Debian package",
+            path.display()
+        )));
+    }
+
+    loop {
+        let mut header = [0u8; AR_HEADER_SIZE];
+        match reader.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(err) => return Err(err).with_context(|| format!("reading {}", path.display())),
+        }
+
+        let name = String::from_utf8_lossy(&header[..16]);
+        let name = name.trim_end().trim_end_matches('/');
+        let size: u64 = String::from_utf8_lossy(&header[48..58])
+            .trim()
+            .parse()
+            .map_err(|_| SoarError::Custom(format!("invalid member size in {}", path.display())))?;
+
+        let mut member = (&mut reader).take(size);
+        if name.starts_with("data.tar") {
+            return unpack_tar(decompress(BufReader::new(member))?, install_dir);
+        }
+
+        io::copy(&mut member, &mut io::sink())
+            .with_context(|| format!("reading {}", path.display()))?;
+        // members are aligned to 2 bytes
+        if size % 2 == 1 {
+            io::copy(&mut (&mut reader).take(1), &mut io::sink())
+                .with_context(|| format!("reading {}", path.display()))?;
+        }
+    }
+
+    Err(SoarError::Custom(format!(
+        "{} has no data archive",
+        path.display()
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env, fs,
+        path::{Path, PathBuf},
+        process,
+    };
+
+    use super::unpack_deb;
+    use crate::constants::AR_MAGIC_BYTES;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("soar-deb-{}-{}", name, process::id()));
+        fs::remove_dir_all(&dir).ok();
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn ar_member(name: &str, data: &[u8]) -> Vec<u8> {
+        let mut member = format!(
+            "{:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`\n",
+            name,
+            0,
+            0,
+            0,
+            644,
+            data.len()
+        )
+        .into_bytes();
+        member.extend(data);
+        if data.len() % 2 == 1 {
+            member.push(b'\n');
+        }
+        member
+    }
+
+    fn data_tar() -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(3);
+        header.set_mode(0o755);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "usr/bin/app", &b"app"[..])
+            .unwrap();
+        builder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn unpacks_data_member() {
+        let dest = test_dir("data");
+        let package = [
+            AR_MAGIC_BYTES.to_vec(),
+            ar_member("debian-binary", b"2.0\n"),
+            // odd sized members are padded
+            ar_member("control.tar/", b"odd"),
+            ar_member("data.tar/", &data_tar()),
+        ]
+        .concat();
+
+        unpack_deb(package.as_slice(), &dest, Path::new("test.deb")).unwrap();
+        assert_eq!(fs::read(dest.join("usr/bin/app")).unwrap(), b"app");
+
+        fs::remove_dir_all(&dest).unwrap();
+    }
+
+    #[test]
+    fn rejects_invalid_packages() {
+        let dest = test_dir("invalid");
+
+        let package = [b"!<arXX>\n".to_vec(), ar_member("data.tar", &data_tar())].concat();
+        assert!(unpack_deb(package.as_slice(), &dest, Path::new("test.deb")).is_err());
+
+        let package = [
+            AR_MAGIC_BYTES.to_vec(),
+            ar_member("debian-binary", b"2.0\n"),
+        ]
+        .concat();
+        assert!(unpack_deb(package.as_slice(), &dest, Path::new("test.deb")).is_err());
+
+        let mut package = [AR_MAGIC_BYTES.to_vec(), ar_member("data.tar", b"")].concat();
+        package[8 + 48] = b'x';
+        assert!(unpack_deb(package.as_slice(), &dest, Path::new("test.deb")).is_err());
+
+        fs::remove_dir_all(&dest).unwrap();
+    }
+}
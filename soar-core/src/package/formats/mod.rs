@@ -2,15 +2,20 @@ use std::io::{BufReader, Read, Seek, SeekFrom};
 
 use crate::{
     constants::{
-        APPIMAGE_MAGIC_BYTES, ELF_MAGIC_BYTES, FLATIMAGE_MAGIC_BYTES, RUNIMAGE_MAGIC_BYTES,
-        WRAPPE_MAGIC_BYTES,
+        APPIMAGE_MAGIC_BYTES, AR_MAGIC_BYTES, BZIP2_MAGIC_BYTES, ELF_MAGIC_BYTES,
+        FLATIMAGE_MAGIC_BYTES, GZIP_MAGIC_BYTES, RPM_MAGIC_BYTES, RUNIMAGE_MAGIC_BYTES,
+        SHEBANG_MAGIC_BYTES, TAR_MAGIC_BYTES, WRAPPE_MAGIC_BYTES, XZ_MAGIC_BYTES, ZST_MAGIC_BYTES,
     },
     error::SoarError,
     SoarResult,
 };
 
 pub mod appimage;
+pub mod archive;
 pub mod common;
+pub mod deb;
+pub mod rpm;
+pub mod script;
 pub mod wrappe;
 
 #[derive(Debug, PartialEq, Eq)]
@@ -20,9 +25,48 @@ pub enum PackageFormat {
     RunImage,
     Wrappe,
     ELF,
+    /// Debian package, with its payload in `data.tar`
+    Deb,
+    /// RPM package, with its payload in a cpio archive
+    Rpm,
+    /// Tar archive, optionally compressed
+    Tarball,
+    /// Script run by the interpreter in its shebang
+    Script,
+    /// Anything else. Flatpak bundles end up here too, as they can only be
+    /// installed into an OSTree repo next to the Flatpak runtime they need.
     Unknown,
 }
 
+/// Offset of the magic bytes in the header of a tar archive.
+const TAR_MAGIC_OFFSET: usize = 257;
+
+/// Detects the formats that aren't ELF executables from the start of the file.
+fn get_non_elf_type(header: &[u8]) -> Option<PackageFormat> {
+    if header.starts_with(&AR_MAGIC_BYTES) && header[8..].starts_with(b"debian-binary") {
+        return Some(PackageFormat::Deb);
+    }
+    if header.starts_with(&RPM_MAGIC_BYTES) {
+        return Some(PackageFormat::Rpm);
+    }
+    if header.starts_with(&SHEBANG_MAGIC_BYTES) {
+        return Some(PackageFormat::Script);
+    }
+
+    let is_tar = header
+        .get(TAR_MAGIC_OFFSET..)
+        .is_some_and(|magic| magic.starts_with(&TAR_MAGIC_BYTES));
+    let is_compressed = header.starts_with(&GZIP_MAGIC_BYTES)
+        || header.starts_with(&XZ_MAGIC_BYTES)
+        || header.starts_with(&BZIP2_MAGIC_BYTES)
+        || header.starts_with(&ZST_MAGIC_BYTES);
+    if is_tar || is_compressed {
+        return Some(PackageFormat::Tarball);
+    }
+
+    None
+}
+
 pub fn get_file_type<T>(file: &mut BufReader<T>) -> SoarResult<PackageFormat>
 where
     T: Read + Seek,
@@ -41,6 +85,21 @@ where
         return Ok(PackageFormat::RunImage);
     }
 
+    if magic_bytes[..4] != ELF_MAGIC_BYTES {
+        let mut header = Vec::with_capacity(TAR_MAGIC_OFFSET + TAR_MAGIC_BYTES.len());
+        file.rewind()
+            .map_err(|_| SoarError::Custom("Error rewinding file".into()))?;
+        file.by_ref()
+            .take(header.capacity() as u64)
+            .read_to_end(&mut header)
+            .map_err(|_| SoarError::Custom("Error reading magic bytes".into()))?;
+        file.rewind()
+            .map_err(|_| SoarError::Custom("Error rewinding file".into()))?;
+        if let Some(format) = get_non_elf_type(&header) {
+            return Ok(format);
+        }
+    }
+
     let start = file
         .seek(SeekFrom::End(0))
         .map_err(|_| SoarError::Custom("Error seeking to end of file".into()))?
@@ -0,0 +1,421 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File, Permissions},
+    io::{self, BufRead, BufReader, Read},
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    constants::RPM_MAGIC_BYTES,
+    error::{ErrorContext, SoarError},
+    utils::create_symlink,
+    SoarResult,
+};
+
+use super::archive::{decompress, entry_path};
+
+/// Size of the lead preceding the headers of an RPM package.
+const RPM_LEAD_SIZE: usize = 96;
+const RPM_HEADER_MAGIC: [u8; 3] = [0x8e, 0xad, 0xe8];
+
+/// Size of the header of each entry of a cpio archive in the newc format.
+const CPIO_HEADER_SIZE: usize = 110;
+const CPIO_TRAILER: &str = "TRAILER!!!";
+/// Longest symlink target accepted, as `PATH_MAX`.
+const CPIO_MAX_LINK_SIZE: u64 = 4096;
+
+const S_IFMT: u32 = 0o170000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFREG: u32 = 0o100000;
+const S_IFLNK: u32 = 0o120000;
+
+fn rpm_error(path: &Path, message: &str) -> SoarError {
+    SoarError::Custom(format!("{}: {}", path.display(), message))
+}
+
+fn skip<R: Read>(reader: &mut R, len: u64) -> io::Result<()> {
+    let skipped = io::copy(&mut reader.take(len), &mut io::sink())?;
+    if skipped != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(())
+}
+
+/// Skips a header structure of the package. The signature header is padded
+/// to 8 bytes.
+fn skip_header<R: Read>(reader: &mut R, path: &Path, padded: bool) -> SoarResult<()> {
+    let mut intro = [0u8; 16];
+    reader
+        .read_exact(&mut intro)
+        .with_context(|| format!("reading {}", path.display()))?;
+    if intro[..3] != RPM_HEADER_MAGIC {
+        return Err(rpm_error(path, "invalid header"));
+    }
+
+    let index_count = u32::from_be_bytes(intro[8..12].try_into().unwrap()) as u64;
+    let data_size = u32::from_be_bytes(intro[12..16].try_into().unwrap()) as u64;
+    let mut len = index_count * 16 + data_size;
+    if padded {
+        len += (8 - len % 8) % 8;
+    }
+    skip(reader, len).with_context(|| format!("reading {}", path.display()))?;
+
+    Ok(())
+}
+
+/// Returns the path of the entry in the directory, failing if one of its
+/// parents is a symlink, which would let the entry be written outside of it.
+fn entry_target(dest: &Path, relative: &Path, path: &Path) -> SoarResult<PathBuf> {
+    let mut target = dest.to_path_buf();
+    let mut components = relative.components().peekable();
+    while let Some(component) = components.next() {
+        target.push(component);
+        if components.peek().is_some() && target.is_symlink() {
+            return Err(rpm_error(
+                path,
+                &format!("payload entry {} is behind a symlink", relative.display()),
+            ));
+        }
+    }
+    Ok(target)
+}
+
+/// Unpacks a cpio archive in the newc format, the payload format of RPM
+/// packages, into the directory.
+fn unpack_cpio<R: Read>(mut reader: R, dest: &Path, path: &Path) -> SoarResult<()> {
+    // hard links have their data in the last entry of the inode
+    let mut hard_links: HashMap<u32, Vec<PathBuf>> = HashMap::new();
+
+    loop {
+        let mut header = [0u8; CPIO_HEADER_SIZE];
+        reader
+            .read_exact(&mut header)
+            .with_context(|| format!("reading payload of {}", path.display()))?;
+        if !matches!(&header[..6], b"070701" | b"070702") {
+            return Err(rpm_error(path, "unsupported payload format"));
+        }
+
+        let field = |index: usize| -> SoarResult<u32> {
+            let start = 6 + index * 8;
+            std::str::from_utf8(&header[start..start + 8])
+                .ok()
+                .and_then(|value| u32::from_str_radix(value, 16).ok())
+                .ok_or_else(|| rpm_error(path, "invalid payload header"))
+        };
+        let inode = field(0)?;
+        let mode = field(1)?;
+        let nlink = field(4)?;
+        let file_size = field(6)? as u64;
+        let name_size = field(11)? as usize;
+
+        let mut name = vec![0u8; name_size];
+        reader
+            .read_exact(&mut name)
+            .with_context(|| format!("reading payload of {}", path.display()))?;
+        let name = String::from_utf8_lossy(&name)
+            .trim_end_matches('\0')
+            .to_string();
+        // the header and name, and the data are aligned to 4 bytes
+        skip(
+            &mut reader,
+            ((4 - (CPIO_HEADER_SIZE + name_size) % 4) % 4) as u64,
+        )
+        .with_context(|| format!("reading payload of {}", path.display()))?;
+        let data_padding = (4 - file_size % 4) % 4;
+
+        if name == CPIO_TRAILER {
+            break;
+        }
+
+        let Some(relative) = entry_path(&name) else {
+            skip(&mut reader, file_size + data_padding)
+                .with_context(|| format!("reading payload of {}", path.display()))?;
+            continue;
+        };
+        let target = entry_target(dest, &relative, path)?;
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("creating directory {}", parent.display()))?;
+        }
+
+        match mode & S_IFMT {
+            S_IFDIR => {
+                fs::create_dir_all(&target)
+                    .with_context(|| format!("creating directory {}", target.display()))?;
+            }
+            S_IFLNK => {
+                if file_size > CPIO_MAX_LINK_SIZE {
+                    return Err(rpm_error(path, "symlink target too long"));
+                }
+                let mut link = vec![0u8; file_size as usize];
+                reader
+                    .read_exact(&mut link)
+                    .with_context(|| format!("reading payload of {}", path.display()))?;
+                let link = PathBuf::from(String::from_utf8_lossy(&link).into_owned());
+                create_symlink(&link, &target)?;
+                skip(&mut reader, data_padding)
+                    .with_context(|| format!("reading payload of {}", path.display()))?;
+                continue;
+            }
+            S_IFREG if file_size == 0 && nlink > 1 => {
+                hard_links.entry(inode).or_default().push(relative);
+            }
+            S_IFREG => {
+                // don't write through a symlink unpacked earlier
+                if target.is_symlink() {
+                    fs::remove_file(&target)
+                        .with_context(|| format!("removing symlink {}", target.display()))?;
+                }
+                let mut file = File::create(&target)
+                    .with_context(|| format!("creating file {}", target.display()))?;
+                let written = io::copy(&mut (&mut reader).take(file_size), &mut file)
+                    .with_context(|| format!("writing file {}", target.display()))?;
+                if written != file_size {
+                    return Err(rpm_error(path, "truncated payload"));
+                }
+                fs::set_permissions(&target, Permissions::from_mode(mode & 0o7777))
+                    .with_context(|| format!("setting permissions of {}", target.display()))?;
+
+                for link in hard_links.remove(&inode).unwrap_or_default() {
+                    let link = entry_target(dest, &link, path)?;
+                    fs::hard_link(&target, &link).with_context(|| {
+                        format!("linking {} to {}", link.display(), target.display())
+                    })?;
+                }
+                skip(&mut reader, data_padding)
+                    .with_context(|| format!("reading payload of {}", path.display()))?;
+                continue;
+            }
+            _ => {}
+        }
+
+        skip(&mut reader, file_size + data_padding)
+            .with_context(|| format!("reading payload of {}", path.display()))?;
+    }
+
+    // entries whose data never came are empty files
+    for link in hard_links.into_values().flatten() {
+        let link = entry_target(dest, &link, path)?;
+        if link.is_symlink() {
+            fs::remove_file(&link)
+                .with_context(|| format!("removing symlink {}", link.display()))?;
+        }
+        File::create(&link).with_context(|| format!("creating file {}", link.display()))?;
+    }
+
+    Ok(())
+}
+
+/// Extracts the payload of an RPM package into the install directory.
+pub fn extract_rpm<P: AsRef<Path>>(path: P, install_dir: P) -> SoarResult<()> {
+    let path = path.as_ref();
+    let reader =
+        BufReader::new(File::open(path).with_context(|| format!("opening {}", path.display()))?);
+    unpack_rpm(reader, install_dir.as_ref(), path)
+}
+
+/// Unpacks an RPM package read from the reader into the directory. The path
+/// of the package is used in errors.
+fn unpack_rpm<R: BufRead>(mut reader: R, dest: &Path, path: &Path) -> SoarResult<()> {
+    let mut lead = [0u8; RPM_LEAD_SIZE];
+    reader
+        .read_exact(&mut lead)
+        .with_context(|| format!("reading {}", path.display()))?;
+    if lead[..4] != RPM_MAGIC_BYTES {
+        return Err(rpm_error(path, "not an RPM package"));
+    }
+
+    skip_header(&mut reader, path, true)?;
+    skip_header(&mut reader, path, false)?;
+
+    unpack_cpio(decompress(reader)?, dest, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env, fs,
+        os::unix::fs::PermissionsExt,
+        path::{Path, PathBuf},
+        process,
+    };
+
+    use super::{unpack_cpio, unpack_rpm, RPM_HEADER_MAGIC, RPM_LEAD_SIZE};
+    use crate::constants::RPM_MAGIC_BYTES;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("soar-rpm-{}-{}", name, process::id()));
+        fs::remove_dir_all(&dir).ok();
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn cpio_entry(name: &str, mode: u32, inode: u32, nlink: u32, data: &[u8]) -> Vec<u8> {
+        cpio_header(name, mode, inode, nlink, data.len() as u32, data)
+    }
+
+    fn cpio_header(
+        name: &str,
+        mode: u32,
+        inode: u32,
+        nlink: u32,
+        file_size: u32,
+        data: &[u8],
+    ) -> Vec<u8> {
+        let name_size = name.len() as u32 + 1;
+        let fields = [
+            inode, mode, 0, 0, nlink, 0, file_size, 0, 0, 0, 0, name_size, 0,
+        ];
+
+        let mut entry = b"070701".to_vec();
+        for field in fields {
+            entry.extend(format!("{:08x}", field).bytes());
+        }
+        entry.extend(name.bytes());
+        entry.push(0);
+        entry.resize(entry.len().next_multiple_of(4), 0);
+        entry.extend(data);
+        entry.resize(entry.len().next_multiple_of(4), 0);
+        entry
+    }
+
+    fn cpio(entries: &[Vec<u8>]) -> Vec<u8> {
+        let mut archive = entries.concat();
+        archive.extend(cpio_entry("TRAILER!!!", 0, 0, 1, &[]));
+        archive
+    }
+
+    fn unpack(archive: &[u8], dest: &Path) -> crate::SoarResult<()> {
+        unpack_cpio(archive, dest, Path::new("test.rpm"))
+    }
+
+    #[test]
+    fn unpacks_cpio() {
+        let dest = test_dir("cpio");
+        let archive = cpio(&[
+            cpio_entry("./usr/bin", 0o040755, 1, 2, &[]),
+            cpio_entry("./usr/bin/app", 0o100755, 2, 1, b"#!/bin/sh\n"),
+            cpio_entry("./usr/bin/alias", 0o120777, 3, 1, b"app"),
+            cpio_entry("./usr/share/a", 0o100644, 4, 2, &[]),
+            cpio_entry("./usr/share/b", 0o100644, 4, 2, b"linked"),
+            cpio_entry("../escape", 0o100644, 5, 1, b"skipped"),
+        ]);
+
+        unpack(&archive, &dest).unwrap();
+
+        let app = dest.join("usr/bin/app");
+        assert_eq!(fs::read(&app).unwrap(), b"#!/bin/sh\n");
+        assert_eq!(
+            fs::metadata(&app).unwrap().permissions().mode() & 0o777,
+            0o755
+        );
+        assert_eq!(
+            fs::read_link(dest.join("usr/bin/alias")).unwrap(),
+            Path::new("app")
+        );
+        assert_eq!(fs::read(dest.join("usr/share/a")).unwrap(), b"linked");
+        assert!(!dest.parent().unwrap().join("escape").exists());
+
+        fs::remove_dir_all(&dest).unwrap();
+    }
+
+    #[test]
+    fn rejects_entries_behind_symlinks() {
+        let dest = test_dir("cpio-symlink");
+        let outside = test_dir("cpio-symlink-outside");
+        let archive = cpio(&[
+            cpio_entry(
+                "./lib",
+                0o120777,
+                1,
+                1,
+                outside.to_string_lossy().as_bytes(),
+            ),
+            cpio_entry("./lib/evil", 0o100644, 2, 1, b"evil"),
+        ]);
+
+        assert!(unpack(&archive, &dest).is_err());
+        assert!(!outside.join("evil").exists());
+
+        fs::remove_dir_all(&dest).unwrap();
+        fs::remove_dir_all(&outside).unwrap();
+    }
+
+    #[test]
+    fn replaces_symlinks_instead_of_writing_through() {
+        let dest = test_dir("cpio-overwrite");
+        let outside = test_dir("cpio-overwrite-outside");
+        let victim = outside.join("victim");
+        fs::write(&victim, "original").unwrap();
+        let archive = cpio(&[
+            cpio_entry(
+                "./file",
+                0o120777,
+                1,
+                1,
+                victim.to_string_lossy().as_bytes(),
+            ),
+            cpio_entry("./file", 0o100644, 2, 1, b"replaced"),
+        ]);
+
+        unpack(&archive, &dest).unwrap();
+
+        assert_eq!(fs::read(&victim).unwrap(), b"original");
+        assert!(!dest.join("file").is_symlink());
+        assert_eq!(fs::read(dest.join("file")).unwrap(), b"replaced");
+
+        fs::remove_dir_all(&dest).unwrap();
+        fs::remove_dir_all(&outside).unwrap();
+    }
+
+    #[test]
+    fn rejects_oversized_symlink_targets() {
+        let dest = test_dir("cpio-oversized");
+        let archive = cpio(&[cpio_header("./link", 0o120777, 1, 1, u32::MAX, &[])]);
+
+        assert!(unpack(&archive, &dest).is_err());
+
+        fs::remove_dir_all(&dest).unwrap();
+    }
+
+    fn rpm(payload: &[u8]) -> Vec<u8> {
+        let mut lead = RPM_MAGIC_BYTES.to_vec();
+        lead.resize(RPM_LEAD_SIZE, 0);
+
+        // signature and main headers without entries
+        let mut header = RPM_HEADER_MAGIC.to_vec();
+        header.resize(16, 0);
+
+        [lead, header.clone(), header, payload.to_vec()].concat()
+    }
+
+    #[test]
+    fn unpacks_rpm() {
+        let dest = test_dir("rpm");
+        let package = rpm(&cpio(&[cpio_entry("./bin/app", 0o100755, 1, 1, b"app")]));
+
+        unpack_rpm(package.as_slice(), &dest, Path::new("test.rpm")).unwrap();
+        assert_eq!(fs::read(dest.join("bin/app")).unwrap(), b"app");
+
+        fs::remove_dir_all(&dest).unwrap();
+    }
+
+    #[test]
+    fn rejects_invalid_rpm() {
+        let dest = test_dir("rpm-invalid");
+
+        let mut package = rpm(&cpio(&[]));
+        package[0] = 0;
+        assert!(unpack_rpm(package.as_slice(), &dest, Path::new("test.rpm")).is_err());
+
+        let mut package = rpm(&cpio(&[]));
+        package[RPM_LEAD_SIZE] = 0;
+        assert!(unpack_rpm(package.as_slice(), &dest, Path::new("test.rpm")).is_err());
+
+        let package = rpm(b"not a cpio archive");
+        assert!(unpack_rpm(package.as_slice(), &dest, Path::new("test.rpm")).is_err());
+
+        fs::remove_dir_all(&dest).unwrap();
+    }
+}
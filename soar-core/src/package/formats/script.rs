@@ -0,0 +1,70 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+};
+
+use tracing::warn;
+
+use crate::{error::ErrorContext, utils::find_in_path, SoarResult};
+
+use super::{get_file_type, PackageFormat};
+
+/// Whether the file is a script with a shebang.
+pub fn is_script<P: AsRef<Path>>(path: P) -> bool {
+    File::open(path).is_ok_and(|file| {
+        get_file_type(&mut BufReader::new(file)).ok() == Some(PackageFormat::Script)
+    })
+}
+
+/// Returns the program running the script, resolving `env` to the program
+/// it's given.
+pub fn read_interpreter<P: AsRef<Path>>(path: P) -> SoarResult<Option<String>> {
+    let path = path.as_ref();
+    let mut shebang = String::new();
+    BufReader::new(File::open(path).with_context(|| format!("opening {}", path.display()))?)
+        .read_line(&mut shebang)
+        .with_context(|| format!("reading {}", path.display()))?;
+
+    let Some(command) = shebang.strip_prefix("#!") else {
+        return Ok(None);
+    };
+    let mut words = command.split_whitespace();
+    let Some(interpreter) = words.next() else {
+        return Ok(None);
+    };
+
+    if Path::new(interpreter)
+        .file_name()
+        .is_some_and(|name| name == "env")
+    {
+        // skips the options of env, like -S
+        return Ok(words
+            .find(|word| !word.starts_with('-') && !word.contains('='))
+            .map(String::from));
+    }
+
+    Ok(Some(interpreter.to_string()))
+}
+
+/// Checks the interpreter of the script is available, as unlike the other
+/// formats, scripts aren't self-contained.
+pub fn integrate_script<P: AsRef<Path>>(bin_path: P, pkg_name: &str) -> SoarResult<()> {
+    let Some(interpreter) = read_interpreter(bin_path)? else {
+        return Ok(());
+    };
+
+    let is_available = if interpreter.contains('/') {
+        Path::new(&interpreter).is_file()
+    } else {
+        find_in_path(&interpreter).is_some()
+    };
+    if !is_available {
+        warn!(
+            "{} requires {}, which is not installed.",
+            pkg_name, interpreter
+        );
+    }
+
+    Ok(())
+}
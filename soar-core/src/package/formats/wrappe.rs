@@ -8,7 +8,7 @@ pub fn setup_wrappe_portable_dir<P: AsRef<Path>>(
     bin_path: P,
     pkg_name: &str,
     portable: Option<&str>,
-) -> SoarResult<()> {
+) -> SoarResult<Vec<PathBuf>> {
     let bin_path = bin_path.as_ref();
     let package_path = &bin_path.parent().unwrap();
     let real_path = package_path.join(format!(".{}.wrappe", pkg_name));
@@ -16,9 +16,10 @@ pub fn setup_wrappe_portable_dir<P: AsRef<Path>>(
     if let Some(portable) = portable {
         if !portable.is_empty() {
             let portable = PathBuf::from(portable);
-            create_portable_link(&portable, &real_path, pkg_name, "wrappe")?;
+            let portable_dir = create_portable_link(&portable, &real_path, pkg_name, "wrappe")?;
+            return Ok(vec![portable_dir]);
         }
     }
 
-    Ok(())
+    Ok(Vec::new())
 }
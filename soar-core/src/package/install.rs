@@ -1,5 +1,8 @@
 use std::{
-    env, fs,
+    env,
+    fs::{self, Permissions},
+    io::BufReader,
+    os::unix::fs::PermissionsExt,
     path::{Path, PathBuf},
     sync::{Arc, Mutex},
     thread::sleep,
@@ -13,25 +16,51 @@ use soar_dl::{
     error::DownloadError,
     utils::FileMode,
 };
+use tracing::debug;
 
 use crate::{
-    config::get_config,
+    config::{get_config, get_current_profile},
     database::{
         models::{InstalledPackage, Package},
         packages::{FilterCondition, PackageQueryBuilder, ProvideStrategy},
     },
     error::{ErrorContext, SoarError},
-    utils::{calculate_checksum, desktop_dir, get_extract_dir, icons_dir, process_dir},
+    package::{
+        files::{collect_package_files, record_package_files, remove_recorded_links},
+        formats::{
+            appimage::{read_update_info, APPIMAGE_EXTRACT_DIR},
+            archive::unpack_package,
+            common::remove_integration_links,
+            get_file_type,
+            script::is_script,
+            PackageFormat,
+        },
+        provenance::record_provenance,
+        zsync::{delta_download, resolve_zsync_url},
+    },
+    utils::{calculate_checksum, get_extract_dir},
     SoarResult,
 };
 
-pub struct PackageInstaller {
+/// Downloads a package into a directory and validates it, without recording
+/// it as installed.
+pub struct PackageFetcher {
     package: Package,
     install_dir: PathBuf,
     progress_callback: Option<Arc<dyn Fn(DownloadState) + Send + Sync>>,
+    globs: Vec<String>,
+    /// Installed AppImage of another version, used for delta updates.
+    delta_seed: Option<PathBuf>,
+    /// Whether the direct download was an archive, unpacked into the install
+    /// directory.
+    unpacked: bool,
+}
+
+pub struct PackageInstaller {
+    fetcher: PackageFetcher,
     db: Arc<Mutex<Connection>>,
     with_pkg_id: bool,
-    globs: Vec<String>,
+    was_extracted: bool,
 }
 
 #[derive(Clone)]
@@ -42,61 +71,96 @@ pub struct InstallTarget {
     pub profile: Option<String>,
 }
 
-impl PackageInstaller {
-    pub async fn new<P: AsRef<Path>>(
-        target: &InstallTarget,
+impl PackageFetcher {
+    pub fn new<P: AsRef<Path>>(
+        package: &Package,
         install_dir: P,
         progress_callback: Option<Arc<dyn Fn(DownloadState) + Send + Sync>>,
-        db: Arc<Mutex<Connection>>,
-        with_pkg_id: bool,
         globs: Vec<String>,
     ) -> SoarResult<Self> {
-        let install_dir = install_dir.as_ref().to_path_buf();
-        let package = &target.package;
-        let profile = get_config().default_profile.clone();
-
-        if target.existing_install.is_none() {
-            let conn = db.lock()?;
-            let Package {
-                ref repo_name,
-                ref pkg,
-                ref pkg_id,
-                ref pkg_name,
-                ref pkg_type,
-                ref version,
-                ref ghcr_size,
-                ref size,
-                ..
-            } = package;
-            let installed_path = install_dir.to_string_lossy();
-            let size = ghcr_size.unwrap_or(size.unwrap_or(0));
-            let install_patterns = serde_json::to_string(&globs).unwrap();
-            let mut stmt = prepare_and_bind!(
-                conn,
-                "INSERT INTO packages (
-                    repo_name, pkg, pkg_id, pkg_name, pkg_type, version, size,
-                    installed_path, installed_date, with_pkg_id, profile, install_patterns
-                )
-                VALUES
-                (
-                    $repo_name, $pkg, $pkg_id, $pkg_name, $pkg_type, $version, $size,
-                    $installed_path, datetime(), $with_pkg_id, $profile, $install_patterns
-                )"
-            );
-            stmt.raw_execute()?;
+        if package.bsum.is_none() && get_config().require_checksum.unwrap_or(false) {
+            return Err(SoarError::MissingChecksum(format!(
+                "{}#{}",
+                package.pkg_name, package.pkg_id
+            )));
         }
 
         Ok(Self {
             package: package.clone(),
-            install_dir,
+            install_dir: install_dir.as_ref().to_path_buf(),
             progress_callback,
-            db: db.clone(),
-            with_pkg_id,
             globs,
+            delta_seed: None,
+            unpacked: false,
         })
     }
 
-    pub async fn download_package(&self) -> SoarResult<Option<String>> {
+    /// Builds the package from the installed AppImage of another version with
+    /// zsync, fetching only the changed blocks. Returns `None` to fall back to
+    /// a full download.
+    ///
+    /// The result can only be trusted to be the repository version if it has
+    /// a checksum, so packages without one are always downloaded in full.
+    async fn download_delta(&self, output_path: &Path) -> Option<String> {
+        let seed = self.delta_seed.as_ref()?;
+        let package = &self.package;
+        let bsum = package.bsum.as_ref()?;
+        let package_name = format!("{}#{}", package.pkg_name, package.pkg_id);
+
+        let update_info = read_update_info(seed).ok()??;
+        let zsync_url = match resolve_zsync_url(&update_info).await {
+            Ok(url) => url?,
+            Err(err) => {
+                debug!("{} - Failed to resolve update info: {}", package_name, err);
+                return None;
+            }
+        };
+
+        if let Err(err) = fs::create_dir_all(&self.install_dir) {
+            debug!(
+                "{} - Failed to create install directory: {}",
+                package_name, err
+            );
+            return None;
+        }
+        match delta_download(
+            &zsync_url,
+            seed,
+            output_path,
+            self.progress_callback.clone(),
+        )
+        .await
+        {
+            Ok(fetched) => debug!(
+                "{} - Delta update fetched {} of {} bytes",
+                package_name,
+                fetched,
+                fs::metadata(output_path).map_or(0, |m| m.len())
+            ),
+            Err(err) => {
+                debug!("{} - {}, downloading the full file", package_name, err);
+                fs::remove_file(output_path).ok();
+                return None;
+            }
+        }
+
+        // the update info points to the latest upstream release, which may
+        // not be the version in the repository
+        let checksum = calculate_checksum(output_path).ok()?;
+        if *bsum != checksum {
+            debug!(
+                "{} - Delta update doesn't match the repository version, downloading the full file",
+                package_name
+            );
+            fs::remove_file(output_path).ok();
+            return None;
+        }
+        fs::set_permissions(output_path, Permissions::from_mode(0o755)).ok()?;
+
+        Some(checksum)
+    }
+
+    pub async fn download_package(&mut self) -> SoarResult<Option<String>> {
         let package = &self.package;
         let output_path = self.install_dir.join(&package.pkg_name);
 
@@ -153,6 +217,10 @@ impl PackageInstaller {
 
             Ok(None)
         } else {
+            if let Some(checksum) = self.download_delta(output_path).await {
+                return Ok(Some(checksum));
+            }
+
             let downloader = Downloader::default();
             let extract_dir = get_extract_dir(&self.install_dir);
             let options = DownloadOptions {
@@ -174,6 +242,7 @@ impl PackageInstaller {
             };
 
             let extract_path = PathBuf::from(&extract_dir);
+            self.unpacked = extract_path.exists();
             if extract_path.exists() {
                 fs::remove_file(file_name).ok();
 
@@ -192,11 +261,208 @@ impl PackageInstaller {
 
                 fs::remove_dir_all(&extract_path).ok();
             }
+            self.unpacked |= unpack_package(&self.install_dir, &package.pkg_name)?;
+
+            // only ELF files are made executable on download
+            let bin_path = self.install_dir.join(&package.pkg_name);
+            if is_script(&bin_path) {
+                fs::set_permissions(&bin_path, Permissions::from_mode(0o755))
+                    .with_context(|| format!("setting permissions of {}", bin_path.display()))?;
+            }
 
             Ok(checksum)
         }
     }
 
+    /// Locates the file the package checksum belongs to. GHCR packages are
+    /// checksummed by their main binary, which is the file named after the
+    /// package, or the only file of single-file packages. Direct downloads are
+    /// checksummed as downloaded, so there's none if they were unpacked.
+    pub fn checksum_file(&self) -> Option<PathBuf> {
+        let package = &self.package;
+        let Some(ref ghcr_files) = package.ghcr_files else {
+            return (!self.unpacked).then(|| self.install_dir.join(&package.pkg_name));
+        };
+
+        let names = [Some(&package.pkg_name), package.pkg.as_ref()];
+        let main_file = ghcr_files
+            .iter()
+            .find(|file| {
+                let file_name = Path::new(file).file_name().and_then(|name| name.to_str());
+                names
+                    .iter()
+                    .flatten()
+                    .any(|name| file_name == Some(name.as_str()))
+            })
+            .or_else(|| {
+                let mut files = ghcr_files.iter().filter(|file| {
+                    !file.ends_with(".sig") && !matches!(file.as_str(), "CHECKSUM" | "LICENSE")
+                });
+                files.next().filter(|_| files.next().is_none())
+            })?;
+
+        Some(self.install_dir.join(main_file))
+    }
+
+    /// Validates the package against its blake3 checksum. For direct downloads,
+    /// the checksum of the downloaded file is compared before extraction.
+    ///
+    /// Returns `false` if the package couldn't be validated, as it has no
+    /// checksum or the checksummed file was excluded by install patterns. If
+    /// checksums are required, this is an error instead.
+    pub fn validate_checksum(&self, downloaded_checksum: Option<String>) -> SoarResult<bool> {
+        let package = &self.package;
+        let package_name = format!("{}#{}", package.pkg_name, package.pkg_id);
+        let skip_validation = || {
+            if get_config().require_checksum.unwrap_or(false) {
+                Err(SoarError::MissingChecksum(package_name.clone()))
+            } else {
+                Ok(false)
+            }
+        };
+
+        let Some(ref expected) = package.bsum else {
+            return skip_validation();
+        };
+
+        let (path, actual) = if package.ghcr_pkg.is_some() {
+            match self.checksum_file().filter(|path| path.is_file()) {
+                Some(path) => {
+                    let checksum = calculate_checksum(&path)?;
+                    (path, checksum)
+                }
+                None => return skip_validation(),
+            }
+        } else {
+            match downloaded_checksum {
+                Some(checksum) => (self.install_dir.join(&package.pkg_name), checksum),
+                None => return skip_validation(),
+            }
+        };
+
+        if actual != *expected {
+            return Err(SoarError::ChecksumMismatch {
+                package: package_name,
+                path: path.display().to_string(),
+                expected: expected.clone(),
+                actual,
+            });
+        }
+
+        Ok(true)
+    }
+}
+
+/// Returns the installed versions of the package other than the one being
+/// installed.
+fn other_installed_versions(
+    db: &Arc<Mutex<Connection>>,
+    package: &Package,
+) -> SoarResult<Vec<InstalledPackage>> {
+    Ok(PackageQueryBuilder::new(db.clone())
+        .where_and("repo_name", FilterCondition::Eq(package.repo_name.clone()))
+        .where_and("pkg_id", FilterCondition::Eq(package.pkg_id.clone()))
+        .where_and("pkg_name", FilterCondition::Eq(package.pkg_name.clone()))
+        .where_and("is_installed", FilterCondition::Eq("1".to_string()))
+        .where_and("version", FilterCondition::Ne(package.version.clone()))
+        .load_installed()?
+        .items)
+}
+
+/// Finds the installed AppImage of another version of the package, to update
+/// from.
+fn find_delta_seed(installed: &[InstalledPackage]) -> Option<PathBuf> {
+    for installed in installed {
+        let path = PathBuf::from(&installed.installed_path).join(&installed.pkg_name);
+        let Ok(file) = fs::File::open(&path) else {
+            continue;
+        };
+        if matches!(
+            get_file_type(&mut BufReader::new(file)),
+            Ok(PackageFormat::AppImage)
+        ) {
+            return Some(path);
+        }
+    }
+    None
+}
+
+impl PackageInstaller {
+    pub async fn new<P: AsRef<Path>>(
+        target: &InstallTarget,
+        install_dir: P,
+        progress_callback: Option<Arc<dyn Fn(DownloadState) + Send + Sync>>,
+        db: Arc<Mutex<Connection>>,
+        with_pkg_id: bool,
+        globs: Vec<String>,
+    ) -> SoarResult<Self> {
+        let package = &target.package;
+        let profile = target.profile.clone().unwrap_or_else(get_current_profile);
+        let mut fetcher = PackageFetcher::new(package, install_dir, progress_callback, globs)?;
+        let other_versions = other_installed_versions(&db, package)?;
+        fetcher.delta_seed = find_delta_seed(&other_versions);
+        let was_extracted = other_versions.iter().any(|installed| {
+            Path::new(&installed.installed_path)
+                .join(APPIMAGE_EXTRACT_DIR)
+                .is_dir()
+        });
+        let install_dir = &fetcher.install_dir;
+        let globs = &fetcher.globs;
+
+        if target.existing_install.is_none() {
+            let conn = db.lock()?;
+            let Package {
+                ref repo_name,
+                ref pkg,
+                ref pkg_id,
+                ref pkg_name,
+                ref pkg_type,
+                ref version,
+                ref ghcr_size,
+                ref size,
+                ..
+            } = package;
+            let installed_path = install_dir.to_string_lossy();
+            let size = ghcr_size.unwrap_or(size.unwrap_or(0));
+            let install_patterns = serde_json::to_string(&globs).unwrap();
+            let mut stmt = prepare_and_bind!(
+                conn,
+                "INSERT INTO packages (
+                    repo_name, pkg, pkg_id, pkg_name, pkg_type, version, size,
+                    installed_path, installed_date, with_pkg_id, profile, install_patterns
+                )
+                VALUES
+                (
+                    $repo_name, $pkg, $pkg_id, $pkg_name, $pkg_type, $version, $size,
+                    $installed_path, datetime(), $with_pkg_id, $profile, $install_patterns
+                )"
+            );
+            stmt.raw_execute()?;
+        }
+
+        Ok(Self {
+            fetcher,
+            db: db.clone(),
+            with_pkg_id,
+            was_extracted,
+        })
+    }
+
+    /// Whether another installed version of the package is an AppImage
+    /// extracted to run without FUSE.
+    pub fn was_extracted(&self) -> bool {
+        self.was_extracted
+    }
+
+    pub async fn download_package(&mut self) -> SoarResult<Option<String>> {
+        self.fetcher.download_package().await
+    }
+
+    pub fn validate_checksum(&self, downloaded_checksum: Option<String>) -> SoarResult<bool> {
+        self.fetcher.validate_checksum(downloaded_checksum)
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub async fn record(
         &self,
         unlinked: bool,
@@ -204,9 +470,11 @@ impl PackageInstaller {
         portable_home: Option<&str>,
         portable_config: Option<&str>,
         portable_share: Option<&str>,
+        created_paths: &[PathBuf],
+        pubkey_fingerprint: Option<&str>,
     ) -> SoarResult<()> {
         let mut conn = self.db.lock()?;
-        let package = &self.package;
+        let package = &self.fetcher.package;
         let Package {
             repo_name,
             pkg_name,
@@ -260,6 +528,21 @@ impl PackageInstaller {
             |row| row.get(0),
         )?;
 
+        let files = collect_package_files(&self.fetcher.install_dir, created_paths)?;
+        record_package_files(&tx, record_id as u64, &files)?;
+        let checksum_file = bsum
+            .as_ref()
+            .and_then(|_| self.fetcher.checksum_file())
+            .filter(|path| path.is_file());
+        record_provenance(
+            &tx,
+            record_id as u64,
+            package,
+            &self.fetcher.install_dir,
+            pubkey_fingerprint,
+            checksum_file.as_deref(),
+        )?;
+
         if portable.is_some() || portable_home.is_some() || portable_config.is_some() {
             let base_dir = env::current_dir()
                 .map_err(|_| SoarError::Custom("Error retrieving current directory".into()))?;
@@ -375,31 +658,11 @@ impl PackageInstaller {
                 .items;
 
             for package in alternate_packages {
-                let installed_path = PathBuf::from(&package.installed_path);
-
-                let mut remove_action = |path: &Path| -> SoarResult<()> {
-                    if let Ok(real_path) = fs::read_link(path) {
-                        if real_path.parent() == Some(&installed_path) {
-                            fs::remove_file(path).with_context(|| {
-                                format!("removing desktop file {}", path.display())
-                            })?;
-                        }
-                    }
-                    Ok(())
-                };
-                process_dir(desktop_dir(), &mut remove_action)?;
-
-                let mut remove_action = |path: &Path| -> SoarResult<()> {
-                    if let Ok(real_path) = fs::read_link(path) {
-                        if real_path.parent() == Some(&installed_path) {
-                            fs::remove_file(path).with_context(|| {
-                                format!("removing icon file {}", path.display())
-                            })?;
-                        }
-                    }
-                    Ok(())
-                };
-                process_dir(icons_dir(), &mut remove_action)?;
+                let conn = self.db.lock()?;
+                if !remove_recorded_links(&conn, package.id, &package.installed_path)? {
+                    remove_integration_links(&package.installed_path)?;
+                }
+                drop(conn);
 
                 if let Some(provides) = package.provides {
                     for provide in provides {
@@ -410,7 +673,9 @@ impl PackageInstaller {
                                     | Some(ProvideStrategy::KeepBoth)
                             );
                             if is_symlink {
-                                let target_name = get_config().get_bin_path()?.join(target);
+                                let target_name = get_config()
+                                    .get_profile_bin_path(&package.profile)?
+                                    .join(target);
                                 if target_name.is_symlink() || target_name.is_file() {
                                     std::fs::remove_file(&target_name).with_context(|| {
                                         format!("removing provide {}", target_name.display())
@@ -1,4 +1,7 @@
+pub mod files;
 pub mod formats;
 pub mod install;
+pub mod provenance;
 pub mod query;
 pub mod remove;
+pub mod zsync;
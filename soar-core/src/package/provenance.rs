@@ -0,0 +1,145 @@
+use std::{fs, path::Path};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use rusqlite::{params, Connection};
+
+use crate::{
+    database::models::{FromRow, Package, PackageProvenance, PackageSignature},
+    error::ErrorContext,
+    SoarResult,
+};
+
+/// Returns the key id of a minisign public key, as displayed by minisign.
+fn key_id(pubkey: &str) -> Option<String> {
+    // the key is the last line, the first line may be an untrusted comment
+    let bytes = STANDARD.decode(pubkey.lines().last()?.trim()).ok()?;
+
+    // 2 bytes signature algorithm, followed by the 8 bytes little-endian key id
+    let key_id = bytes.get(2..10)?;
+    Some(key_id.iter().rev().map(|b| format!("{:02X}", b)).collect())
+}
+
+/// Returns the key id of the repository's minisign public key.
+pub fn pubkey_fingerprint<P: AsRef<Path>>(repository_path: P) -> Option<String> {
+    let pubkey = fs::read_to_string(repository_path.as_ref().join("minisign.pub")).ok()?;
+    key_id(&pubkey)
+}
+
+/// Collects the signatures retained in the install directory.
+pub fn collect_signatures<P: AsRef<Path>>(install_dir: P) -> SoarResult<Vec<PackageSignature>> {
+    let install_dir = install_dir.as_ref();
+    let mut signatures = Vec::new();
+
+    if !install_dir.is_dir() {
+        return Ok(signatures);
+    }
+
+    for entry in fs::read_dir(install_dir)
+        .with_context(|| format!("reading directory {}", install_dir.display()))?
+    {
+        let path = entry
+            .with_context(|| format!("reading entry from directory {}", install_dir.display()))?
+            .path();
+        if !path.is_file() || path.extension().is_none_or(|ext| ext != "sig") {
+            continue;
+        }
+
+        let signature = fs::read_to_string(&path)
+            .with_context(|| format!("reading signature file {}", path.display()))?;
+        signatures.push(PackageSignature {
+            file: path.with_extension("").to_string_lossy().into_owned(),
+            signature,
+        });
+    }
+
+    signatures.sort_by(|a, b| a.file.cmp(&b.file));
+    Ok(signatures)
+}
+
+/// Replaces the provenance record of the installed package. The signatures
+/// are only considered verified if a public key fingerprint is given.
+pub fn record_provenance<P: AsRef<Path>>(
+    conn: &Connection,
+    package_id: u64,
+    package: &Package,
+    install_dir: P,
+    pubkey_fingerprint: Option<&str>,
+    checksum_file: Option<&Path>,
+) -> SoarResult<()> {
+    let signatures = collect_signatures(install_dir)?;
+    let signature_verified = pubkey_fingerprint.is_some() && !signatures.is_empty();
+    let signatures = serde_json::to_string(&signatures).unwrap();
+
+    let download_url = package.ghcr_pkg.as_ref().unwrap_or(&package.download_url);
+    let ghcr_digest = package
+        .ghcr_blob
+        .as_ref()
+        .map(|blob| blob.rsplit_once('@').map_or(blob.as_str(), |(_, d)| d));
+
+    conn.execute(
+        "DELETE FROM package_provenance WHERE package_id = ?",
+        params![package_id],
+    )?;
+    conn.execute(
+        "INSERT INTO package_provenance (
+            package_id, repo_name, download_url, ghcr_digest, build_id,
+            build_action, pubkey_fingerprint, signature_verified, signatures,
+            recorded_date, checksum_file
+        )
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, jsonb(?), datetime(), ?)",
+        params![
+            package_id,
+            package.repo_name,
+            download_url,
+            ghcr_digest,
+            package.build_id,
+            package.build_action,
+            pubkey_fingerprint,
+            signature_verified,
+            signatures,
+            checksum_file.map(|path| path.to_string_lossy())
+        ],
+    )?;
+
+    Ok(())
+}
+
+pub fn get_provenance(conn: &Connection, package_id: u64) -> SoarResult<Option<PackageProvenance>> {
+    let mut stmt = conn.prepare(
+        "SELECT
+            package_id, repo_name, download_url, ghcr_digest, build_id,
+            build_action, pubkey_fingerprint, signature_verified,
+            json(signatures) AS signatures, recorded_date, checksum_file
+        FROM package_provenance
+        WHERE package_id = ?",
+    )?;
+    let mut rows = stmt.query_map(params![package_id], PackageProvenance::from_row)?;
+    Ok(rows.next().transpose()?)
+}
+
+pub fn delete_provenance(conn: &Connection, package_id: u64) -> SoarResult<()> {
+    conn.execute(
+        "DELETE FROM package_provenance WHERE package_id = ?",
+        params![package_id],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::key_id;
+
+    #[test]
+    fn key_id_of_public_key() {
+        let pubkey = "untrusted comment: minisign public key E7620F1842B4E81F\n\
+            RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3\n";
+        assert_eq!(key_id(pubkey).as_deref(), Some("E7620F1842B4E81F"));
+    }
+
+    #[test]
+    fn key_id_of_invalid_key() {
+        assert_eq!(key_id("RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucN$Ch1CBM0Q"), None);
+        assert_eq!(key_id("RWQf6LRC"), None);
+        assert_eq!(key_id(""), None);
+    }
+}
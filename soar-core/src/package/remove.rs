@@ -1,7 +1,5 @@
 use std::{
-    ffi::OsString,
     fs,
-    path::{Path, PathBuf},
     sync::{Arc, Mutex},
 };
 
@@ -11,7 +9,12 @@ use crate::{
     config::get_config,
     database::{models::InstalledPackage, packages::ProvideStrategy},
     error::ErrorContext,
-    utils::{desktop_dir, icons_dir, process_dir},
+    package::{
+        files::{delete_package_files, remove_recorded_links},
+        formats::common::remove_integration_links,
+        provenance::delete_provenance,
+    },
+    xdg::refresh_desktop_integration,
     SoarResult,
 };
 
@@ -30,57 +33,12 @@ impl PackageRemover {
         let tx = conn.transaction()?;
 
         // to prevent accidentally removing required files by other package,
-        // remove only if the installation was successful
-        if self.package.is_installed {
-            let bin_path = get_config().get_bin_path()?;
-            let def_bin = bin_path.join(&self.package.pkg_name);
-            if def_bin.is_symlink() && def_bin.is_file() {
-                fs::remove_file(&def_bin)
-                    .with_context(|| format!("removing binary {}", def_bin.display()))?;
-            }
-
-            if let Some(provides) = &self.package.provides {
-                for provide in provides {
-                    if let Some(ref target) = provide.target {
-                        let is_symlink = matches!(
-                            provide.strategy,
-                            Some(ProvideStrategy::KeepTargetOnly) | Some(ProvideStrategy::KeepBoth)
-                        );
-                        if is_symlink {
-                            let target_name = bin_path.join(target);
-                            if target_name.exists() {
-                                std::fs::remove_file(&target_name).with_context(|| {
-                                    format!("removing provide {}", target_name.display())
-                                })?;
-                            }
-                        }
-                    }
-                }
-            }
-
-            let installed_path = PathBuf::from(&self.package.installed_path);
-
-            let mut remove_action = |path: &Path| -> SoarResult<()> {
-                if path.extension() == Some(&OsString::from("desktop")) {
-                    if let Ok(real_path) = fs::read_link(path) {
-                        if real_path.parent() == Some(&installed_path) {
-                            let _ = fs::remove_file(path);
-                        }
-                    }
-                }
-                Ok(())
-            };
-            process_dir(desktop_dir(), &mut remove_action)?;
-
-            let mut remove_action = |path: &Path| -> SoarResult<()> {
-                if let Ok(real_path) = fs::read_link(path) {
-                    if real_path.parent() == Some(&installed_path) {
-                        let _ = fs::remove_file(path);
-                    }
-                }
-                Ok(())
-            };
-            process_dir(icons_dir(), &mut remove_action)?;
+        // remove only if the installation was successful. Packages installed
+        // before files were recorded fall back to scanning for their links.
+        if self.package.is_installed
+            && !remove_recorded_links(&tx, self.package.id, &self.package.installed_path)?
+        {
+            self.remove_unrecorded_links()?;
         }
 
         if let Err(err) = fs::remove_dir_all(&self.package.installed_path) {
@@ -106,10 +64,62 @@ impl PackageRemover {
             "#,
             )?;
             stmt.execute(params![self.package.id])?;
+
+            // the profile is kept for the remaining installs, e.g. after update
+            let mut stmt = tx.prepare(
+                r#"
+                DELETE FROM sandbox_profiles
+                WHERE
+                    repo_name = ?1 AND pkg_id = ?2 AND pkg_name = ?3
+                    AND NOT EXISTS (
+                        SELECT 1 FROM packages
+                        WHERE repo_name = ?1 AND pkg_id = ?2 AND pkg_name = ?3
+                    )
+            "#,
+            )?;
+            stmt.execute(params![
+                self.package.repo_name,
+                self.package.pkg_id,
+                self.package.pkg_name
+            ])?;
         }
+        delete_package_files(&tx, self.package.id)?;
+        delete_provenance(&tx, self.package.id)?;
 
         tx.commit()?;
 
+        refresh_desktop_integration();
+
         Ok(())
     }
+
+    fn remove_unrecorded_links(&self) -> SoarResult<()> {
+        let bin_path = get_config().get_profile_bin_path(&self.package.profile)?;
+        let def_bin = bin_path.join(&self.package.pkg_name);
+        if def_bin.is_symlink() && def_bin.is_file() {
+            fs::remove_file(&def_bin)
+                .with_context(|| format!("removing binary {}", def_bin.display()))?;
+        }
+
+        if let Some(provides) = &self.package.provides {
+            for provide in provides {
+                if let Some(ref target) = provide.target {
+                    let is_symlink = matches!(
+                        provide.strategy,
+                        Some(ProvideStrategy::KeepTargetOnly) | Some(ProvideStrategy::KeepBoth)
+                    );
+                    if is_symlink {
+                        let target_name = bin_path.join(target);
+                        if target_name.exists() {
+                            std::fs::remove_file(&target_name).with_context(|| {
+                                format!("removing provide {}", target_name.display())
+                            })?;
+                        }
+                    }
+                }
+            }
+        }
+
+        remove_integration_links(&self.package.installed_path)
+    }
 }
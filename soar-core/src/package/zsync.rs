@@ -0,0 +1,755 @@
+//! Delta updates of AppImages with zsync. The blocks of the new file found in
+//! the installed file are copied, and only the remaining ranges are fetched.
+
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::Path,
+    sync::Arc,
+};
+
+use memmap2::Mmap;
+use reqwest::{
+    header::{ACCEPT, RANGE},
+    StatusCode, Url,
+};
+use sha1::{Digest, Sha1};
+use soar_dl::{
+    downloader::{DownloadState, Downloader},
+    github::{Github, GithubRelease},
+    platform::{Release, ReleaseAsset, ReleaseHandler},
+    utils::matches_pattern,
+};
+
+use crate::{
+    error::{ErrorContext, SoarError},
+    SoarResult,
+};
+
+/// Missing blocks closer than this are fetched in a single request.
+const MAX_RANGE_GAP_BLOCKS: usize = 16;
+
+fn delta_error(message: impl Into<String>) -> SoarError {
+    SoarError::DeltaUpdate(message.into())
+}
+
+/// Resolves the URL of the zsync control file from the update information of
+/// an AppImage. Only `zsync` and `gh-releases-zsync` are supported.
+pub async fn resolve_zsync_url(update_info: &str) -> SoarResult<Option<String>> {
+    let parts: Vec<&str> = update_info.split('|').collect();
+    match parts[..] {
+        ["zsync", url] => Ok(Some(url.to_string())),
+        ["gh-releases-zsync", owner, repo, tag, pattern] => {
+            let tag = (tag != "latest").then_some(tag);
+            let handler = ReleaseHandler::<Github>::new();
+            let releases: Vec<GithubRelease> = handler
+                .fetch_releases(&format!("{owner}/{repo}"), tag)
+                .await?;
+            let release = releases
+                .iter()
+                .find(|release| tag.is_some() || !release.is_prerelease())
+                .or(releases.first());
+
+            let globs = [pattern.to_string()];
+            Ok(release.and_then(|release| {
+                release
+                    .assets()
+                    .into_iter()
+                    .find(|asset| matches_pattern(asset.name(), &[], &globs, &[], &[], true))
+                    .map(|asset| asset.download_url().to_string())
+            }))
+        }
+        _ => Ok(None),
+    }
+}
+
+struct BlockSum {
+    rsum: u32,
+    checksum: Vec<u8>,
+}
+
+/// The header and block checksums of a zsync control file.
+struct ControlFile {
+    block_size: usize,
+    length: u64,
+    seq_matches: usize,
+    rsum_bytes: usize,
+    checksum_bytes: usize,
+    url: String,
+    sha1: String,
+    blocks: Vec<BlockSum>,
+}
+
+impl ControlFile {
+    fn parse(data: &[u8], base_url: &str) -> SoarResult<Self> {
+        let header_end = data
+            .windows(2)
+            .position(|window| window == b"\n\n")
+            .ok_or_else(|| delta_error("control file has no header"))?;
+        let header = String::from_utf8_lossy(&data[..header_end]);
+
+        let mut fields = HashMap::new();
+        for line in header.lines() {
+            if let Some((key, value)) = line.split_once(':') {
+                fields
+                    .entry(key.trim().to_string())
+                    .or_insert_with(|| value.trim().to_string());
+            }
+        }
+        let field = |key: &str| {
+            fields
+                .get(key)
+                .ok_or_else(|| delta_error(format!("control file has no {key} field")))
+        };
+        let number = |key: &str| -> SoarResult<u64> {
+            field(key)?
+                .parse()
+                .map_err(|_| delta_error(format!("invalid {key} field in control file")))
+        };
+
+        let block_size = number("Blocksize")? as usize;
+        let length = number("Length")?;
+        let hash_lengths: Vec<usize> = field("Hash-Lengths")?
+            .split(',')
+            .filter_map(|len| len.trim().parse().ok())
+            .collect();
+        let [seq_matches, rsum_bytes, checksum_bytes] = hash_lengths[..] else {
+            return Err(delta_error("invalid Hash-Lengths field in control file"));
+        };
+        if block_size == 0
+            || !(1..=2).contains(&seq_matches)
+            || !(1..=4).contains(&rsum_bytes)
+            || !(3..=16).contains(&checksum_bytes)
+        {
+            return Err(delta_error("unsupported block parameters in control file"));
+        }
+
+        // compressed targets (Z-URL) aren't supported
+        let target = field("URL")?;
+        let url = Url::parse(base_url)
+            .and_then(|base| base.join(target))
+            .map_err(|err| delta_error(format!("invalid target URL {target}: {err}")))?
+            .to_string();
+        let sha1 = field("SHA-1")?.to_lowercase();
+
+        let block_count = length.div_ceil(block_size as u64) as usize;
+        let entry_size = rsum_bytes + checksum_bytes;
+        let checksums = &data[header_end + 2..];
+        if checksums.len() < block_count * entry_size {
+            return Err(delta_error("control file has missing block checksums"));
+        }
+
+        let blocks = checksums
+            .chunks_exact(entry_size)
+            .take(block_count)
+            .map(|entry| BlockSum {
+                rsum: entry[..rsum_bytes]
+                    .iter()
+                    .fold(0, |rsum, &b| (rsum << 8) | b as u32),
+                checksum: entry[rsum_bytes..].to_vec(),
+            })
+            .collect();
+
+        Ok(Self {
+            block_size,
+            length,
+            seq_matches,
+            rsum_bytes,
+            checksum_bytes,
+            url,
+            sha1,
+            blocks,
+        })
+    }
+
+    /// Mask of the rsum bytes kept in the control file, which are the
+    /// trailing ones.
+    fn rsum_mask(&self) -> u32 {
+        if self.rsum_bytes == 4 {
+            u32::MAX
+        } else {
+            (1 << (self.rsum_bytes * 8)) - 1
+        }
+    }
+}
+
+/// The rolling checksum of zsync, the same as the one of rsync.
+#[derive(Clone, Copy)]
+struct Rsum {
+    a: u16,
+    b: u16,
+}
+
+impl Rsum {
+    fn new(block: &[u8]) -> Self {
+        let len = block.len();
+        let mut rsum = Self { a: 0, b: 0 };
+        for (idx, &byte) in block.iter().enumerate() {
+            rsum.a = rsum.a.wrapping_add(byte as u16);
+            rsum.b = rsum
+                .b
+                .wrapping_add(((len - idx) as u16).wrapping_mul(byte as u16));
+        }
+        rsum
+    }
+
+    fn roll(&mut self, old: u8, new: u8, block_size: usize) {
+        self.a = self.a.wrapping_sub(old as u16).wrapping_add(new as u16);
+        self.b = self
+            .b
+            .wrapping_sub((block_size as u16).wrapping_mul(old as u16))
+            .wrapping_add(self.a);
+    }
+
+    fn value(&self) -> u32 {
+        ((self.a as u32) << 16) | self.b as u32
+    }
+}
+
+/// MD4 digest, used by zsync for the strong block checksums.
+fn md4(data: &[u8]) -> [u8; 16] {
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64).wrapping_mul(8)).to_le_bytes());
+
+    let f = |x: u32, y: u32, z: u32| (x & y) | (!x & z);
+    let g = |x: u32, y: u32, z: u32| (x & y) | (x & z) | (y & z);
+    let h = |x: u32, y: u32, z: u32| x ^ y ^ z;
+
+    let mut state: [u32; 4] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476];
+    for chunk in message.chunks_exact(64) {
+        let mut x = [0u32; 16];
+        for (idx, word) in chunk.chunks_exact(4).enumerate() {
+            x[idx] = u32::from_le_bytes(word.try_into().unwrap());
+        }
+        let [mut a, mut b, mut c, mut d] = state;
+
+        for i in [0, 4, 8, 12] {
+            a = a.wrapping_add(f(b, c, d)).wrapping_add(x[i]).rotate_left(3);
+            d = d
+                .wrapping_add(f(a, b, c))
+                .wrapping_add(x[i + 1])
+                .rotate_left(7);
+            c = c
+                .wrapping_add(f(d, a, b))
+                .wrapping_add(x[i + 2])
+                .rotate_left(11);
+            b = b
+                .wrapping_add(f(c, d, a))
+                .wrapping_add(x[i + 3])
+                .rotate_left(19);
+        }
+        for i in 0..4 {
+            let k = 0x5a827999u32;
+            a = a
+                .wrapping_add(g(b, c, d))
+                .wrapping_add(x[i])
+                .wrapping_add(k)
+                .rotate_left(3);
+            d = d
+                .wrapping_add(g(a, b, c))
+                .wrapping_add(x[i + 4])
+                .wrapping_add(k)
+                .rotate_left(5);
+            c = c
+                .wrapping_add(g(d, a, b))
+                .wrapping_add(x[i + 8])
+                .wrapping_add(k)
+                .rotate_left(9);
+            b = b
+                .wrapping_add(g(c, d, a))
+                .wrapping_add(x[i + 12])
+                .wrapping_add(k)
+                .rotate_left(13);
+        }
+        for i in [0, 2, 1, 3] {
+            let k = 0x6ed9eba1u32;
+            a = a
+                .wrapping_add(h(b, c, d))
+                .wrapping_add(x[i])
+                .wrapping_add(k)
+                .rotate_left(3);
+            d = d
+                .wrapping_add(h(a, b, c))
+                .wrapping_add(x[i + 8])
+                .wrapping_add(k)
+                .rotate_left(9);
+            c = c
+                .wrapping_add(h(d, a, b))
+                .wrapping_add(x[i + 4])
+                .wrapping_add(k)
+                .rotate_left(11);
+            b = b
+                .wrapping_add(h(c, d, a))
+                .wrapping_add(x[i + 12])
+                .wrapping_add(k)
+                .rotate_left(15);
+        }
+
+        state[0] = state[0].wrapping_add(a);
+        state[1] = state[1].wrapping_add(b);
+        state[2] = state[2].wrapping_add(c);
+        state[3] = state[3].wrapping_add(d);
+    }
+
+    let mut digest = [0u8; 16];
+    for (idx, word) in state.iter().enumerate() {
+        digest[idx * 4..idx * 4 + 4].copy_from_slice(&word.to_le_bytes());
+    }
+    digest
+}
+
+/// Finds the blocks of the target in the seed file. Returns the offset in the
+/// seed of each block, if found.
+fn match_blocks(control: &ControlFile, seed: &[u8]) -> Vec<Option<usize>> {
+    let block_size = control.block_size;
+    let block_count = control.blocks.len();
+    let mask = control.rsum_mask();
+    let checksum_bytes = control.checksum_bytes;
+
+    let mut found = vec![None; block_count];
+    let mut lookup: HashMap<u32, Vec<usize>> = HashMap::new();
+    for (idx, block) in control.blocks.iter().enumerate() {
+        lookup.entry(block.rsum).or_default().push(idx);
+    }
+
+    let block_matches =
+        |idx: usize, data: &[u8]| md4(data)[..checksum_bytes] == control.blocks[idx].checksum[..];
+
+    if seed.len() < block_size {
+        return found;
+    }
+
+    let mut pos = 0;
+    let mut rsum = Rsum::new(&seed[..block_size]);
+    loop {
+        let mut matched = false;
+        if let Some(candidates) = lookup.get(&(rsum.value() & mask)) {
+            let block = &seed[pos..pos + block_size];
+            let next_block = seed.get(pos + block_size..pos + 2 * block_size);
+            let next_rsum = next_block.map(|next| Rsum::new(next).value() & mask);
+
+            for &idx in candidates {
+                if found[idx].is_some() {
+                    continue;
+                }
+                // with sequential matches, the following block must match too,
+                // unless the block continues the match of the previous one
+                let continues =
+                    idx > 0 && pos >= block_size && found[idx - 1] == Some(pos - block_size);
+                if control.seq_matches > 1 && idx + 1 < block_count && !continues {
+                    let (Some(next_block), Some(next_rsum)) = (next_block, next_rsum) else {
+                        continue;
+                    };
+                    if next_rsum != control.blocks[idx + 1].rsum
+                        || !block_matches(idx + 1, next_block)
+                    {
+                        continue;
+                    }
+                }
+                if !block_matches(idx, block) {
+                    continue;
+                }
+                found[idx] = Some(pos);
+                matched = true;
+            }
+        }
+
+        if matched {
+            pos += block_size;
+            if pos + block_size > seed.len() {
+                break;
+            }
+            rsum = Rsum::new(&seed[pos..pos + block_size]);
+        } else {
+            if pos + block_size >= seed.len() {
+                break;
+            }
+            rsum.roll(seed[pos], seed[pos + block_size], block_size);
+            pos += 1;
+        }
+    }
+
+    found
+}
+
+/// Returns the byte ranges of the target to fetch, merging ranges separated
+/// by a few found blocks to reduce the number of requests.
+fn missing_ranges(control: &ControlFile, found: &[Option<usize>]) -> Vec<(u64, u64)> {
+    let block_size = control.block_size as u64;
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+
+    for (idx, offset) in found.iter().enumerate() {
+        if offset.is_some() {
+            continue;
+        }
+        match ranges.last_mut() {
+            Some((_, end)) if idx - *end <= MAX_RANGE_GAP_BLOCKS => *end = idx + 1,
+            _ => ranges.push((idx, idx + 1)),
+        }
+    }
+
+    ranges
+        .into_iter()
+        .map(|(start, end)| {
+            (
+                start as u64 * block_size,
+                (end as u64 * block_size).min(control.length),
+            )
+        })
+        .collect()
+}
+
+fn file_sha1(path: &Path) -> SoarResult<String> {
+    let mut file = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+    let mut hasher = Sha1::new();
+    let mut buffer = vec![0u8; 1 << 16];
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .with_context(|| format!("reading {}", path.display()))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+/// Builds the target of the zsync control file at `output_path`, reusing the
+/// blocks of the seed file. Returns the number of bytes fetched.
+pub async fn delta_download(
+    zsync_url: &str,
+    seed_path: &Path,
+    output_path: &Path,
+    progress_callback: Option<Arc<dyn Fn(DownloadState) + Send + Sync>>,
+) -> SoarResult<u64> {
+    let downloader = Downloader::default();
+    let client = downloader.client();
+
+    let response = client.get(zsync_url).send().await?;
+    if !response.status().is_success() {
+        return Err(delta_error(format!(
+            "fetching {} returned {}",
+            zsync_url,
+            response.status()
+        )));
+    }
+    let control = ControlFile::parse(&response.bytes().await?, zsync_url)?;
+
+    let seed_file =
+        File::open(seed_path).with_context(|| format!("opening {}", seed_path.display()))?;
+    // SAFETY: the seed is the installed file, which isn't modified meanwhile
+    let seed = unsafe { Mmap::map(&seed_file) }
+        .with_context(|| format!("mapping {} into memory", seed_path.display()))?;
+    let found = match_blocks(&control, &seed);
+
+    let mut output = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open(output_path)
+        .with_context(|| format!("creating {}", output_path.display()))?;
+    output
+        .set_len(control.length)
+        .with_context(|| format!("resizing {}", output_path.display()))?;
+
+    let block_size = control.block_size as u64;
+    for (idx, offset) in found.iter().enumerate() {
+        let Some(offset) = offset else {
+            continue;
+        };
+        let start = idx as u64 * block_size;
+        let len = block_size.min(control.length - start) as usize;
+        output
+            .seek(SeekFrom::Start(start))
+            .and_then(|_| output.write_all(&seed[*offset..*offset + len]))
+            .with_context(|| format!("writing to {}", output_path.display()))?;
+    }
+
+    let ranges = missing_ranges(&control, &found);
+    let total: u64 = ranges.iter().map(|(start, end)| end - start).sum();
+    if let Some(ref callback) = progress_callback {
+        callback(DownloadState::Preparing(total));
+    }
+
+    let mut fetched = 0;
+    for (start, end) in ranges {
+        let mut response = client
+            .get(&control.url)
+            .header(RANGE, format!("bytes={}-{}", start, end - 1))
+            .header(ACCEPT, "*/*")
+            .send()
+            .await?;
+        if response.status() != StatusCode::PARTIAL_CONTENT {
+            return Err(delta_error(format!(
+                "range request to {} returned {}",
+                control.url,
+                response.status()
+            )));
+        }
+
+        output
+            .seek(SeekFrom::Start(start))
+            .with_context(|| format!("seeking in {}", output_path.display()))?;
+        let mut received = 0;
+        while let Some(chunk) = response.chunk().await? {
+            received += chunk.len() as u64;
+            if received > end - start {
+                return Err(delta_error("received more data than requested"));
+            }
+            output
+                .write_all(&chunk)
+                .with_context(|| format!("writing to {}", output_path.display()))?;
+            fetched += chunk.len() as u64;
+            if let Some(ref callback) = progress_callback {
+                callback(DownloadState::Progress(fetched));
+            }
+        }
+        if received != end - start {
+            return Err(delta_error("range response ended early"));
+        }
+    }
+    drop(output);
+
+    if file_sha1(output_path)? != control.sha1 {
+        return Err(delta_error("SHA-1 of the result doesn't match"));
+    }
+    if let Some(ref callback) = progress_callback {
+        callback(DownloadState::Complete);
+    }
+
+    Ok(fetched)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{match_blocks, md4, missing_ranges, BlockSum, ControlFile, Rsum};
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    fn random_bytes(len: usize, seed: u32) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                (state >> 16) as u8
+            })
+            .collect()
+    }
+
+    fn control_for(target: &[u8], block_size: usize, seq_matches: usize) -> ControlFile {
+        let mut control = ControlFile {
+            block_size,
+            length: target.len() as u64,
+            seq_matches,
+            rsum_bytes: 3,
+            checksum_bytes: 8,
+            url: String::new(),
+            sha1: String::new(),
+            blocks: Vec::new(),
+        };
+        let mask = control.rsum_mask();
+        control.blocks = target
+            .chunks(block_size)
+            .map(|chunk| {
+                let mut block = chunk.to_vec();
+                block.resize(block_size, 0);
+                BlockSum {
+                    rsum: Rsum::new(&block).value() & mask,
+                    checksum: md4(&block)[..8].to_vec(),
+                }
+            })
+            .collect();
+        control
+    }
+
+    #[test]
+    fn md4_test_vectors() {
+        let vectors = [
+            ("", "31d6cfe0d16ae931b73c59d7e0c089c0"),
+            ("a", "bde52cb31de33e46245e05fbdbd6fb24"),
+            ("abc", "a448017aaf21d8525fc10ae87aa6729d"),
+            ("message digest", "d9130a8164549fe818874806e1c7014b"),
+            (
+                "abcdefghijklmnopqrstuvwxyz",
+                "d79e1c308aa5bbcdeea8ed63df412da9",
+            ),
+            (
+                "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789",
+                "043f8582f241db351ce627e153e7f0e4",
+            ),
+            (
+                "12345678901234567890123456789012345678901234567890123456789012345678901234567890",
+                "e33b4ddc9c38f2199c3e7b164fcc0536",
+            ),
+        ];
+        for (input, digest) in vectors {
+            assert_eq!(hex(&md4(input.as_bytes())), digest, "MD4 of {:?}", input);
+        }
+    }
+
+    #[test]
+    fn rolled_rsum_matches_computed_rsum() {
+        let data = random_bytes(1000, 1);
+        let block_size = 64;
+
+        let mut rsum = Rsum::new(&data[..block_size]);
+        for pos in 1..=data.len() - block_size {
+            rsum.roll(data[pos - 1], data[pos - 1 + block_size], block_size);
+            assert_eq!(
+                rsum.value(),
+                Rsum::new(&data[pos..pos + block_size]).value(),
+                "rsum at offset {}",
+                pos
+            );
+        }
+    }
+
+    fn control_file(header: &str, checksums: &[u8]) -> Vec<u8> {
+        [header.as_bytes(), b"\n\n", checksums].concat()
+    }
+
+    const HEADER: &str = "zsync: 0.6.2\n\
+        Filename: app.AppImage\n\
+        MTime: Sat, 01 Jan 2022 00:00:00 +0000\n\
+        Blocksize: 4\n\
+        Length: 10\n\
+        Hash-Lengths: 1,2,4\n\
+        URL: app.AppImage\n\
+        SHA-1: 0A4D55A8D778E5022FAB701977C5D840BBC486D0";
+
+    #[test]
+    fn parses_control_file() {
+        let checksums = [
+            0x12, 0x34, 1, 2, 3, 4, //
+            0x56, 0x78, 5, 6, 7, 8, //
+            0x9a, 0xbc, 9, 10, 11, 12,
+        ];
+        let control = ControlFile::parse(
+            &control_file(HEADER, &checksums),
+            "https://example.com/releases/app.AppImage.zsync",
+        )
+        .unwrap();
+
+        assert_eq!(control.block_size, 4);
+        assert_eq!(control.length, 10);
+        assert_eq!(control.seq_matches, 1);
+        assert_eq!(control.rsum_bytes, 2);
+        assert_eq!(control.checksum_bytes, 4);
+        assert_eq!(control.url, "https://example.com/releases/app.AppImage");
+        assert_eq!(control.sha1, "0a4d55a8d778e5022fab701977c5d840bbc486d0");
+        assert_eq!(control.rsum_mask(), 0xffff);
+        assert_eq!(control.blocks.len(), 3);
+        assert_eq!(control.blocks[1].rsum, 0x5678);
+        assert_eq!(control.blocks[2].checksum, [9, 10, 11, 12]);
+    }
+
+    #[test]
+    fn rejects_invalid_control_files() {
+        let checksums = [0u8; 18];
+        let base_url = "https://example.com/app.AppImage.zsync";
+        let parse = |header: &str, checksums: &[u8]| {
+            ControlFile::parse(&control_file(header, checksums), base_url)
+        };
+
+        assert!(ControlFile::parse(HEADER.as_bytes(), base_url).is_err());
+        assert!(parse(HEADER, &checksums[..17]).is_err());
+        for field in ["Blocksize", "Length", "Hash-Lengths", "URL", "SHA-1"] {
+            let header: Vec<&str> = HEADER
+                .lines()
+                .filter(|line| !line.starts_with(field))
+                .collect();
+            assert!(
+                parse(&header.join("\n"), &checksums).is_err(),
+                "missing {}",
+                field
+            );
+        }
+        for (from, to) in [
+            ("Blocksize: 4", "Blocksize: 0"),
+            ("Blocksize: 4", "Blocksize: four"),
+            ("Length: 10", "Length: -1"),
+            ("Hash-Lengths: 1,2,4", "Hash-Lengths: 1,2"),
+            ("Hash-Lengths: 1,2,4", "Hash-Lengths: 3,2,4"),
+            ("Hash-Lengths: 1,2,4", "Hash-Lengths: 1,5,4"),
+            ("Hash-Lengths: 1,2,4", "Hash-Lengths: 1,2,17"),
+        ] {
+            assert!(
+                parse(&HEADER.replace(from, to), &checksums).is_err(),
+                "{}",
+                to
+            );
+        }
+    }
+
+    #[test]
+    fn matches_shifted_and_changed_blocks() {
+        let block_size = 16;
+        // the last block is partial
+        let target = random_bytes(block_size * 40 + 5, 2);
+
+        // bytes inserted before block 3, block 10 changed and block 30 dropped
+        let mut seed = target[..3 * block_size].to_vec();
+        seed.extend(random_bytes(5, 3));
+        seed.extend(&target[3 * block_size..10 * block_size]);
+        seed.extend(random_bytes(block_size, 4));
+        seed.extend(&target[11 * block_size..30 * block_size]);
+        seed.extend(&target[31 * block_size..40 * block_size]);
+
+        for seq_matches in [1, 2] {
+            let control = control_for(&target, block_size, seq_matches);
+            let found = match_blocks(&control, &seed);
+            assert_eq!(found.len(), 41);
+
+            for (idx, offset) in found.iter().enumerate() {
+                match idx {
+                    10 | 30 | 40 => assert_eq!(*offset, None, "block {}", idx),
+                    _ => {
+                        let offset = offset.unwrap_or_else(|| panic!("block {} not found", idx));
+                        assert_eq!(
+                            seed[offset..offset + block_size],
+                            target[idx * block_size..(idx + 1) * block_size]
+                        );
+                    }
+                }
+            }
+            assert_eq!(found[2], Some(2 * block_size));
+            assert_eq!(found[3], Some(3 * block_size + 5));
+            assert_eq!(found[31], Some(30 * block_size + 5));
+
+            let ranges = missing_ranges(&control, &found);
+            for (idx, offset) in found.iter().enumerate() {
+                let start = (idx * block_size) as u64;
+                let covered = ranges.iter().any(|(s, e)| *s <= start && start < *e);
+                assert!(offset.is_some() || covered, "block {} isn't fetched", idx);
+            }
+            assert_eq!(ranges.last().unwrap().1, target.len() as u64);
+        }
+    }
+
+    #[test]
+    fn merges_close_missing_ranges() {
+        let block_size = 4;
+        let control = control_for(&random_bytes(block_size * 100, 5), block_size, 1);
+        let mut found: Vec<Option<usize>> = (0..100).map(Some).collect();
+        for idx in [2, 5, 50, 99] {
+            found[idx] = None;
+        }
+
+        assert_eq!(
+            missing_ranges(&control, &found),
+            [(8, 24), (200, 204), (396, 400)]
+        );
+    }
+}
@@ -0,0 +1,736 @@
+use std::{
+    env,
+    ffi::CString,
+    fs::{self, OpenOptions},
+    io,
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd},
+        unix::{
+            self,
+            ffi::{OsStrExt, OsStringExt},
+            fs::{OpenOptionsExt, PermissionsExt},
+            process::CommandExt,
+        },
+    },
+    path::{Path, PathBuf},
+    process::Command,
+    ptr,
+};
+
+use nix::{
+    sched::{unshare, CloneFlags},
+    unistd::{getgid, getuid},
+};
+use rusqlite::{params, Connection};
+
+use crate::{
+    config::get_config,
+    constants::{CAP_MKNOD, CAP_SYS_ADMIN},
+    database::models::{FromRow, InstalledPackage, PackageFileKind, SandboxProfile},
+    error::{ErrorContext, SoarError},
+    package::files::{collect_package_files, get_package_files, record_package_files},
+    utils::{find_in_path, shell_quote},
+    SoarResult,
+};
+
+/// Directory inside the install directory holding the launchers.
+const LAUNCHER_DIR: &str = ".sandbox";
+const TARGET_VAR: &str = "SOAR_SANDBOX_TARGET";
+
+/// System paths readable inside the sandbox.
+const SYSTEM_PATHS: [&str; 11] = [
+    "/usr", "/bin", "/sbin", "/lib", "/lib32", "/lib64", "/etc", "/opt", "/run", "/sys", "/proc",
+];
+
+const LANDLOCK_CREATE_RULESET_VERSION: u32 = 1 << 0;
+const LANDLOCK_RULE_PATH_BENEATH: u32 = 1;
+
+const ACCESS_FS_EXECUTE: u64 = 1 << 0;
+const ACCESS_FS_WRITE_FILE: u64 = 1 << 1;
+const ACCESS_FS_READ_FILE: u64 = 1 << 2;
+const ACCESS_FS_READ_DIR: u64 = 1 << 3;
+const ACCESS_FS_TRUNCATE: u64 = 1 << 14;
+
+const ACCESS_FS_READ: u64 = ACCESS_FS_EXECUTE | ACCESS_FS_READ_FILE | ACCESS_FS_READ_DIR;
+/// Access rights applicable to files, the rest only apply to directories.
+const ACCESS_FS_FILE: u64 =
+    ACCESS_FS_EXECUTE | ACCESS_FS_WRITE_FILE | ACCESS_FS_READ_FILE | ACCESS_FS_TRUNCATE;
+
+#[repr(C)]
+struct LandlockRulesetAttr {
+    handled_access_fs: u64,
+}
+
+#[repr(C, packed)]
+struct LandlockPathBeneathAttr {
+    allowed_access: u64,
+    parent_fd: i32,
+}
+
+pub fn get_sandbox_profile(
+    conn: &Connection,
+    repo_name: &str,
+    pkg_id: &str,
+    pkg_name: &str,
+) -> SoarResult<Option<SandboxProfile>> {
+    let mut stmt = conn.prepare(
+        "SELECT
+            repo_name, pkg_id, pkg_name, json(allow) AS allow,
+            json(allow_ro) AS allow_ro, network, isolate_home
+        FROM sandbox_profiles
+        WHERE repo_name = ? AND pkg_id = ? AND pkg_name = ?",
+    )?;
+    let mut rows = stmt.query_map(
+        params![repo_name, pkg_id, pkg_name],
+        SandboxProfile::from_row,
+    )?;
+    Ok(rows.next().transpose()?)
+}
+
+pub fn save_sandbox_profile(conn: &Connection, profile: &SandboxProfile) -> SoarResult<()> {
+    let allow = serde_json::to_string(&profile.allow).unwrap();
+    let allow_ro = serde_json::to_string(&profile.allow_ro).unwrap();
+
+    conn.execute(
+        "INSERT INTO sandbox_profiles (
+            repo_name, pkg_id, pkg_name, allow, allow_ro, network, isolate_home
+        )
+        VALUES (?, ?, ?, jsonb(?), jsonb(?), ?, ?)
+        ON CONFLICT (repo_name, pkg_id, pkg_name) DO UPDATE SET
+            allow = excluded.allow,
+            allow_ro = excluded.allow_ro,
+            network = excluded.network,
+            isolate_home = excluded.isolate_home",
+        params![
+            profile.repo_name,
+            profile.pkg_id,
+            profile.pkg_name,
+            allow,
+            allow_ro,
+            profile.network,
+            profile.isolate_home
+        ],
+    )?;
+
+    Ok(())
+}
+
+pub fn delete_sandbox_profile(
+    conn: &Connection,
+    repo_name: &str,
+    pkg_id: &str,
+    pkg_name: &str,
+) -> SoarResult<()> {
+    conn.execute(
+        "DELETE FROM sandbox_profiles WHERE repo_name = ? AND pkg_id = ? AND pkg_name = ?",
+        params![repo_name, pkg_id, pkg_name],
+    )?;
+    Ok(())
+}
+
+/// Home directory used to isolate the package from the user's home. The config
+/// and share directories are next to it, as set up for portable packages.
+///
+/// It is kept in the data directory of the profile so it survives updates and
+/// repairs of the package, unless the package links to a portable home.
+pub fn sandbox_home_dir(package: &InstalledPackage) -> SoarResult<PathBuf> {
+    let portable_home = PathBuf::from(&package.installed_path)
+        .join(&package.pkg_name)
+        .with_extension("home");
+    if portable_home.is_symlink() {
+        return Ok(portable_home);
+    }
+
+    Ok(get_config()
+        .get_profile(&package.profile)?
+        .get_root_path()?
+        .join("sandbox")
+        .join(&package.pkg_id)
+        .join(&package.pkg_name)
+        .with_extension("home"))
+}
+
+fn home_dirs(home: &Path) -> [PathBuf; 3] {
+    [
+        home.to_path_buf(),
+        home.with_extension("config"),
+        home.with_extension("share"),
+    ]
+}
+
+/// Reads the binary wrapped by the launcher.
+fn launcher_target(launcher: &Path) -> Option<PathBuf> {
+    let content = fs::read_to_string(launcher).ok()?;
+    let prefix = format!("{}=", TARGET_VAR);
+    let value = content
+        .lines()
+        .find_map(|line| line.strip_prefix(&prefix))?;
+    let value = value.strip_prefix('\'')?.strip_suffix('\'')?;
+    Some(PathBuf::from(value.replace(r"'\''", "'")))
+}
+
+fn write_launcher(
+    launcher: &Path,
+    target: &Path,
+    install_dir: &Path,
+    home: Option<&Path>,
+    profile: &SandboxProfile,
+    soar_exe: &Path,
+) -> SoarResult<()> {
+    let mut args = vec![
+        "sandbox-exec".to_string(),
+        "--package-dir".to_string(),
+        shell_quote(install_dir.to_string_lossy()),
+    ];
+    for path in &profile.allow {
+        args.extend(["--allow".to_string(), shell_quote(path)]);
+    }
+    for path in &profile.allow_ro {
+        args.extend(["--allow-ro".to_string(), shell_quote(path)]);
+    }
+    if !profile.network {
+        args.push("--no-network".to_string());
+    }
+    if let Some(home) = home {
+        args.extend(["--home".to_string(), shell_quote(home.to_string_lossy())]);
+    }
+
+    let content = format!(
+        "#!/bin/sh\n{}={}\nexec {} {} --argv0 \"$0\" -- \"${}\" \"$@\"\n",
+        TARGET_VAR,
+        shell_quote(target.to_string_lossy()),
+        shell_quote(soar_exe.to_string_lossy()),
+        args.join(" "),
+        TARGET_VAR
+    );
+
+    fs::write(launcher, content)
+        .with_context(|| format!("writing launcher {}", launcher.display()))?;
+    fs::set_permissions(launcher, fs::Permissions::from_mode(0o755))
+        .with_context(|| format!("setting permissions on {}", launcher.display()))?;
+
+    Ok(())
+}
+
+fn replace_link(link: &Path, target: &Path) -> SoarResult<()> {
+    fs::remove_file(link).with_context(|| format!("removing symlink {}", link.display()))?;
+    unix::fs::symlink(target, link).with_context(|| {
+        format!(
+            "creating symlink {} -> {}",
+            target.display(),
+            link.display()
+        )
+    })?;
+    Ok(())
+}
+
+/// Records the package files again, keeping the recorded paths outside of the
+/// install directory.
+fn rerecord_package_files(
+    conn: &Connection,
+    package: &InstalledPackage,
+    install_dir: &Path,
+) -> SoarResult<()> {
+    let extra_paths: Vec<PathBuf> = get_package_files(conn, package.id)?
+        .into_iter()
+        .map(|file| PathBuf::from(file.path))
+        .filter(|path| !path.starts_with(install_dir))
+        .collect();
+    let files = collect_package_files(install_dir, &extra_paths)?;
+    record_package_files(conn, package.id, &files)
+}
+
+/// Replaces the bin links of the package with launchers running the binaries
+/// in the sandbox described by the profile.
+pub fn apply_sandbox(
+    conn: &Connection,
+    package: &InstalledPackage,
+    profile: &SandboxProfile,
+    soar_exe: &Path,
+) -> SoarResult<()> {
+    let install_dir = PathBuf::from(&package.installed_path);
+    let launcher_dir = install_dir.join(LAUNCHER_DIR);
+    let bin_path = get_config().get_profile_bin_path(&package.profile)?;
+
+    fs::create_dir_all(&launcher_dir)
+        .with_context(|| format!("creating directory {}", launcher_dir.display()))?;
+
+    let home = if profile.isolate_home {
+        Some(sandbox_home_dir(package)?)
+    } else {
+        None
+    };
+    if let Some(ref home) = home {
+        for dir in home_dirs(home) {
+            // may be a link to the portable directory
+            if fs::symlink_metadata(&dir).is_err() {
+                fs::create_dir_all(&dir)
+                    .with_context(|| format!("creating directory {}", dir.display()))?;
+            }
+        }
+    }
+
+    for file in get_package_files(conn, package.id)? {
+        let link = PathBuf::from(&file.path);
+        if file.kind != PackageFileKind::Symlink || link.parent() != Some(bin_path.as_path()) {
+            continue;
+        }
+        let (Ok(target), Some(file_name)) = (fs::read_link(&link), link.file_name()) else {
+            continue;
+        };
+
+        let real_path = if target.starts_with(&launcher_dir) {
+            match launcher_target(&target) {
+                Some(real_path) => real_path,
+                None => continue,
+            }
+        } else if target.starts_with(&install_dir) {
+            target.clone()
+        } else {
+            continue;
+        };
+
+        let launcher = launcher_dir.join(file_name);
+        write_launcher(
+            &launcher,
+            &real_path,
+            &install_dir,
+            home.as_deref(),
+            profile,
+            soar_exe,
+        )?;
+        if target != launcher {
+            replace_link(&link, &launcher)?;
+        }
+    }
+
+    rerecord_package_files(conn, package, &install_dir)
+}
+
+/// Points the bin links of the package back to its binaries and removes the
+/// launchers.
+pub fn remove_sandbox(conn: &Connection, package: &InstalledPackage) -> SoarResult<()> {
+    let install_dir = PathBuf::from(&package.installed_path);
+    let launcher_dir = install_dir.join(LAUNCHER_DIR);
+
+    for file in get_package_files(conn, package.id)? {
+        let link = PathBuf::from(&file.path);
+        if file.kind != PackageFileKind::Symlink {
+            continue;
+        }
+        let Ok(target) = fs::read_link(&link) else {
+            continue;
+        };
+        if !target.starts_with(&launcher_dir) {
+            continue;
+        }
+        if let Some(real_path) = launcher_target(&target) {
+            replace_link(&link, &real_path)?;
+        }
+    }
+
+    if let Err(err) = fs::remove_dir_all(&launcher_dir) {
+        if err.kind() != io::ErrorKind::NotFound {
+            return Err(err)
+                .with_context(|| format!("removing directory {}", launcher_dir.display()))?;
+        }
+    }
+
+    rerecord_package_files(conn, package, &install_dir)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SandboxOptions {
+    /// Install directory of the package, readable inside the sandbox.
+    pub package_dir: PathBuf,
+    pub allow: Vec<PathBuf>,
+    pub allow_ro: Vec<PathBuf>,
+    pub network: bool,
+    /// Portable home directory replacing the user's home.
+    pub home: Option<PathBuf>,
+}
+
+impl SandboxOptions {
+    fn readable_paths(&self) -> Vec<PathBuf> {
+        SYSTEM_PATHS
+            .iter()
+            .map(PathBuf::from)
+            .chain(self.allow_ro.iter().cloned())
+            .chain(std::iter::once(self.package_dir.clone()))
+            .collect()
+    }
+
+    fn writable_paths(&self) -> Vec<PathBuf> {
+        let mut paths = self.allow.clone();
+        paths.push(PathBuf::from("/tmp"));
+        match self.home {
+            Some(ref home) => paths.extend(home_dirs(home)),
+            None => paths.extend(env::var_os("HOME").map(PathBuf::from)),
+        }
+        paths.extend(runtime_sockets());
+        paths
+    }
+}
+
+/// Sockets in the runtime directory needed by graphical apps. The directory
+/// itself isn't shared, as the session bus and the service manager listening
+/// in it could run commands outside of the sandbox; other sockets have to be
+/// allowed explicitly.
+fn runtime_sockets() -> Vec<PathBuf> {
+    let Some(runtime_dir) = env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from) else {
+        return Vec::new();
+    };
+
+    let mut sockets = vec![runtime_dir.join("pulse")];
+    sockets.extend(env::var_os("WAYLAND_DISPLAY").map(|display| runtime_dir.join(display)));
+    sockets
+}
+
+fn bwrap_command(
+    bwrap: &Path,
+    options: &SandboxOptions,
+    command: &Path,
+    argv0: Option<&str>,
+) -> Command {
+    let mut cmd = Command::new(bwrap);
+    cmd.args(["--die-with-parent", "--unshare-all"]);
+    if options.network {
+        cmd.arg("--share-net");
+    }
+
+    for path in options.readable_paths() {
+        if path != Path::new("/proc") {
+            cmd.arg("--ro-bind-try").arg(&path).arg(&path);
+        }
+    }
+    cmd.args(["--proc", "/proc", "--dev", "/dev"]);
+    // hides the sockets of the runtime directory readable through /run
+    if let Some(runtime_dir) = env::var_os("XDG_RUNTIME_DIR") {
+        cmd.arg("--tmpfs").arg(&runtime_dir);
+        for path in &options.allow_ro {
+            if path.starts_with(&runtime_dir) {
+                cmd.arg("--ro-bind-try").arg(path).arg(path);
+            }
+        }
+    }
+    for path in options.writable_paths() {
+        cmd.arg("--bind-try").arg(&path).arg(&path);
+    }
+    if let Some(argv0) = argv0 {
+        cmd.arg("--argv0").arg(argv0);
+    }
+
+    cmd.arg("--").arg(command);
+    cmd
+}
+
+fn add_landlock_rule(ruleset: &OwnedFd, path: &Path, access: u64) -> SoarResult<()> {
+    // not every path exists on every system
+    let Ok(file) = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_PATH | libc::O_CLOEXEC)
+        .open(path)
+    else {
+        return Ok(());
+    };
+
+    let is_dir = file.metadata().is_ok_and(|metadata| metadata.is_dir());
+    let attr = LandlockPathBeneathAttr {
+        allowed_access: if is_dir {
+            access
+        } else {
+            access & ACCESS_FS_FILE
+        },
+        parent_fd: file.as_raw_fd(),
+    };
+
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_landlock_add_rule,
+            ruleset.as_raw_fd(),
+            LANDLOCK_RULE_PATH_BENEATH,
+            &attr as *const LandlockPathBeneathAttr,
+            0u32,
+        )
+    };
+    if ret != 0 {
+        return Err(io::Error::last_os_error())
+            .with_context(|| format!("adding sandbox rule for {}", path.display()))?;
+    }
+
+    Ok(())
+}
+
+fn landlock_ruleset(options: &SandboxOptions) -> SoarResult<OwnedFd> {
+    let abi = unsafe {
+        libc::syscall(
+            libc::SYS_landlock_create_ruleset,
+            ptr::null::<LandlockRulesetAttr>(),
+            0usize,
+            LANDLOCK_CREATE_RULESET_VERSION,
+        )
+    };
+    if abi < 1 {
+        return Err(SoarError::SandboxUnavailable(
+            "neither bubblewrap nor landlock is available".into(),
+        ));
+    }
+
+    // each ABI version handles one more access right
+    let handled_access = match abi {
+        1 => (1 << 13) - 1,
+        2 => (1 << 14) - 1,
+        _ => (1 << 15) - 1,
+    };
+    let attr = LandlockRulesetAttr {
+        handled_access_fs: handled_access,
+    };
+    let fd = unsafe {
+        libc::syscall(
+            libc::SYS_landlock_create_ruleset,
+            &attr as *const LandlockRulesetAttr,
+            size_of::<LandlockRulesetAttr>(),
+            0u32,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error())
+            .with_context(|| "creating landlock ruleset".into())?;
+    }
+    let ruleset = unsafe { OwnedFd::from_raw_fd(fd as i32) };
+
+    for path in options.readable_paths() {
+        add_landlock_rule(&ruleset, &path, ACCESS_FS_READ)?;
+    }
+    add_landlock_rule(
+        &ruleset,
+        Path::new("/dev"),
+        ACCESS_FS_READ | ACCESS_FS_WRITE_FILE,
+    )?;
+    for path in options.writable_paths() {
+        add_landlock_rule(&ruleset, &path, handled_access)?;
+    }
+
+    Ok(ruleset)
+}
+
+fn write_proc_file(path: &CString, content: &[u8]) -> io::Result<()> {
+    let fd = unsafe { libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let written = unsafe { libc::write(fd, content.as_ptr().cast(), content.len()) };
+    unsafe { libc::close(fd) };
+    if written < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Socket of the runtime directory to mount back over the empty directory
+/// hiding the others.
+struct RuntimeSocket {
+    path: CString,
+    is_dir: bool,
+    /// Descriptor reserved for the socket, as it has to be opened in the
+    /// mount namespace of the sandbox to be mounted.
+    fd: OwnedFd,
+    /// `/proc/self/fd` path of the reserved descriptor.
+    source: CString,
+}
+
+fn runtime_socket(path: &Path) -> Option<RuntimeSocket> {
+    let is_dir = fs::metadata(path).ok()?.is_dir();
+    let fd = OwnedFd::from(
+        OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_PATH | libc::O_CLOEXEC)
+            .open(path)
+            .ok()?,
+    );
+
+    Some(RuntimeSocket {
+        path: CString::new(path.as_os_str().as_bytes()).ok()?,
+        is_dir,
+        source: CString::new(format!("/proc/self/fd/{}", fd.as_raw_fd())).unwrap(),
+        fd,
+    })
+}
+
+/// Mounts an empty directory over the runtime directory, keeping only the
+/// given sockets.
+fn hide_runtime_dir(runtime_dir: &CString, sockets: &[RuntimeSocket]) -> io::Result<()> {
+    let check = |ret: libc::c_int| {
+        if ret != 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    };
+
+    unsafe {
+        check(libc::mount(
+            c"none".as_ptr(),
+            c"/".as_ptr(),
+            ptr::null(),
+            libc::MS_REC | libc::MS_PRIVATE,
+            ptr::null(),
+        ))?;
+
+        // opened before the runtime directory is hidden
+        for socket in sockets {
+            let fd = libc::open(socket.path.as_ptr(), libc::O_PATH | libc::O_CLOEXEC);
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let ret = libc::dup3(fd, socket.fd.as_raw_fd(), libc::O_CLOEXEC);
+            libc::close(fd);
+            if ret < 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        check(libc::mount(
+            c"tmpfs".as_ptr(),
+            runtime_dir.as_ptr(),
+            c"tmpfs".as_ptr(),
+            libc::MS_NOSUID | libc::MS_NODEV,
+            c"mode=0700".as_ptr().cast(),
+        ))?;
+
+        for socket in sockets {
+            if socket.is_dir {
+                check(libc::mkdir(socket.path.as_ptr(), 0o700))?;
+            } else {
+                let fd = libc::open(
+                    socket.path.as_ptr(),
+                    libc::O_CREAT | libc::O_WRONLY | libc::O_CLOEXEC,
+                    0o600,
+                );
+                if fd < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                libc::close(fd);
+            }
+            check(libc::mount(
+                socket.source.as_ptr(),
+                socket.path.as_ptr(),
+                ptr::null(),
+                libc::MS_BIND,
+                ptr::null(),
+            ))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn landlock_command(options: &SandboxOptions, command: &Path) -> SoarResult<Command> {
+    let ruleset = landlock_ruleset(options)?;
+    let network = options.network;
+
+    // landlock doesn't restrict connecting to sockets, so the runtime
+    // directory is hidden in a mount namespace instead
+    let runtime_dir = env::var_os("XDG_RUNTIME_DIR")
+        .filter(|dir| Path::new(dir).is_dir())
+        .and_then(|dir| CString::new(dir.into_vec()).ok());
+    let sockets: Vec<RuntimeSocket> = match env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => runtime_sockets()
+            .iter()
+            .chain(options.allow.iter().chain(&options.allow_ro))
+            .filter(|path| path.starts_with(&dir))
+            .filter_map(|path| runtime_socket(path))
+            .collect(),
+        None => Vec::new(),
+    };
+
+    // prepared beforehand, as the child can't safely allocate after fork
+    let setgroups = CString::new("/proc/self/setgroups").unwrap();
+    let uid_map_path = CString::new("/proc/self/uid_map").unwrap();
+    let gid_map_path = CString::new("/proc/self/gid_map").unwrap();
+    let uid_map = format!("{0} {0} 1", getuid());
+    let gid_map = format!("{0} {0} 1", getgid());
+
+    let mut cmd = Command::new(command);
+    unsafe {
+        cmd.pre_exec(move || {
+            if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            if !network || runtime_dir.is_some() {
+                // namespaces require a user namespace when unprivileged
+                let mut flags = CloneFlags::CLONE_NEWUSER;
+                if !network {
+                    flags |= CloneFlags::CLONE_NEWNET;
+                }
+                if runtime_dir.is_some() {
+                    flags |= CloneFlags::CLONE_NEWNS;
+                }
+                unshare(flags)?;
+                write_proc_file(&setgroups, b"deny")?;
+                write_proc_file(&uid_map_path, uid_map.as_bytes())?;
+                write_proc_file(&gid_map_path, gid_map.as_bytes())?;
+
+                if let Some(ref runtime_dir) = runtime_dir {
+                    hide_runtime_dir(runtime_dir, &sockets)?;
+                }
+
+                // capabilities granted by the user namespace would allow
+                // mounting over or creating devices to escape the restrictions
+                for cap in [CAP_SYS_ADMIN, CAP_MKNOD] {
+                    if libc::prctl(libc::PR_CAPBSET_DROP, cap, 0, 0, 0) != 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                }
+            }
+
+            if libc::syscall(libc::SYS_landlock_restrict_self, ruleset.as_raw_fd(), 0u32) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            Ok(())
+        });
+    }
+
+    Ok(cmd)
+}
+
+/// Runs the command in the sandbox, using bubblewrap if available and falling
+/// back to landlock. Returns the exit code of the command.
+pub fn run_sandboxed(
+    options: &SandboxOptions,
+    command: &Path,
+    argv0: Option<&str>,
+    args: &[String],
+) -> SoarResult<i32> {
+    let mut cmd = match find_in_path("bwrap") {
+        Some(bwrap) => {
+            let mut cmd = bwrap_command(&bwrap, options, command, argv0);
+            cmd.args(args);
+            cmd
+        }
+        None => {
+            let mut cmd = landlock_command(options, command)?;
+            if let Some(argv0) = argv0 {
+                cmd.arg0(argv0);
+            }
+            cmd.args(args);
+            cmd
+        }
+    };
+
+    if let Some(ref home) = options.home {
+        let [home, config, share] = home_dirs(home);
+        cmd.env("HOME", home)
+            .env("XDG_CONFIG_HOME", config)
+            .env("XDG_DATA_HOME", share);
+    }
+    // FUSE mounts need setuid fusermount, which can't gain privileges here
+    cmd.env("APPIMAGE_EXTRACT_AND_RUN", "1");
+
+    let status = cmd
+        .status()
+        .with_context(|| format!("executing {} in sandbox", command.display()))?;
+    Ok(status.code().unwrap_or(1))
+}
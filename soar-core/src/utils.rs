@@ -13,7 +13,8 @@ use nix::unistd::{geteuid, User};
 use tracing::info;
 
 use crate::{
-    config::get_config,
+    config::{get_config, is_system_mode},
+    constants::{SYSTEM_DATA_PATH, SYSTEM_DESKTOP_PATH},
     error::{ErrorContext, SoarError},
     SoarResult,
 };
@@ -96,23 +97,33 @@ pub fn format_bytes(bytes: u64) -> String {
     }
 }
 
+/// Parses a size such as `512`, `20MB` or `1.5GiB` into bytes.
+///
+/// `KB`/`MB`/`GB` are decimal, while `KiB`/`MiB`/`GiB` and the single-letter
+/// `K`/`M`/`G` are binary. A bare number is taken as bytes.
 pub fn parse_size(size_str: &str) -> Option<u64> {
-    let size_str = size_str.trim();
+    let size_str = size_str.trim().to_uppercase();
     let units = [
-        ("B", 1u64),
+        ("KIB", 1024u64),
+        ("MIB", 1024u64 * 1024),
+        ("GIB", 1024u64 * 1024 * 1024),
         ("KB", 1000u64),
         ("MB", 1000u64 * 1000),
         ("GB", 1000u64 * 1000 * 1000),
-        ("KiB", 1024u64),
-        ("MiB", 1024u64 * 1024),
-        ("GiB", 1024u64 * 1024 * 1024),
+        ("K", 1024u64),
+        ("M", 1024u64 * 1024),
+        ("G", 1024u64 * 1024 * 1024),
+        ("B", 1u64),
+        ("", 1u64),
     ];
 
     for (unit, multiplier) in &units {
-        let size_str = size_str.to_uppercase();
-        if size_str.ends_with(unit) {
-            let number_part = size_str.trim_end_matches(unit).trim();
+        if let Some(number_part) = size_str.strip_suffix(unit) {
+            let number_part = number_part.trim();
             if let Ok(num) = number_part.parse::<f64>() {
+                if num.is_sign_negative() || !num.is_finite() {
+                    return None;
+                }
                 return Some((num * (*multiplier as f64)) as u64);
             }
         }
@@ -121,6 +132,11 @@ pub fn parse_size(size_str: &str) -> Option<u64> {
     None
 }
 
+/// Quotes a value as a single word for POSIX shells (and fish).
+pub fn shell_quote<S: AsRef<str>>(value: S) -> String {
+    format!("'{}'", value.as_ref().replace('\'', r"'\''"))
+}
+
 pub fn calculate_checksum<P: AsRef<Path>>(file_path: P) -> Result<String> {
     let file_path = file_path.as_ref();
     let mut hasher = blake3::Hasher::new();
@@ -243,19 +259,67 @@ pub fn remove_broken_symlinks() -> Result<()> {
         Ok(())
     };
 
+    // the stem of `<id>-soar.metainfo.xml` still has the `.metainfo` suffix
+    let mut soar_metainfo_action = |path: &Path| -> SoarResult<()> {
+        if let Some(filename) = path.file_name().and_then(|s| s.to_str()) {
+            if filename.ends_with("-soar.metainfo.xml") {
+                return remove_action(path);
+            }
+        }
+        Ok(())
+    };
+
     process_dir(&get_config().get_bin_path()?, &mut remove_action)?;
     process_dir(desktop_dir(), &mut soar_files_action)?;
     process_dir(icons_dir(), &mut soar_files_action)?;
+    process_dir(format!("{}/packages", mime_dir()), &mut soar_files_action)?;
+    process_dir(metainfo_dir(), &mut soar_metainfo_action)?;
 
     Ok(())
 }
 
+/// Finds an executable in `PATH`.
+pub fn find_in_path(name: &str) -> Option<PathBuf> {
+    env::var_os("PATH").and_then(|paths| {
+        env::split_paths(&paths)
+            .map(|dir| dir.join(name))
+            .find(|path| path.is_file())
+    })
+}
+
+/// Whether the FUSE device needed to mount AppImages is available.
+pub fn fuse_available() -> bool {
+    Path::new("/dev/fuse").exists()
+}
+
+/// Base directory for desktop integration files, shared by all users in
+/// system mode.
+pub fn integration_data_path() -> String {
+    if is_system_mode() {
+        SYSTEM_DATA_PATH.to_string()
+    } else {
+        home_data_path()
+    }
+}
+
 pub fn desktop_dir() -> String {
-    format!("{}/applications", home_data_path())
+    if is_system_mode() {
+        SYSTEM_DESKTOP_PATH.to_string()
+    } else {
+        format!("{}/applications", integration_data_path())
+    }
 }
 
 pub fn icons_dir() -> String {
-    format!("{}/icons/hicolor", home_data_path())
+    format!("{}/icons/hicolor", integration_data_path())
+}
+
+pub fn mime_dir() -> String {
+    format!("{}/mime", integration_data_path())
+}
+
+pub fn metainfo_dir() -> String {
+    format!("{}/metainfo", integration_data_path())
 }
 
 /// Retrieves the platform string in the format `ARCH-Os`.
@@ -339,3 +403,29 @@ pub fn apply_sig_variants(patterns: Vec<String>) -> Vec<String> {
         })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_sizes() {
+        assert_eq!(parse_size("512"), Some(512));
+        assert_eq!(parse_size("512B"), Some(512));
+        assert_eq!(parse_size("1MB"), Some(1_000_000));
+        assert_eq!(parse_size("1MiB"), Some(1 << 20));
+        assert_eq!(parse_size("1m"), Some(1 << 20));
+        assert_eq!(parse_size("1.5 K"), Some(1536));
+        assert_eq!(parse_size("2gib"), Some(2 << 30));
+        assert_eq!(parse_size("1TB"), None);
+        assert_eq!(parse_size("-1M"), None);
+        assert_eq!(parse_size(""), None);
+    }
+
+    #[test]
+    fn quotes_shell_words() {
+        assert_eq!(shell_quote("/opt/my app"), "'/opt/my app'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+        assert_eq!(shell_quote(""), "''");
+    }
+}
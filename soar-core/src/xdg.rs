@@ -0,0 +1,316 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{self, File},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    time::SystemTime,
+};
+
+use tracing::debug;
+
+use crate::{
+    config::is_system_mode,
+    desktop_entry::{DesktopEntry, MAIN_GROUP},
+    error::{ErrorContext, SoarError},
+    utils::{desktop_dir, find_in_path, home_config_path, icons_dir, mime_dir},
+    SoarResult,
+};
+
+const DEFAULT_APPLICATIONS_GROUP: &str = "Default Applications";
+const ADDED_ASSOCIATIONS_GROUP: &str = "Added Associations";
+
+/// Marks the icon theme as modified. Icon theme caches older than the theme
+/// directory are ignored, so new icons show up without regenerating them.
+pub fn refresh_icon_theme() -> SoarResult<()> {
+    let icons_dir = icons_dir();
+    let icons_dir = Path::new(&icons_dir);
+    if !icons_dir.is_dir() {
+        return Ok(());
+    }
+
+    File::open(icons_dir)
+        .and_then(|dir| dir.set_modified(SystemTime::now()))
+        .with_context(|| format!("updating modification time of {}", icons_dir.display()))
+}
+
+fn collect_mime_handlers(
+    dir: &Path,
+    prefix: &str,
+    handlers: &mut BTreeMap<String, BTreeSet<String>>,
+) -> SoarResult<()> {
+    for entry in
+        fs::read_dir(dir).with_context(|| format!("reading directory {}", dir.display()))?
+    {
+        let path = entry
+            .with_context(|| format!("reading entry from directory {}", dir.display()))?
+            .path();
+        let file_name = path.file_name().unwrap().to_string_lossy();
+
+        if path.is_dir() {
+            collect_mime_handlers(&path, &format!("{prefix}{file_name}-"), handlers)?;
+            continue;
+        }
+        if !file_name.ends_with(".desktop") {
+            continue;
+        }
+
+        // broken symlinks of removed packages are skipped
+        let Ok(content) = fs::read_to_string(&path) else {
+            continue;
+        };
+        let desktop_id = format!("{prefix}{file_name}");
+        let Ok(entry) = DesktopEntry::parse(&content) else {
+            continue;
+        };
+        for mime_type in entry.get_list(MAIN_GROUP, "MimeType") {
+            handlers
+                .entry(mime_type.to_string())
+                .or_default()
+                .insert(desktop_id.clone());
+        }
+    }
+    Ok(())
+}
+
+/// Regenerates `mimeinfo.cache` of the applications directory, the same way
+/// as `update-desktop-database`.
+pub fn update_desktop_database() -> SoarResult<()> {
+    let desktop_dir = desktop_dir();
+    let desktop_dir = Path::new(&desktop_dir);
+    if !desktop_dir.is_dir() {
+        return Ok(());
+    }
+
+    let mut handlers = BTreeMap::new();
+    collect_mime_handlers(desktop_dir, "", &mut handlers)?;
+
+    let mut content = String::from("[MIME Cache]\n");
+    for (mime_type, desktop_ids) in handlers {
+        content.push_str(&mime_type);
+        content.push('=');
+        for desktop_id in desktop_ids {
+            content.push_str(&desktop_id);
+            content.push(';');
+        }
+        content.push('\n');
+    }
+
+    let cache_path = desktop_dir.join("mimeinfo.cache");
+    fs::write(&cache_path, content).with_context(|| format!("writing {}", cache_path.display()))?;
+    Ok(())
+}
+
+/// Rebuilds the MIME database with `update-mime-database` if the package
+/// files changed since it was last built.
+pub fn update_mime_database() -> SoarResult<()> {
+    let mime_dir = PathBuf::from(mime_dir());
+    let packages_dir = mime_dir.join("packages");
+    let Ok(packages_modified) = packages_dir.metadata().and_then(|m| m.modified()) else {
+        return Ok(());
+    };
+    let is_stale = mime_dir
+        .join("mime.cache")
+        .metadata()
+        .and_then(|m| m.modified())
+        .map_or(true, |cache_modified| cache_modified < packages_modified);
+    if !is_stale {
+        return Ok(());
+    }
+
+    let Some(update_mime_database) = find_in_path("update-mime-database") else {
+        debug!("update-mime-database not found, skipping MIME database update");
+        return Ok(());
+    };
+    let status = Command::new(&update_mime_database)
+        .arg(&mime_dir)
+        .stdout(Stdio::null())
+        .status()
+        .with_context(|| format!("executing {}", update_mime_database.display()))?;
+    if !status.success() {
+        return Err(SoarError::Custom(format!(
+            "update-mime-database exited with {}",
+            status
+        )));
+    }
+    Ok(())
+}
+
+/// Refreshes the indexes used by launchers after desktop files, icons or MIME
+/// types were added or removed. Failures are not fatal, the indexes are only
+/// caches.
+pub fn refresh_desktop_integration() {
+    if let Err(err) = refresh_icon_theme() {
+        debug!("Failed to refresh icon theme: {}", err);
+    }
+    if let Err(err) = update_desktop_database() {
+        debug!("Failed to update desktop database: {}", err);
+    }
+    if let Err(err) = update_mime_database() {
+        debug!("Failed to update MIME database: {}", err);
+    }
+    if let Err(err) = prune_default_applications() {
+        debug!("Failed to prune default applications: {}", err);
+    }
+}
+
+/// Path of the `mimeapps.list` holding the default applications.
+pub fn mimeapps_list_path() -> PathBuf {
+    if is_system_mode() {
+        PathBuf::from("/etc/xdg/mimeapps.list")
+    } else {
+        PathBuf::from(home_config_path()).join("mimeapps.list")
+    }
+}
+
+/// Lines of `mimeapps.list`. MIME types aren't valid desktop entry keys, so
+/// it's edited line by line, keeping everything else as it is.
+struct MimeAppsList {
+    lines: Vec<String>,
+}
+
+impl MimeAppsList {
+    fn read(path: &Path) -> SoarResult<Self> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err).with_context(|| format!("reading {}", path.display())),
+        };
+        Ok(Self {
+            lines: content.lines().map(String::from).collect(),
+        })
+    }
+
+    fn write(&self, path: &Path) -> SoarResult<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("creating directory {}", parent.display()))?;
+        }
+        let mut content = self.lines.join("\n");
+        content.push('\n');
+        fs::write(path, content).with_context(|| format!("writing {}", path.display()))
+    }
+
+    /// Returns the range of the lines of the group, excluding its header.
+    fn group_range(&self, group: &str) -> Option<(usize, usize)> {
+        let header = format!("[{}]", group);
+        let start = self.lines.iter().position(|line| line.trim() == header)? + 1;
+        let end = self.lines[start..]
+            .iter()
+            .position(|line| line.trim_start().starts_with('['))
+            .map_or(self.lines.len(), |idx| start + idx);
+        Some((start, end))
+    }
+
+    /// Puts the desktop id first in the preference list of the key, keeping
+    /// the previous ones to fall back to.
+    fn prepend_desktop_id(&mut self, group: &str, key: &str, desktop_id: &str) {
+        let Some((start, end)) = self.group_range(group) else {
+            if self
+                .lines
+                .last()
+                .is_some_and(|line| !line.trim().is_empty())
+            {
+                self.lines.push(String::new());
+            }
+            self.lines.push(format!("[{}]", group));
+            self.lines.push(format!("{}={};", key, desktop_id));
+            return;
+        };
+
+        for line in &mut self.lines[start..end] {
+            let Some((k, value)) = line.split_once('=') else {
+                continue;
+            };
+            if k.trim() != key {
+                continue;
+            }
+            let mut ids = vec![desktop_id];
+            ids.extend(
+                value
+                    .split(';')
+                    .map(str::trim)
+                    .filter(|id| !id.is_empty() && *id != desktop_id),
+            );
+            *line = format!("{}={};", key, ids.join(";"));
+            return;
+        }
+
+        let position = self.lines[start..end]
+            .iter()
+            .rposition(|line| !line.trim().is_empty())
+            .map_or(start, |idx| start + idx + 1);
+        self.lines
+            .insert(position, format!("{}={};", key, desktop_id));
+    }
+
+    /// Removes the desktop ids matching the predicate from the group, and the
+    /// keys left without any. Returns whether anything was removed.
+    fn remove_desktop_ids<F: Fn(&str) -> bool>(&mut self, group: &str, predicate: F) -> bool {
+        let Some((start, end)) = self.group_range(group) else {
+            return false;
+        };
+
+        let mut changed = false;
+        let mut idx = start;
+        let mut end = end;
+        while idx < end {
+            let Some((key, value)) = self.lines[idx].split_once('=') else {
+                idx += 1;
+                continue;
+            };
+            let ids: Vec<&str> = value
+                .split(';')
+                .map(str::trim)
+                .filter(|id| !id.is_empty())
+                .collect();
+            let kept: Vec<&str> = ids.iter().copied().filter(|id| !predicate(id)).collect();
+            if kept.len() == ids.len() {
+                idx += 1;
+                continue;
+            }
+
+            changed = true;
+            if kept.is_empty() {
+                self.lines.remove(idx);
+                end -= 1;
+            } else {
+                self.lines[idx] = format!("{}={};", key, kept.join(";"));
+                idx += 1;
+            }
+        }
+        changed
+    }
+}
+
+/// Sets the desktop file as the default application of the MIME types in
+/// `mimeapps.list`.
+pub fn set_default_application(desktop_id: &str, mime_types: &[String]) -> SoarResult<()> {
+    let path = mimeapps_list_path();
+    let mut list = MimeAppsList::read(&path)?;
+    for mime_type in mime_types {
+        list.prepend_desktop_id(DEFAULT_APPLICATIONS_GROUP, mime_type, desktop_id);
+    }
+    list.write(&path)
+}
+
+/// Removes the desktop files of removed packages from `mimeapps.list`, so the
+/// previous defaults apply again.
+pub fn prune_default_applications() -> SoarResult<()> {
+    let path = mimeapps_list_path();
+    if !path.exists() {
+        return Ok(());
+    }
+
+    let desktop_dir = PathBuf::from(desktop_dir());
+    let is_removed = |desktop_id: &str| {
+        desktop_id.ends_with("-soar.desktop") && !desktop_dir.join(desktop_id).exists()
+    };
+
+    let mut list = MimeAppsList::read(&path)?;
+    let removed_defaults = list.remove_desktop_ids(DEFAULT_APPLICATIONS_GROUP, is_removed);
+    let removed_associations = list.remove_desktop_ids(ADDED_ASSOCIATIONS_GROUP, is_removed);
+    if removed_defaults || removed_associations {
+        list.write(&path)?;
+    }
+    Ok(())
+}